@@ -0,0 +1,90 @@
+use std::sync::Arc;
+
+use tokio_util::sync::CancellationToken;
+
+use tracing::{error, info, warn};
+
+use clap::Parser;
+
+use dbatrs_shared::{ConfArgs, SurrealEngine, TotalConf, migrate};
+
+// Runs the portal and game together in one process against an embedded SurrealDB,
+// for local development and end-to-end tests. No SurrealDB server is needed.
+#[derive(Parser, Debug)]
+#[command(name = "dbatrs-dev", version)]
+struct Cli {
+    #[command(flatten)]
+    conf: ConfArgs
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let cli = Cli::parse();
+
+    let mut conf: TotalConf = match cli.conf.load() {
+        Ok(conf) => conf,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    if cli.conf.check_config {
+        println!("Configuration for mode '{}' in {} is valid.", cli.conf.mode, cli.conf.config_dir.display());
+        return Ok(());
+    }
+
+    let remote = !conf.surreal.engine.is_embedded();
+    if remote {
+        conf.surreal.engine = SurrealEngine::Memory;
+    }
+
+    let conf = Arc::new(conf);
+
+    dbatrs_shared::logging::init(&conf, "dev")?;
+
+    info!("dbatrs-dev starting up...");
+    if remote {
+        warn!("surreal.engine is 'remote'; using an in-memory database instead.");
+    }
+    info!("Using embedded database at {}", dbatrs_shared::db::endpoint(&conf));
+
+    let db = dbatrs_shared::db::connect_root(&conf).await?;
+    match migrate::migrate(&db).await {
+        Ok(_) => info!("Database schema is at version {}.", migrate::latest_version()),
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    }
+
+    let shutdown = CancellationToken::new();
+    let signal = async {
+        tokio::select! {
+            _ = dbatrs_shared::shutdown::wait_for_signal() => shutdown.cancel(),
+            _ = shutdown.cancelled() => {}
+        }
+    };
+    // If either half stops on its own, take the other down with it.
+    let game = async {
+        let result = dbatrs_game::run(conf.clone(), db.clone(), shutdown.clone().cancelled_owned()).await;
+        shutdown.cancel();
+        result
+    };
+    let portal = async {
+        let result = dbatrs_portal::run(conf.clone(), db.clone(), shutdown.clone().cancelled_owned()).await;
+        shutdown.cancel();
+        result
+    };
+
+    let (_, game, portal) = tokio::join!(signal, game, portal);
+
+    for (name, result) in [("game", game), ("portal", portal)] {
+        if let Err(e) = result {
+            error!("The {} stopped with an error: {}", name, e);
+        }
+    }
+
+    info!("dbatrs-dev shutting down.");
+    Ok(())
+}
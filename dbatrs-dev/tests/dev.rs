@@ -0,0 +1,102 @@
+use std::{
+    fs,
+    io::{ErrorKind, Read, Write},
+    net::{TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    thread,
+    time::{Duration, Instant}
+};
+
+const WAIT: Duration = Duration::from_secs(30);
+
+// A dbatrs-dev process run with the shipped devel configuration, listening on a free
+// port, and killed again when dropped.
+struct Dev {
+    child: Child,
+    dir: PathBuf,
+    port: u16
+}
+
+impl Dev {
+    fn start(name: &str) -> Self {
+        let data = Path::new(env!("CARGO_MANIFEST_DIR")).join("../data");
+        let dir = std::env::temp_dir().join(format!("dbatrs-dev-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for file in ["config.default.toml", "config.devel.toml"] {
+            fs::copy(data.join(file), dir.join(file)).unwrap();
+        }
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        fs::write(dir.join("config.user.devel.toml"), format!("\
+            [portal]\n\
+            reverse_dns = false\n\
+            [[portal.listeners]]\n\
+            address = \"127.0.0.1:{}\"\n\
+            protocol = \"telnet\"\n\
+            [logging]\n\
+            file = false\n", port)).unwrap();
+
+        let child = Command::new(env!("CARGO_BIN_EXE_dbatrs-dev"))
+            .args(["--mode", "devel", "--config-dir"])
+            .arg(&dir)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        Dev { child, dir, port }
+    }
+
+    fn connect(&mut self) -> TcpStream {
+        let deadline = Instant::now() + WAIT;
+        loop {
+            if let Ok(stream) = TcpStream::connect(("127.0.0.1", self.port)) {
+                stream.set_read_timeout(Some(Duration::from_millis(100))).unwrap();
+                return stream;
+            }
+            if let Ok(Some(status)) = self.child.try_wait() {
+                panic!("dbatrs-dev exited with {} before listening", status);
+            }
+            assert!(Instant::now() < deadline, "dbatrs-dev never started listening");
+            thread::sleep(Duration::from_millis(50));
+        }
+    }
+}
+
+impl Drop for Dev {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+// Reads until the text received contains wanted. Telnet negotiation goes unanswered, so
+// the portal gives up on it and carries on.
+fn expect_text(stream: &mut TcpStream, wanted: &str) -> String {
+    let deadline = Instant::now() + WAIT;
+    let mut text = String::new();
+    let mut buf = [0; 4096];
+    while !text.contains(wanted) {
+        assert!(Instant::now() < deadline, "never received {:?}; got {:?}", wanted, text);
+        match stream.read(&mut buf) {
+            Ok(0) => panic!("the connection closed before {:?}; got {:?}", wanted, text),
+            Ok(n) => text.push_str(&String::from_utf8_lossy(&buf[..n])),
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {},
+            Err(e) => panic!("{}", e)
+        }
+    }
+    text
+}
+
+#[test]
+fn registering_reaches_the_game() {
+    let mut dev = Dev::start("register");
+    let mut stream = dev.connect();
+    expect_text(&mut stream, "Connected to game server.\r\n");
+
+    stream.write_all(b"register someone@example.com=hunter22\r\n").unwrap();
+    expect_text(&mut stream, "You have successfully registered.\r\n");
+    // Only the game sends this, so the portal has handed the connection over to it.
+    expect_text(&mut stream, "You have no character to play.");
+}
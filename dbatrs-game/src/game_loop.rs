@@ -0,0 +1,138 @@
+use std::time::{Duration, Instant};
+
+use shipyard::{Unique, UniqueViewMut, Workload, World};
+use tokio::time::{self, MissedTickBehavior};
+use tracing::{error, info_span, warn};
+
+use dbatrs_shared::GameConf;
+
+use crate::{
+    persist::{self, DeletedRecords},
+    structs::common::DbIndexes,
+    systems::{
+        indexes,
+        location::{self, MoveEvents},
+        session::{self, Sessions, StartRoom}
+    }
+};
+
+// Workload names. PULSE runs every pulse; the rest run on their own schedules.
+pub const PULSE: &str = "pulse";
+pub const VIOLENCE: &str = "violence";
+pub const MOBILE: &str = "mobile";
+pub const ZONE: &str = "zone";
+pub const AFFECT: &str = "affect";
+pub const AUTOSAVE: &str = "autosave";
+
+// The number of the pulse being run, counting from zero at startup.
+#[derive(Unique, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Pulse(pub u64);
+
+// A workload and how often it runs.
+#[derive(Debug, Clone, Copy)]
+pub struct Schedule {
+    pub name: &'static str,
+    pub every: u64
+}
+
+// Owns the World and runs its workloads at a fixed rate.
+pub struct GameLoop {
+    pub world: World,
+    period: Duration,
+    schedules: Vec<Schedule>,
+    pulse: u64
+}
+
+impl GameLoop {
+    pub fn new(conf: &GameConf) -> Result<Self, shipyard::error::AddWorkload> {
+        let world = World::new();
+        world.add_unique(Pulse(0));
+        world.add_unique(DbIndexes::default());
+        world.add_unique(DeletedRecords::default());
+        world.add_unique(MoveEvents::default());
+        world.add_unique(Sessions::default());
+        world.add_unique(StartRoom(conf.start_room));
+
+        let schedules = vec![
+            Schedule { name: PULSE, every: 1 },
+            Schedule { name: VIOLENCE, every: conf.violence_pulses },
+            Schedule { name: MOBILE, every: conf.mobile_pulses },
+            Schedule { name: ZONE, every: conf.zone_pulses },
+            Schedule { name: AFFECT, every: conf.affect_pulses },
+            Schedule { name: AUTOSAVE, every: conf.autosave_pulses }
+        ];
+        for schedule in schedules.iter() {
+            let workload = Workload::new(schedule.name);
+            let workload = match schedule.name {
+                // Everything spawned or deleted last pulse can be looked up by id this pulse.
+                PULSE => workload.with_system(location::clear_move_events)
+                    .with_system(indexes::index_pulse)
+                    .with_system(session::handle_input),
+                // Debug builds check nothing's been left inconsistent before saving it.
+                AUTOSAVE if cfg!(debug_assertions) => workload.with_system(location::check_invariants).with_system(persist::autosave),
+                AUTOSAVE => workload.with_system(persist::autosave),
+                _ => workload
+            };
+            workload.add_to_world(&world)?;
+        }
+
+        Ok(Self {
+            world,
+            period: Duration::from_secs(1) / conf.pulses_per_second,
+            schedules,
+            pulse: 0
+        })
+    }
+
+    pub fn schedules(&self) -> &[Schedule] {
+        &self.schedules
+    }
+
+    // The workloads that run on the given pulse, in order.
+    pub fn due(&self, pulse: u64) -> Vec<&'static str> {
+        self.schedules.iter().filter(|s| pulse.is_multiple_of(s.every)).map(|s| s.name).collect()
+    }
+
+    // Runs pulses forever. Cancel-safe between pulses, which are never interrupted.
+    pub async fn run(&mut self) {
+        let mut interval = time::interval(self.period);
+        // A slow pulse pushes the ones after it back rather than making them bunch up.
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            self.run_pulse();
+        }
+    }
+
+    // Runs every workload due this pulse, then advances the pulse count.
+    pub fn run_pulse(&mut self) {
+        let pulse = self.pulse;
+        self.world.run(|mut current: UniqueViewMut<Pulse>| current.0 = pulse);
+
+        let _span = info_span!("pulse", pulse).entered();
+        let start = Instant::now();
+        let mut timings = Vec::new();
+        for name in self.due(pulse) {
+            timings.push((name, self.run_workload(name)));
+        }
+
+        let elapsed = start.elapsed();
+        if elapsed > self.period {
+            let breakdown: Vec<String> = timings.iter()
+                .map(|(name, took)| format!("{} {:?}", name, took))
+                .collect();
+            warn!("Pulse {} overran: took {:?} of its {:?} ({}).", pulse, elapsed, self.period, breakdown.join(", "));
+        }
+        self.pulse += 1;
+    }
+
+    // Runs one workload inside its own span, so the Tracy layer shows it as a zone.
+    pub fn run_workload(&self, name: &'static str) -> Duration {
+        let _span = info_span!("workload", name).entered();
+        let start = Instant::now();
+        if let Err(e) = self.world.run_workload(name) {
+            error!("Workload {} failed: {}", name, e);
+        }
+        start.elapsed()
+    }
+}
@@ -0,0 +1,154 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf}
+};
+
+use crate::structs::character::Dice;
+
+pub mod player;
+pub mod world;
+
+// Something wrong with one record in a legacy file. The record is skipped; the rest of
+// the file is still loaded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportProblem {
+    pub file: PathBuf,
+    // 1-based. Zero when the problem is with the file as a whole.
+    pub line: usize,
+    pub message: String
+}
+
+impl fmt::Display for ImportProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.file.display(), self.line, self.message)
+    }
+}
+
+// Reads the line-oriented, tilde-terminated format all the legacy files share, keeping
+// track of line numbers for error reports.
+pub(crate) struct Reader<'a> {
+    file: &'a Path,
+    lines: Vec<&'a str>,
+    // The index of the next line to read.
+    pos: usize
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(file: &'a Path, text: &'a str) -> Self {
+        Self {
+            file,
+            lines: text.lines().map(|l| l.trim_end_matches('\r')).collect(),
+            pos: 0
+        }
+    }
+
+    // The line number of the last line read.
+    pub(crate) fn line_no(&self) -> usize {
+        self.pos
+    }
+
+    pub(crate) fn problem(&self, message: impl Into<String>) -> ImportProblem {
+        self.problem_at(self.pos, message)
+    }
+
+    pub(crate) fn problem_at(&self, line: usize, message: impl Into<String>) -> ImportProblem {
+        ImportProblem { file: self.file.to_path_buf(), line, message: message.into() }
+    }
+
+    // The next line that isn't blank or a '*' comment, without consuming it.
+    pub(crate) fn peek(&mut self) -> Option<&'a str> {
+        while let Some(line) = self.lines.get(self.pos) {
+            let trimmed = line.trim();
+            if !trimmed.is_empty() && !trimmed.starts_with('*') {
+                return Some(trimmed);
+            }
+            self.pos += 1;
+        }
+        None
+    }
+
+    // The next line that isn't blank or a '*' comment, trimmed.
+    pub(crate) fn line(&mut self, what: &str) -> Result<&'a str, ImportProblem> {
+        match self.peek() {
+            Some(line) => {
+                self.pos += 1;
+                Ok(line)
+            },
+            None => Err(self.problem(format!("unexpected end of file, expected {}", what)))
+        }
+    }
+
+    // Reads text up to the next '~', which may be several lines on. Lines are joined
+    // with '\n', and anything after the '~' is ignored.
+    pub(crate) fn string(&mut self, what: &str) -> Result<String, ImportProblem> {
+        let start = self.pos + 1;
+        let mut parts = Vec::new();
+        while let Some(line) = self.lines.get(self.pos) {
+            self.pos += 1;
+            if let Some(end) = line.find('~') {
+                parts.push(&line[..end]);
+                return Ok(parts.join("\n"));
+            }
+            parts.push(line);
+        }
+        Err(self.problem_at(start, format!("{} starting here is missing its closing '~'", what)))
+    }
+
+    // A line of at least min whitespace-separated integers.
+    pub(crate) fn numbers(&mut self, min: usize, what: &str) -> Result<Vec<i64>, ImportProblem> {
+        let line = self.line(what)?;
+        let numbers = parse_numbers(line).ok_or_else(|| self.problem(format!("expected numbers for {}, got '{}'", what, line)))?;
+        if numbers.len() < min {
+            return Err(self.problem(format!("expected at least {} numbers for {}, got '{}'", min, what, line)));
+        }
+        Ok(numbers)
+    }
+
+    // Moves to the next line starting with '#' or '$', after a bad record.
+    pub(crate) fn skip_record(&mut self) {
+        while let Some(line) = self.lines.get(self.pos) {
+            if line.starts_with('#') || line.starts_with('$') {
+                return;
+            }
+            self.pos += 1;
+        }
+    }
+}
+
+pub(crate) fn parse_numbers(line: &str) -> Option<Vec<i64>> {
+    line.split_whitespace().map(|n| n.parse().ok()).collect()
+}
+
+// A flag field is either a number or letters, a-z for bits 0-25 and A-Z for 26-51.
+pub(crate) fn parse_flags(field: &str) -> Option<u64> {
+    if field.chars().all(|c| c.is_ascii_digit()) {
+        return field.parse().ok();
+    }
+    field.chars().try_fold(0u64, |bits, c| match c {
+        'a'..='z' => Some(bits | 1 << (c as u64 - 'a' as u64)),
+        'A'..='Z' => Some(bits | 1 << (26 + c as u64 - 'A' as u64)),
+        _ => None
+    })
+}
+
+// Flags split over several 32-bit fields, the first holding bits 0-31 and so on.
+pub(crate) fn parse_flag_words(fields: &[&str]) -> Option<u128> {
+    fields.iter().enumerate().try_fold(0u128, |bits, (i, field)| {
+        parse_flags(field).map(|word| bits | (word as u128) << (32 * i))
+    })
+}
+
+// Dice written as 3d8+10 or 3d8-1.
+pub(crate) fn parse_dice(field: &str) -> Option<Dice> {
+    let (count, rest) = field.split_once('d')?;
+    let (sides, bonus) = match rest.find(['+', '-']) {
+        Some(i) => (&rest[..i], rest[i..].trim_start_matches('+').parse().ok()?),
+        None => (rest, 0)
+    };
+    Some(Dice { count: count.parse().ok()?, sides: sides.parse().ok()?, bonus })
+}
+
+// Legacy files use -1 to mean "nothing".
+pub(crate) fn vnum(n: i64) -> Option<usize> {
+    usize::try_from(n).ok()
+}
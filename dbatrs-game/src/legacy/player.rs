@@ -0,0 +1,380 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf}
+};
+
+use chrono::Utc;
+use serde::Serialize;
+use shipyard::{IntoIter, View, World};
+use surrealdb::{RecordId, Surreal, engine::any::Any};
+
+use crate::{
+    legacy::ImportProblem,
+    structs::{
+        character::{Race, Sensei, Sex},
+        common::{DbEntityType, DbIdentifier, Player, ThingType, Title, UserAccount},
+        item::{SavedItem, SavedItems}
+    },
+    systems::indexes::sync_indexes
+};
+
+// An account file from lib/user. These are positional: name, password hash, email, slots,
+// RPP, five character names ("Empty" for an unused slot), then admin level. Anything
+// after that is ignored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LegacyAccount {
+    pub name: String,
+    pub email: String,
+    pub slots: usize,
+    pub rpp: usize,
+    pub characters: Vec<String>,
+    pub admin_level: u8
+}
+
+// The parts of an ASCII player file we carry over.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LegacyPlayer {
+    pub id: usize,
+    pub name: String,
+    pub race: Option<Race>,
+    pub sensei: Option<Sensei>,
+    pub sex: Sex,
+    pub level: i64,
+    pub inventory: Vec<SavedItem>
+}
+
+// What import_players created, and what it had to skip.
+#[derive(Debug, Default)]
+pub struct PlayerReport {
+    pub accounts: usize,
+    pub players: usize,
+    pub problems: Vec<ImportProblem>
+}
+
+fn problem(file: &Path, line: usize, message: impl Into<String>) -> ImportProblem {
+    ImportProblem { file: file.to_path_buf(), line, message: message.into() }
+}
+
+pub fn parse_account(file: &Path, text: &str) -> Result<LegacyAccount, ImportProblem> {
+    let lines: Vec<&str> = text.lines().map(str::trim).collect();
+    if lines.len() < 11 {
+        return Err(problem(file, lines.len(), format!("expected at least 11 lines, got {}", lines.len())));
+    }
+    let number = |i: usize, what: &str| -> Result<usize, ImportProblem> {
+        lines[i].parse().map_err(|_| problem(file, i + 1, format!("bad {} '{}'", what, lines[i])))
+    };
+    if lines[0].is_empty() {
+        return Err(problem(file, 1, "account name is empty"));
+    }
+
+    Ok(LegacyAccount {
+        name: lines[0].to_string(),
+        email: lines[2].to_string(),
+        slots: number(3, "slot count")?,
+        rpp: number(4, "RPP")?,
+        characters: lines[5..10].iter()
+            .filter(|name| !name.is_empty() && !name.eq_ignore_ascii_case("empty"))
+            .map(|name| name.to_string())
+            .collect(),
+        admin_level: lines[10].parse().map_err(|_| problem(file, 11, format!("bad admin level '{}'", lines[10])))?
+    })
+}
+
+// Player files are "Tag : value" lines. A tag with no value starts a block, such as skills
+// or aliases, that runs to a line of zeroes, or a description that runs to a '~'.
+pub fn parse_player(file: &Path, text: &str) -> Result<LegacyPlayer, ImportProblem> {
+    let mut tags = HashMap::new();
+    let mut lines = text.lines().enumerate();
+    while let Some((i, line)) = lines.next() {
+        let Some((tag, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        if value.is_empty() {
+            for (_, line) in lines.by_ref() {
+                if line.ends_with('~') || line.split_whitespace().all(|n| n == "0") {
+                    break;
+                }
+            }
+            continue;
+        }
+        tags.entry(tag.trim()).or_insert((i + 1, value));
+    }
+
+    let number = |tag: &str| -> Result<Option<i64>, ImportProblem> {
+        match tags.get(tag) {
+            Some((line, value)) => value.parse().map(Some)
+                .map_err(|_| problem(file, *line, format!("bad {} '{}'", tag, value))),
+            None => Ok(None)
+        }
+    };
+    let Some((_, name)) = tags.get("Name") else {
+        return Err(problem(file, 0, "no Name"));
+    };
+    let id = number("Id")?.ok_or_else(|| problem(file, 0, "no Id"))?;
+    let id = usize::try_from(id).map_err(|_| problem(file, tags["Id"].0, format!("bad Id '{}'", id)))?;
+    let small = |n: Option<i64>| n.and_then(|n| u8::try_from(n).ok());
+
+    Ok(LegacyPlayer {
+        id,
+        name: name.to_string(),
+        race: small(number("Race")?).and_then(Race::from_value),
+        sensei: small(number("Clas")?).and_then(Sensei::from_value),
+        sex: small(number("Sex")?).and_then(Sex::from_value).unwrap_or(Sex::Neuter),
+        level: number("Levl")?.unwrap_or(1),
+        inventory: Vec::new()
+    })
+}
+
+// Rent files list each item as "#<vnum>" followed by tags, of which we only need "Loc".
+// Unreadable items are reported and left out.
+pub fn parse_objects(file: &Path, text: &str, problems: &mut Vec<ImportProblem>) -> Vec<SavedItem> {
+    let mut items: Vec<SavedItem> = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.starts_with('$') {
+            break;
+        }
+        if let Some(vnum) = line.strip_prefix('#') {
+            match vnum.trim().parse() {
+                Ok(vnum) => items.push(SavedItem { vnum, location: 0 }),
+                Err(_) => problems.push(problem(file, i + 1, format!("bad item vnum '{}'", vnum)))
+            }
+        } else if let Some((tag, value)) = line.split_once(':') {
+            if tag.trim() == "Loc" {
+                match (items.last_mut(), value.trim().parse()) {
+                    (Some(item), Ok(location)) => item.location = location,
+                    _ => problems.push(problem(file, i + 1, format!("bad item location '{}'", value.trim())))
+                }
+            }
+        }
+    }
+    items
+}
+
+// Every file under dir, at any depth, with the given extension. The legacy game buckets
+// these by first letter.
+fn find_files(dir: &Path, extension: &str) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for path in entries.filter_map(|e| e.ok().map(|e| e.path())) {
+            if path.is_dir() {
+                dirs.push(path);
+            } else if path.extension().is_some_and(|ext| ext == extension) {
+                found.push(path);
+            }
+        }
+    }
+    found.sort();
+    found
+}
+
+fn read_all<T>(dir: &Path, extension: &str, problems: &mut Vec<ImportProblem>,
+               parse: impl Fn(&Path, &str) -> Result<T, ImportProblem>) -> Vec<(PathBuf, T)> {
+    let mut parsed = Vec::new();
+    for path in find_files(dir, extension) {
+        let result = fs::read(&path)
+            .map_err(|e| problem(&path, 0, e.to_string()))
+            .and_then(|bytes| parse(&path, &String::from_utf8_lossy(&bytes)));
+        match result {
+            Ok(value) => parsed.push((path, value)),
+            Err(e) => problems.push(e)
+        }
+    }
+    parsed
+}
+
+// Why a write failed, whether the query was rejected or one of its statements errored.
+fn failure(result: surrealdb::Result<surrealdb::Response>) -> Option<surrealdb::Error> {
+    match result {
+        Ok(mut response) => response.take_errors().into_values().next(),
+        Err(e) => Some(e)
+    }
+}
+
+// One past the highest account id already in use, in the database or in world, so an
+// account's id doesn't depend on which others are imported alongside it.
+async fn next_account_id(world: &World, db: &Surreal<Any>) -> Result<usize, surrealdb::Error> {
+    let stored: Vec<i64> = db.query("SELECT VALUE account_id FROM user WHERE account_id != NONE").await?.take(0)?;
+    let loaded = world.run(|ids: View<DbIdentifier>| {
+        ids.iter().filter(|id| id.entity_type == DbEntityType::UserAccount).map(|id| id.id).max()
+    });
+    let highest = stored.into_iter().map(|id| id as usize).chain(loaded).max().unwrap_or(0);
+    Ok(highest + 1)
+}
+
+#[derive(Serialize)]
+struct PcRow {
+    name: String,
+    lower_name: String,
+    user: RecordId,
+    race: Option<&'static str>,
+    sensei: Option<&'static str>,
+    sex: &'static str,
+    level: i64,
+    inventory: Vec<SavedItem>
+}
+
+// Imports the accounts in dir/user (*.usr), their characters from dir/plrfiles (*.plr)
+// and those characters' items from dir/plrobjs (*.new). Each account gets a user row,
+// keyed by its lowercased name and numbered in account_id after the accounts already there,
+// and each character a pc row keyed by its old ID, along with matching entities in world.
+// Imported accounts are flagged for a password reset; see set_password. Anything already in
+// the database, or not tied to an account, is reported and skipped, so importing the same
+// files again changes nothing.
+pub async fn import_players(world: &mut World, db: &Surreal<Any>, dir: &Path) -> PlayerReport {
+    let mut report = PlayerReport::default();
+
+    let mut accounts = read_all(&dir.join("user"), "usr", &mut report.problems, parse_account);
+    accounts.sort_by_key(|(_, account)| account.name.to_lowercase());
+
+    let mut players = HashMap::new();
+    for (path, player) in read_all(&dir.join("plrfiles"), "plr", &mut report.problems, parse_player) {
+        let key = player.name.to_lowercase();
+        if players.contains_key(&key) {
+            report.problems.push(problem(&path, 0, format!("another player file is also named {}; skipped", player.name)));
+            continue;
+        }
+        players.insert(key, (path, player));
+    }
+    for path in find_files(&dir.join("plrobjs"), "new") {
+        let Some(stem) = path.file_stem().map(|s| s.to_string_lossy().to_lowercase()) else {
+            continue;
+        };
+        let Some((_, player)) = players.get_mut(&stem) else {
+            report.problems.push(problem(&path, 0, "no player file for these items; skipped"));
+            continue;
+        };
+        match fs::read(&path) {
+            Ok(bytes) => player.inventory = parse_objects(&path, &String::from_utf8_lossy(&bytes), &mut report.problems),
+            Err(e) => report.problems.push(problem(&path, 0, e.to_string()))
+        }
+    }
+
+    let mut account_id = match next_account_id(world, db).await {
+        Ok(id) => id,
+        Err(e) => {
+            report.problems.push(problem(dir, 0, format!("could not number accounts: {}", e)));
+            return report;
+        }
+    };
+    let mut emails = HashSet::new();
+
+    for (path, account) in accounts {
+        // Legacy accounts often share an address or have none, but ours must be unique.
+        let mut email = account.email.trim().to_lowercase();
+        if !email.contains('@') || !emails.insert(email.clone()) {
+            email = format!("{}@legacy.invalid", account.name.to_lowercase());
+        }
+
+        let user = RecordId::from(("user", account.name.to_lowercase()));
+        let created = failure(db.query("CREATE $user SET account_id = $account_id, name = $name, email = $email, \
+                                password = rand::string(32), password_reset = true, rpp = $rpp, slots = $slots, \
+                                admin_level = $admin_level RETURN NONE")
+            .bind(("user", user.clone()))
+            .bind(("account_id", account_id as i64))
+            .bind(("name", account.name.clone()))
+            .bind(("email", email.clone()))
+            .bind(("rpp", account.rpp as i64))
+            .bind(("slots", account.slots as i64))
+            .bind(("admin_level", account.admin_level as i64))
+            .await);
+        if let Some(e) = created {
+            report.problems.push(problem(&path, 0, format!("could not create account {}: {}", account.name, e)));
+            continue;
+        }
+
+        let mut characters = Vec::new();
+        for name in account.characters.iter() {
+            let Some((player_path, player)) = players.remove(&name.to_lowercase()) else {
+                report.problems.push(problem(&path, 0, format!("lists character {}, but there's no player file", name)));
+                continue;
+            };
+            let row = PcRow {
+                name: player.name.clone(),
+                lower_name: player.name.clone(),
+                user: user.clone(),
+                race: player.race.map(Race::to_str),
+                sensei: player.sensei.map(Sensei::to_str),
+                sex: player.sex.to_str(),
+                level: player.level,
+                inventory: player.inventory.clone()
+            };
+            let created = failure(db.query("CREATE $pc CONTENT $row RETURN NONE")
+                .bind(("pc", RecordId::from(("pc", player.id as i64))))
+                .bind(("row", row))
+                .await);
+            if let Some(e) = created {
+                report.problems.push(problem(&player_path, 0, format!("could not create character {}: {}", player.name, e)));
+                continue;
+            }
+
+            let entity = world.add_entity((
+                DbIdentifier { id: player.id, entity_type: DbEntityType::Player },
+                ThingType::Character,
+                Player { name: player.name.clone(), account: account_id },
+                Title(player.name),
+                player.sex,
+                SavedItems(player.inventory)
+            ));
+            if let Some(race) = player.race {
+                world.add_component(entity, race);
+            }
+            if let Some(sensei) = player.sensei {
+                world.add_component(entity, sensei);
+            }
+            characters.push(player.id);
+            report.players += 1;
+        }
+
+        let now = Utc::now();
+        world.add_entity((
+            DbIdentifier { id: account_id, entity_type: DbEntityType::UserAccount },
+            UserAccount {
+                name: account.name,
+                password: String::new(),
+                email,
+                created: now,
+                last_login: now,
+                last_logout: now,
+                last_password_change: now,
+                admin_level: account.admin_level,
+                rpp: account.rpp,
+                slots: account.slots,
+                characters,
+                password_reset: true
+            }
+        ));
+        account_id += 1;
+        report.accounts += 1;
+    }
+
+    let mut orphans: Vec<_> = players.into_values().collect();
+    orphans.sort_by(|a, b| a.0.cmp(&b.0));
+    for (path, player) in orphans {
+        report.problems.push(problem(&path, 0, format!("{} isn't listed by any account; skipped", player.name)));
+    }
+
+    // Player files and accounts are numbered by us or the database, so this would be a bug.
+    if let Err(e) = sync_indexes(world) {
+        report.problems.push(problem(dir, 0, e.to_string()));
+    }
+    report
+}
+
+// Gives the account with this name or email a new password and clears its password reset
+// flag, so it can sign in again. Returns whether there was such an account.
+pub async fn set_password(db: &Surreal<Any>, account: &str, password: &str) -> Result<bool, surrealdb::Error> {
+    let updated: Vec<RecordId> = db.query("UPDATE user SET password = $password, password_reset = false \
+                                           WHERE string::lowercase(name ?? '') = $account OR email = $account RETURN VALUE id")
+        .bind(("account", account.trim().to_lowercase()))
+        .bind(("password", password.to_string()))
+        .await?
+        .take(0)?;
+    Ok(!updated.is_empty())
+}
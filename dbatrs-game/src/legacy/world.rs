@@ -0,0 +1,542 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf}
+};
+
+use shipyard::{EntityId, UniqueView, World};
+
+use crate::{
+    legacy::{ImportProblem, Reader, parse_dice, parse_flag_words, parse_flags, parse_numbers, vnum},
+    structs::{
+        character::{MobFlags, NpcStats, Race, Sensei, Sex},
+        common::{
+            AffectFlags, DbEntityType, DbIdentifier, DbIndexes, Description, ExtraDescriptions, Keywords, Scripts,
+            ShortDesc, ThingSize, ThingType, Title, Weight
+        },
+        item::{Cost, ItemAffect, ItemAffects, ItemFlags, ItemType, ItemValues, WearFlags},
+        room::{Direction, Exit, ExitFlags, Exits, RoomFlags, SectorType},
+        script::TriggerProto,
+        zone::{Zone, ZoneCommand, ZoneCommands, ZoneFlags}
+    },
+    systems::indexes::sync_indexes
+};
+
+// What import_world loaded, and what it had to skip.
+#[derive(Debug, Default)]
+pub struct WorldReport {
+    pub zones: usize,
+    pub rooms: usize,
+    pub items: usize,
+    pub npcs: usize,
+    pub triggers: usize,
+    pub problems: Vec<ImportProblem>
+}
+
+// Loads a CircleMUD-style world directory (trg/, zon/, wld/, obj/ and mob/, each with an
+// index file listing its files) into the world as prototype entities, indexed in DbIndexes
+// after each file. Records that can't be read are reported and skipped.
+pub fn import_world(world: &mut World, dir: &Path) -> WorldReport {
+    let mut report = WorldReport::default();
+    let mut importer = Importer { world, report: &mut report };
+
+    importer.load(dir, "trg", DbEntityType::ScriptProto, parse_trigger);
+    importer.load(dir, "zon", DbEntityType::Zone, parse_zone);
+    importer.load(dir, "wld", DbEntityType::Room, parse_room);
+    importer.load(dir, "obj", DbEntityType::ItemProto, parse_item);
+    importer.load(dir, "mob", DbEntityType::NpcProto, parse_mobile);
+    report
+}
+
+// The components of one record, added to the world once it has been read in full.
+type Spawn = Box<dyn FnOnce(&mut World, DbIdentifier) -> EntityId>;
+
+type Parser = fn(&mut Reader, &mut Vec<ImportProblem>) -> Result<Spawn, ImportProblem>;
+
+struct Importer<'a> {
+    world: &'a mut World,
+    report: &'a mut WorldReport
+}
+
+impl Importer<'_> {
+    fn load(&mut self, dir: &Path, kind: &str, entity_type: DbEntityType, parse: Parser) {
+        let files = match list_files(&dir.join(kind), kind) {
+            Ok(files) => files,
+            Err(problem) => {
+                self.report.problems.push(problem);
+                return;
+            }
+        };
+        // Vnums already taken, checked before each spawn so indexes only need syncing once a
+        // file is done.
+        let mut vnums: HashSet<usize> = match self.world.borrow::<UniqueView<DbIndexes>>() {
+            Ok(indexes) => indexes.of_type(&entity_type).keys().copied().collect(),
+            Err(_) => HashSet::new()
+        };
+        for path in files {
+            match fs::read(&path) {
+                // Legacy files aren't always UTF-8; keep what we can rather than rejecting them.
+                Ok(bytes) => self.load_file(&path, &String::from_utf8_lossy(&bytes), &entity_type, parse, &mut vnums),
+                Err(e) => self.report.problems.push(ImportProblem { file: path.clone(), line: 0, message: e.to_string() })
+            }
+            if let Err(e) = sync_indexes(self.world) {
+                self.report.problems.push(ImportProblem { file: path, line: 0, message: e.to_string() });
+            }
+        }
+    }
+
+    fn load_file(&mut self, path: &Path, text: &str, entity_type: &DbEntityType, parse: Parser, vnums: &mut HashSet<usize>) {
+        let mut reader = Reader::new(path, text);
+        while let Some(line) = reader.peek() {
+            if line.starts_with('$') {
+                break;
+            }
+            let _ = reader.line("a record");
+            let start = reader.line_no();
+            let Some(id) = line.strip_prefix('#').and_then(|n| n.trim().parse::<usize>().ok()) else {
+                self.report.problems.push(reader.problem(format!("expected '#<vnum>', got '{}'", line)));
+                reader.skip_record();
+                continue;
+            };
+            if vnums.contains(&id) {
+                self.report.problems.push(reader.problem_at(start, format!("duplicate {:?} #{}; keeping the first", entity_type, id)));
+                reader.skip_record();
+                continue;
+            }
+
+            let spawn = match parse(&mut reader, &mut self.report.problems) {
+                Ok(spawn) => spawn,
+                Err(problem) => {
+                    self.report.problems.push(problem);
+                    reader.skip_record();
+                    continue;
+                }
+            };
+
+            vnums.insert(id);
+            spawn(self.world, DbIdentifier { id, entity_type: entity_type.clone() });
+            match entity_type {
+                DbEntityType::Zone => self.report.zones += 1,
+                DbEntityType::Room => self.report.rooms += 1,
+                DbEntityType::ItemProto => self.report.items += 1,
+                DbEntityType::NpcProto => self.report.npcs += 1,
+                DbEntityType::ScriptProto => self.report.triggers += 1,
+                _ => {}
+            }
+        }
+    }
+}
+
+// The files named in dir's index, or every file with the extension if there's no index.
+// A missing directory just means the world has none of that kind.
+fn list_files(dir: &Path, kind: &str) -> Result<Vec<PathBuf>, ImportProblem> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let problem = |file: &Path, e: std::io::Error| ImportProblem { file: file.to_path_buf(), line: 0, message: e.to_string() };
+
+    let index = dir.join("index");
+    if index.is_file() {
+        let text = fs::read_to_string(&index).map_err(|e| problem(&index, e))?;
+        return Ok(text.lines()
+            .map(str::trim)
+            .take_while(|name| !name.starts_with('$'))
+            .filter(|name| !name.is_empty())
+            .map(|name| dir.join(name))
+            .collect());
+    }
+
+    let mut files: Vec<PathBuf> = fs::read_dir(dir).map_err(|e| problem(dir, e))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == kind))
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+// Any "T <vnum>" lines after a record, attaching trigger prototypes to it.
+fn read_scripts(r: &mut Reader) -> Result<Option<Scripts>, ImportProblem> {
+    let mut scripts = Vec::new();
+    while let Some(line) = r.peek() {
+        let Some(trigger) = line.strip_prefix("T ") else {
+            break;
+        };
+        r.line("a trigger")?;
+        match trigger.split_whitespace().next().and_then(|n| n.parse().ok()) {
+            Some(trigger) => scripts.push(trigger),
+            None => return Err(r.problem(format!("expected 'T <vnum>', got '{}'", line)))
+        }
+    }
+    Ok((!scripts.is_empty()).then_some(Scripts(scripts)))
+}
+
+fn read_extra(r: &mut Reader, extras: &mut Vec<(String, String)>) -> Result<(), ImportProblem> {
+    let keywords = r.string("extra description keywords")?;
+    let description = r.string("extra description")?;
+    extras.push((keywords, description));
+    Ok(())
+}
+
+// Splits a line of fields, reporting what was expected if there are fewer than min.
+fn fields<'a>(r: &mut Reader<'a>, min: usize, what: &str) -> Result<Vec<&'a str>, ImportProblem> {
+    let line = r.line(what)?;
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    if fields.len() < min {
+        return Err(r.problem(format!("expected {}, got '{}'", what, line)));
+    }
+    Ok(fields)
+}
+
+fn flags(r: &Reader, fields: &[&str], what: &str) -> Result<u128, ImportProblem> {
+    parse_flag_words(fields).ok_or_else(|| r.problem(format!("bad {} '{}'", what, fields.join(" "))))
+}
+
+fn number<T: std::str::FromStr>(r: &Reader, field: &str, what: &str) -> Result<T, ImportProblem> {
+    field.parse().map_err(|_| r.problem(format!("bad {} '{}'", what, field)))
+}
+
+fn parse_room(r: &mut Reader, problems: &mut Vec<ImportProblem>) -> Result<Spawn, ImportProblem> {
+    let name = r.string("room name")?;
+    let description = r.string("room description")?;
+
+    // zone flags sector, or zone flags1 flags2 flags3 flags4 sector.
+    let line = fields(r, 3, "zone, flags and sector")?;
+    let (flag_fields, sector) = if line.len() >= 6 { (&line[1..5], line[5]) } else { (&line[1..2], line[2]) };
+    let room_flags = RoomFlags::from_bits_retain(flags(r, flag_fields, "room flags")?);
+    let sector = match sector.parse().ok().and_then(SectorType::from_value) {
+        Some(sector) => sector,
+        None => {
+            problems.push(r.problem(format!("unknown sector type '{}'; using INSIDE", sector)));
+            SectorType::INSIDE
+        }
+    };
+
+    let mut exits = HashMap::new();
+    let mut extras = Vec::new();
+    loop {
+        let line = r.line("'D', 'E' or 'S'")?;
+        let start = r.line_no();
+        if let Some(dir) = line.strip_prefix('D') {
+            let description = r.string("exit description")?;
+            let keyword = r.string("exit keyword")?;
+            // exit_info key to_room, followed in DBAT by DCs and fail rooms we don't use.
+            let numbers = r.numbers(3, "exit info, key and destination")?;
+            let Some(direction) = dir.trim().parse().ok().and_then(Direction::from_value) else {
+                problems.push(r.problem_at(start, format!("unknown exit direction '{}'; exit skipped", line)));
+                continue;
+            };
+            // An exit to nowhere can be looked at but not used; there's nowhere to record it.
+            let Some(destination) = vnum(numbers[2]) else {
+                continue;
+            };
+            let flags = match numbers[0] {
+                0 => ExitFlags::empty(),
+                2 => ExitFlags::ISDOOR | ExitFlags::PICKPROOF,
+                3 => ExitFlags::ISDOOR | ExitFlags::SECRET,
+                4 => ExitFlags::ISDOOR | ExitFlags::PICKPROOF | ExitFlags::SECRET,
+                _ => ExitFlags::ISDOOR
+            };
+            // Many builders wrote 0 for "no key".
+            let key = vnum(numbers[1]).filter(|key| *key != 0);
+            exits.insert(direction, Exit { description, keyword, destination, key, flags });
+        } else if line == "E" {
+            read_extra(r, &mut extras)?;
+        } else if line == "S" {
+            break;
+        } else {
+            return Err(r.problem(format!("expected 'D', 'E' or 'S', got '{}'", line)));
+        }
+    }
+    let scripts = read_scripts(r)?;
+
+    Ok(Box::new(move |world: &mut World, id: DbIdentifier| {
+        let entity = world.add_entity((id, ThingType::Room, Title(name), Description(description), room_flags, sector,
+                                       Exits { exits }));
+        if !extras.is_empty() {
+            world.add_component(entity, ExtraDescriptions { extras });
+        }
+        if let Some(scripts) = scripts {
+            world.add_component(entity, scripts);
+        }
+        entity
+    }))
+}
+
+fn parse_item(r: &mut Reader, problems: &mut Vec<ImportProblem>) -> Result<Spawn, ImportProblem> {
+    let keywords = r.string("item keywords")?;
+    let short = r.string("item short description")?;
+    let long = r.string("item long description")?;
+    // The action description is only used by a few item types we don't support yet.
+    let _action = r.string("item action description")?;
+
+    // type extra wear [affects], or type extra1-4 wear1-4 affects1-4.
+    let line = fields(r, 3, "type and flags")?;
+    let (extra, wear, affect) = if line.len() >= 13 {
+        (&line[1..5], &line[5..9], &line[9..13])
+    } else {
+        (&line[1..2], &line[2..3], &line[3..line.len().min(4)])
+    };
+    let item_flags = ItemFlags::from_bits_retain(flags(r, extra, "item flags")? as u64);
+    let wear_flags = WearFlags::from_bits_retain(flags(r, wear, "wear flags")? as u32);
+    let affect_flags = AffectFlags::from_bits_retain(flags(r, affect, "affect flags")? as u64);
+    let item_type = match line[0].parse().ok().and_then(ItemType::from_value) {
+        Some(item_type) => item_type,
+        None => {
+            problems.push(r.problem(format!("unknown item type '{}'; using UNKNOWN", line[0])));
+            ItemType::UNKNOWN
+        }
+    };
+
+    let values = ItemValues(r.numbers(1, "item values")?);
+    // weight cost rent [level ...]
+    let numbers = r.numbers(2, "weight and cost")?;
+    let weight = Weight(numbers[0] as f32);
+    let cost = Cost(numbers[1].max(0) as u64);
+
+    let mut extras = Vec::new();
+    let mut affects = Vec::new();
+    while let Some(line) = r.peek() {
+        match line {
+            "E" => {
+                r.line("'E'")?;
+                read_extra(r, &mut extras)?;
+            },
+            "A" => {
+                r.line("'A'")?;
+                let numbers = r.numbers(2, "affect location and modifier")?;
+                affects.push(ItemAffect {
+                    location: numbers[0],
+                    modifier: numbers[1],
+                    specific: numbers.get(2).copied().unwrap_or(0)
+                });
+            },
+            _ if line.starts_with("T ") || line.starts_with('#') || line.starts_with('$') => break,
+            _ => {
+                r.line("an item section")?;
+                problems.push(r.problem(format!("unknown item section '{}' ignored", line)));
+            }
+        }
+    }
+    let scripts = read_scripts(r)?;
+
+    Ok(Box::new(move |world: &mut World, id: DbIdentifier| {
+        let keywords = Keywords(keywords.split_whitespace().map(String::from).collect());
+        let entity = world.add_entity((id, ThingType::Item, keywords, Title(short), ShortDesc(long), item_type,
+                                       item_flags, wear_flags, values, weight));
+        world.add_component(entity, cost);
+        if !affect_flags.is_empty() {
+            world.add_component(entity, affect_flags);
+        }
+        if !extras.is_empty() {
+            world.add_component(entity, ExtraDescriptions { extras });
+        }
+        if !affects.is_empty() {
+            world.add_component(entity, ItemAffects(affects));
+        }
+        if let Some(scripts) = scripts {
+            world.add_component(entity, scripts);
+        }
+        entity
+    }))
+}
+
+fn parse_mobile(r: &mut Reader, problems: &mut Vec<ImportProblem>) -> Result<Spawn, ImportProblem> {
+    let keywords = r.string("NPC keywords")?;
+    let short = r.string("NPC short description")?;
+    let long = r.string("NPC long description")?;
+    let description = r.string("NPC description")?;
+
+    // flags affects alignment type, or flags1-4 affects1-4 alignment type.
+    let line = fields(r, 4, "flags, alignment and type")?;
+    let (mob, affect, rest) = if line.len() >= 10 {
+        (&line[0..4], &line[4..8], &line[8..])
+    } else {
+        (&line[0..1], &line[1..2], &line[2..])
+    };
+    let mob_flags = MobFlags::from_bits_retain(flags(r, mob, "NPC flags")? as u64);
+    let affect_flags = AffectFlags::from_bits_retain(flags(r, affect, "affect flags")? as u64);
+    let mut stats = NpcStats { alignment: number(r, rest[0], "alignment")?, ..Default::default() };
+    let enhanced = match rest.get(1).copied() {
+        Some("S") => false,
+        Some("E") => true,
+        other => return Err(r.problem(format!("unknown NPC type '{}', expected 'S' or 'E'", other.unwrap_or(""))))
+    };
+
+    // level hitroll armor hit_dice damage_dice
+    let line = fields(r, 5, "level, hitroll, armor and dice")?;
+    stats.level = number(r, line[0], "level")?;
+    stats.hitroll = number(r, line[1], "hitroll")?;
+    stats.armor = number(r, line[2], "armor")?;
+    stats.hit_dice = parse_dice(line[3]).ok_or_else(|| r.problem(format!("bad hit dice '{}'", line[3])))?;
+    stats.damage_dice = parse_dice(line[4]).ok_or_else(|| r.problem(format!("bad damage dice '{}'", line[4])))?;
+
+    // gold exp, with race and sensei in DBAT.
+    let numbers = r.numbers(2, "gold and experience")?;
+    stats.gold = numbers[0];
+    stats.exp = numbers[1];
+    let race = numbers.get(2).and_then(|n| u8::try_from(*n).ok()).and_then(Race::from_value);
+    let sensei = numbers.get(3).and_then(|n| u8::try_from(*n).ok()).and_then(Sensei::from_value);
+
+    // position default_position sex
+    let numbers = r.numbers(3, "position and sex")?;
+    stats.position = numbers[0].clamp(0, u8::MAX as i64) as u8;
+    stats.default_position = numbers[1].clamp(0, u8::MAX as i64) as u8;
+    let sex = u8::try_from(numbers[2]).ok().and_then(Sex::from_value).unwrap_or_else(|| {
+        problems.push(r.problem(format!("unknown sex '{}'; using Neuter", numbers[2])));
+        Sex::Neuter
+    });
+
+    // Enhanced NPCs have "Name: value" lines up to a lone "E".
+    let mut size = None;
+    if enhanced {
+        loop {
+            let line = r.line("an enhanced NPC field or 'E'")?;
+            if line == "E" {
+                break;
+            }
+            if let Some(value) = line.strip_prefix("Size:") {
+                size = parse_numbers(value).and_then(|n| n.first().copied())
+                    .and_then(|n| u8::try_from(n).ok())
+                    .and_then(ThingSize::from_value);
+            }
+        }
+    }
+    let scripts = read_scripts(r)?;
+
+    Ok(Box::new(move |world: &mut World, id: DbIdentifier| {
+        let keywords = Keywords(keywords.split_whitespace().map(String::from).collect());
+        let entity = world.add_entity((id, ThingType::Character, keywords, Title(short), ShortDesc(long),
+                                       Description(description), mob_flags, affect_flags, stats, sex));
+        if let Some(race) = race {
+            world.add_component(entity, race);
+        }
+        if let Some(sensei) = sensei {
+            world.add_component(entity, sensei);
+        }
+        if let Some(size) = size {
+            world.add_component(entity, size);
+        }
+        if let Some(scripts) = scripts {
+            world.add_component(entity, scripts);
+        }
+        entity
+    }))
+}
+
+fn parse_zone(r: &mut Reader, _problems: &mut Vec<ImportProblem>) -> Result<Spawn, ImportProblem> {
+    // Newer files put a builders line before the name.
+    let first = r.string("zone name")?;
+    let (builders, name) = if r.peek().is_some_and(|line| line.ends_with('~')) {
+        (first, r.string("zone name")?)
+    } else {
+        (String::new(), first)
+    };
+
+    // top lifespan reset_mode, or bottom top lifespan reset_mode [flags1-4 min_level max_level].
+    let line = fields(r, 3, "zone range, lifespan and reset mode")?;
+    let mut zone = Zone { builders, ..Default::default() };
+    let mut zone_flags = ZoneFlags::empty();
+    if line.len() == 3 {
+        zone.top = number(r, line[0], "top vnum")?;
+        zone.lifespan = number(r, line[1], "lifespan")?;
+        zone.reset_mode = number(r, line[2], "reset mode")?;
+    } else {
+        zone.bottom = number(r, line[0], "bottom vnum")?;
+        zone.top = number(r, line[1], "top vnum")?;
+        zone.lifespan = number(r, line[2], "lifespan")?;
+        zone.reset_mode = number(r, line[3], "reset mode")?;
+        if line.len() >= 10 {
+            zone_flags = ZoneFlags::from_bits_retain(flags(r, &line[4..8], "zone flags")?);
+            zone.min_level = number(r, line[8], "minimum level")?;
+            zone.max_level = number(r, line[9], "maximum level")?;
+        } else if line.len() > 4 {
+            zone_flags = ZoneFlags::from_bits_retain(flags(r, &line[4..line.len().min(8)], "zone flags")?);
+        }
+    }
+
+    let mut commands = Vec::new();
+    loop {
+        let line = r.line("a zone command or 'S'")?;
+        if line == "S" {
+            break;
+        }
+        commands.push(parse_zone_command(r, line)?);
+    }
+
+    Ok(Box::new(move |world: &mut World, id: DbIdentifier| {
+        // Old files only give the top; the zone starts at its own vnum's hundred.
+        if zone.bottom == 0 {
+            zone.bottom = id.id * 100;
+        }
+        world.add_entity((id, Title(name), zone, zone_flags, ZoneCommands(commands)))
+    }))
+}
+
+fn parse_zone_command(r: &Reader, line: &str) -> Result<ZoneCommand, ImportProblem> {
+    let mut tokens = line.split_whitespace();
+    let command = tokens.next().unwrap_or("");
+    // Everything after the numbers is a comment, usually naming what's loaded.
+    let args: Vec<&str> = tokens.collect();
+    let numbers: Vec<i64> = args.iter().map_while(|t| t.parse().ok()).collect();
+
+    let needed = match command {
+        "M" | "O" | "E" | "P" | "D" => 4,
+        "G" | "R" | "T" => 3,
+        "V" => 4,
+        _ => return Err(r.problem(format!("unknown zone command '{}'", line)))
+    };
+    if numbers.len() < needed {
+        return Err(r.problem(format!("zone command '{}' needs {} numbers", line, needed)));
+    }
+    let if_flag = numbers[0] != 0;
+    let vnum_arg = |i: usize| vnum(numbers[i]).ok_or_else(|| r.problem(format!("bad vnum {} in zone command '{}'", numbers[i], line)));
+
+    Ok(match command {
+        "M" => ZoneCommand::Mobile { if_flag, mobile: vnum_arg(1)?, max: numbers[2], room: vnum_arg(3)? },
+        "O" => ZoneCommand::Object { if_flag, object: vnum_arg(1)?, max: numbers[2], room: vnum_arg(3)? },
+        "G" => ZoneCommand::Give { if_flag, object: vnum_arg(1)?, max: numbers[2] },
+        "E" => ZoneCommand::Equip { if_flag, object: vnum_arg(1)?, max: numbers[2], position: numbers[3] },
+        "P" => ZoneCommand::Put { if_flag, object: vnum_arg(1)?, max: numbers[2], container: vnum_arg(3)? },
+        "D" => ZoneCommand::Door {
+            if_flag,
+            room: vnum_arg(1)?,
+            direction: u8::try_from(numbers[2]).ok().and_then(Direction::from_value)
+                .ok_or_else(|| r.problem(format!("bad direction in zone command '{}'", line)))?,
+            state: numbers[3].clamp(0, 2) as u8
+        },
+        "R" => ZoneCommand::Remove { if_flag, room: vnum_arg(1)?, object: vnum_arg(2)? },
+        "T" => ZoneCommand::Trigger {
+            if_flag,
+            attach_type: numbers[1].clamp(0, 2) as u8,
+            trigger: vnum_arg(2)?,
+            room: numbers.get(3).and_then(|n| vnum(*n))
+        },
+        _ => {
+            // V if_flag attach_type context room name value...
+            let name = args.get(4).ok_or_else(|| r.problem(format!("zone command '{}' has no variable name", line)))?;
+            ZoneCommand::Variable {
+                if_flag,
+                attach_type: numbers[1].clamp(0, 2) as u8,
+                context: numbers[2],
+                room: vnum_arg(3)?,
+                name: name.to_string(),
+                value: args.get(5..).map(|v| v.join(" ")).unwrap_or_default()
+            }
+        }
+    })
+}
+
+fn parse_trigger(r: &mut Reader, _problems: &mut Vec<ImportProblem>) -> Result<Spawn, ImportProblem> {
+    let name = r.string("trigger name")?;
+    // attach_type trigger_types narg
+    let line = fields(r, 3, "attach type, trigger types and numeric argument")?;
+    let trigger = TriggerProto {
+        attach_type: number(r, line[0], "attach type")?,
+        trigger_types: parse_flags(line[1]).ok_or_else(|| r.problem(format!("bad trigger types '{}'", line[1])))?,
+        narg: number(r, line[2], "numeric argument")?,
+        arglist: r.string("trigger argument")?,
+        commands: r.string("trigger commands")?
+    };
+
+    Ok(Box::new(move |world: &mut World, id: DbIdentifier| world.add_entity((id, Title(name), trigger))))
+}
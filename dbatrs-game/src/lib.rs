@@ -1,2 +1,76 @@
+use std::{
+    future::Future,
+    sync::Arc
+};
+
+use shipyard::UniqueView;
+use surrealdb::{Surreal, engine::any::Any};
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+use dbatrs_shared::TotalConf;
+
+use crate::{
+    game_loop::GameLoop,
+    persist::Saver,
+    structs::common::DbIndexes,
+    systems::session::{Inbox, Outbox}
+};
+
+pub mod game_loop;
+pub mod legacy;
+pub mod link;
+pub mod persist;
 pub mod structs;
-pub mod systems;
\ No newline at end of file
+pub mod systems;
+
+// Runs the game until shutdown resolves. db is a root connection with migrations
+// already applied.
+pub async fn run(conf: Arc<TotalConf>, db: Surreal<Any>, shutdown: impl Future<Output = ()>) -> Result<(), Box<dyn std::error::Error>> {
+    let mut game = GameLoop::new(&conf.game)?;
+
+    let loaded = persist::load_world(&mut game.world, &db).await?;
+    for problem in loaded.problems.iter() {
+        warn!("{}", problem);
+    }
+    info!("Loaded {} entities from the database.", loaded.entities);
+
+    // The legacy world only seeds an empty database; after that, the database is the world.
+    let has_rooms = game.world.run(|indexes: UniqueView<DbIndexes>| !indexes.rooms.is_empty());
+    if !conf.game.legacy_world.is_empty() && !has_rooms {
+        let dir = conf.resolve_path(&conf.game.legacy_world);
+        info!("Importing legacy world files from {}...", dir.display());
+        let report = legacy::world::import_world(&mut game.world, &dir);
+        for problem in report.problems.iter() {
+            warn!("{}", problem);
+        }
+        info!("Imported {} zones, {} rooms, {} item, {} NPC and {} trigger prototypes ({} problems).",
+              report.zones, report.rooms, report.items, report.npcs, report.triggers, report.problems.len());
+    }
+
+    let (saver, writer) = persist::spawn_writer(db.clone());
+    game.world.add_unique(saver);
+    let (events, inbox) = mpsc::unbounded_channel();
+    let (outbox, output) = mpsc::unbounded_channel();
+    game.world.add_unique(Inbox(inbox));
+    game.world.add_unique(Outbox(outbox));
+
+    info!("Starting all tasks...");
+    info!("Running the game loop at {} pulses per second.", conf.game.pulses_per_second);
+
+    let result = tokio::select! {
+        result = link::run(conf.clone(), db, events, output) => result,
+        _ = game.run() => Ok(()),
+        _ = shutdown => {
+            info!("Shutdown requested.");
+            Ok(())
+        }
+    };
+
+    // Whatever stopped us, don't lose up to a whole autosave interval.
+    game.run_workload(game_loop::AUTOSAVE);
+    // Dropping the Saver lets the writer finish what's queued and stop.
+    let _ = game.world.remove_unique::<Saver>();
+    let _ = writer.await;
+    result
+}
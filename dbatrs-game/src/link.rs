@@ -0,0 +1,109 @@
+use std::sync::Arc;
+
+use serde_json::Value as JsonValue;
+use surrealdb::{RecordId, Surreal, engine::any::Any};
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tracing::{debug, info, warn};
+
+use dbatrs_shared::{
+    TotalConf,
+    protocol::{ToGame, ToPortal},
+    transport::{GameAcceptor, Transport}
+};
+
+// What the link tells the game loop about the portal's connections.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LinkEvent {
+    // conn has logged in to play the character whose pc record has this id, or has no
+    // character to play.
+    Login { conn: RecordId, pc: Option<usize> },
+    Input { conn: RecordId, command: String },
+    Logout { conn: RecordId }
+}
+
+// Accepts a link from the portal and relays between it and the game loop until it closes,
+// then waits for the next one. Output sent while there's no link waits for one.
+pub async fn run(conf: Arc<TotalConf>, db: Surreal<Any>, events: UnboundedSender<LinkEvent>,
+                 mut output: UnboundedReceiver<ToPortal>) -> Result<(), Box<dyn std::error::Error>> {
+    let mut acceptor = GameAcceptor::bind(&conf, db.clone()).await?;
+    info!("Waiting for the portal over the {:?} transport...", conf.transport.kind);
+
+    loop {
+        let mut link = match acceptor.accept().await {
+            Ok(link) => link,
+            Err(e) => {
+                warn!("Could not accept a portal link: {}", e);
+                tokio::time::sleep(std::time::Duration::from_secs(conf.transport.reconnect_interval)).await;
+                continue;
+            }
+        };
+        info!("Portal linked.");
+
+        loop {
+            tokio::select! {
+                msg = link.recv() => match msg {
+                    Ok(Some(msg)) => forward(&db, &events, msg).await,
+                    Ok(None) => {
+                        info!("Portal link closed.");
+                        break;
+                    },
+                    Err(e) => {
+                        warn!("Lost the portal link: {}", e);
+                        break;
+                    }
+                },
+                Some(msg) = output.recv() => {
+                    if let Err(e) = link.send(msg).await {
+                        warn!("Lost the portal link: {}", e);
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
+async fn forward(db: &Surreal<Any>, events: &UnboundedSender<LinkEvent>, msg: ToGame) {
+    let event = match msg {
+        ToGame::Connected { conn, user } => {
+            let pc = session_character(db, &user).await.unwrap_or_else(|e| {
+                warn!("Could not find a character for {}: {}", user, e);
+                None
+            });
+            LinkEvent::Login { conn, pc }
+        },
+        ToGame::Input { conn, command } => LinkEvent::Input { conn, command },
+        ToGame::Disconnected { conn } => LinkEvent::Logout { conn },
+        other => {
+            debug!("From portal: {:?}", other);
+            return;
+        }
+    };
+    let _ = events.send(event);
+}
+
+// The character user's game_session is for. Without one, a session is started with their
+// first character, since there's no character selection yet.
+pub async fn session_character(db: &Surreal<Any>, user: &RecordId) -> Result<Option<usize>, surrealdb::Error> {
+    let session: Vec<JsonValue> = db.query("SELECT VALUE record::id(pc) FROM game_session WHERE user = $user LIMIT 1")
+        .bind(("user", user.clone()))
+        .await?
+        .take(0)?;
+    if let Some(pc) = session.first() {
+        return Ok(pc.as_u64().map(|pc| pc as usize));
+    }
+
+    let characters: Vec<JsonValue> = db.query("SELECT VALUE record::id(id) FROM pc WHERE user = $user")
+        .bind(("user", user.clone()))
+        .await?
+        .take(0)?;
+    let Some(pc) = characters.iter().filter_map(JsonValue::as_u64).min() else {
+        return Ok(None);
+    };
+    db.query("CREATE game_session SET user = $user, pc = type::thing('pc', $pc) RETURN NONE")
+        .bind(("user", user.clone()))
+        .bind(("pc", pc as i64))
+        .await?
+        .check()?;
+    Ok(Some(pc as usize))
+}
@@ -1,5 +1,6 @@
 use std::{
     net::{IpAddr, SocketAddr},
+    path::PathBuf,
     sync::Arc
 };
 
@@ -9,46 +10,115 @@ use tokio;
 use tokio::sync::mpsc::{Sender, Receiver, channel};
 
 use tracing::{error, info, Level};
-use tracing_subscriber;
-use tracing_subscriber::layer::SubscriberExt;
-use tracing_subscriber::{prelude::*, fmt};
-
-use dbatrs_shared::TotalConf;
 
+use clap::{Parser, Subcommand};
+
+use dbatrs_shared::{ConfArgs, TotalConf, migrate};
+
+
+#[derive(Parser, Debug)]
+#[command(name = "dbatrs-game", version)]
+struct Cli {
+    #[command(flatten)]
+    conf: ConfArgs,
+    #[command(subcommand)]
+    command: Option<Command>
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Apply pending schema migrations, then exit.
+    Migrate {
+        /// Only list applied and pending migrations.
+        #[arg(long)]
+        status: bool
+    },
+    /// Import accounts and characters from a legacy game's lib directory, then exit.
+    ImportPlayers {
+        /// The directory holding user/, plrfiles/ and plrobjs/.
+        dir: PathBuf
+    },
+    /// Set an account's password and clear its password reset flag, then exit. Imported
+    /// accounts can't sign in until this is done.
+    SetPassword {
+        /// The account's name or email.
+        account: String,
+        password: String
+    }
+}
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-
-    // This check will also ensure we're in the right directory.
-    let conf = Arc::new(TotalConf::set("devel")?);
-
-    // TODO: Make this more configurable and save to the logs directory.
-    // HECK, modularize it into dbatrs-shared somehow...
-
-    // Create a formatting layer. Customize this as needed (targets, time, etc.)
-    let fmt_layer = fmt::layer()
-        .with_target(true)
-        .with_level(true);
-
-    // Create the tracy profiling layer.
-    let tracy_layer = tracy_full::tracing::TracyLayer;
-
-    // Build a composite subscriber that includes the logging filter, formatted logging, and Tracy profiling.
-    let subscriber = tracing_subscriber::registry()
-        .with(fmt_layer)
-        .with(tracy_layer);
-
-    // Set this as the global default subscriber.
-    tracing::subscriber::set_global_default(subscriber)
-        .expect("Failed to set global subscriber");
+    let cli = Cli::parse();
+
+    let conf: TotalConf = match cli.conf.load() {
+        Ok(conf) => conf,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    if cli.conf.check_config {
+        println!("Configuration for mode '{}' in {} is valid.", cli.conf.mode, cli.conf.config_dir.display());
+        return Ok(());
+    }
+
+    if conf.surreal.engine.is_embedded() {
+        eprintln!("surreal.engine is '{:?}', but embedded databases only work with dbatrs-dev.", conf.surreal.engine);
+        std::process::exit(1);
+    }
+
+    match cli.command {
+        Some(Command::Migrate { status }) => {
+            let db = dbatrs_shared::db::connect_root(&conf).await?;
+            return Ok(migrate::run_migrate(&db, status).await?);
+        },
+        Some(Command::ImportPlayers { dir }) => {
+            let db = dbatrs_shared::db::connect_root(&conf).await?;
+            migrate::startup(&db, &conf.surreal).await?;
+            return run_import_players(&db, &dir).await;
+        },
+        Some(Command::SetPassword { account, password }) => {
+            let db = dbatrs_shared::db::connect_root(&conf).await?;
+            migrate::startup(&db, &conf.surreal).await?;
+            if !dbatrs_game::legacy::player::set_password(&db, &account, &password).await? {
+                eprintln!("There is no account named {}.", account);
+                std::process::exit(1);
+            }
+            println!("Set a new password for {}.", account);
+            return Ok(());
+        },
+        None => {}
+    }
+
+    let conf = Arc::new(conf);
+
+    dbatrs_shared::logging::init(&conf, "game")?;
 
     info!("dbatrs-game starting up...");
 
-    //let mut v = Vec::new();
+    let db = dbatrs_shared::db::connect_root(&conf).await?;
+    if let Err(e) = migrate::startup(&db, &conf.surreal).await {
+        error!("{}", e);
+        std::process::exit(1);
+    }
 
-    info!("Starting all tasks...");
-    //join_all(v).await;
+    dbatrs_game::run(conf, db, dbatrs_shared::shutdown::wait_for_signal()).await?;
 
-    info!("dbatrs-portal shutting down.");
+    info!("dbatrs-game shutting down.");
+    Ok(())
+}
+
+async fn run_import_players(db: &surrealdb::Surreal<surrealdb::engine::any::Any>, dir: &std::path::Path) -> Result<(), Box<dyn std::error::Error>> {
+    let mut world = shipyard::World::new();
+    let report = dbatrs_game::legacy::player::import_players(&mut world, db, dir).await;
+    let snapshot = world.run(|all: shipyard::AllStoragesView| dbatrs_game::persist::snapshot_all(&all));
+    dbatrs_game::persist::write(db, &snapshot).await?;
+    for problem in report.problems.iter() {
+        println!("{}", problem);
+    }
+    println!("Imported {} account(s) and {} character(s) ({} problem(s)). Imported accounts can't sign in until given a new password with set-password.",
+             report.accounts, report.players, report.problems.len());
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,334 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value as JsonValue};
+use shipyard::{
+    AddComponent, AllStorages, AllStoragesView, Component, EntityId, Get, IntoIter, IntoWithId, Unique, UniqueView, UniqueViewMut,
+    View, ViewMut, World, track
+};
+use surrealdb::{Surreal, engine::any::Any};
+use tokio::{sync::mpsc, task::JoinHandle};
+use tracing::warn;
+
+use crate::{
+    structs::{
+        character::{MobFlags, MovePoints, NpcStats, Position, Race, Sensei, Sex},
+        common::{
+            AffectFlags, DbEntityType, DbIdentifier, DbIndexes, Description, ExtraDescriptions, Keywords, Location, Player,
+            Scripts, ShortDesc, ThingContents, ThingSize, ThingType, Title, UserAccount, Weight
+        },
+        item::{AntiRace, AntiSensei, Cost, ItemAffects, ItemFlags, ItemType, ItemValues, OnlyRace, OnlySensei, Prototype, SavedItems, WearFlags},
+        room::{Exits, People, RoomFlags, SectorType},
+        script::TriggerProto,
+        zone::{Zone, ZoneCommands, ZoneFlags}
+    },
+    systems::{indexes::sync_indexes, location::is_person_in_room}
+};
+
+// Rows per query when writing a snapshot.
+const BATCH: usize = 500;
+
+// Every persistent component, and Location, is declared with this tracking, so the next
+// save can find whatever changed without anything having to mark it.
+type Tracked = track::InsertionAndModificationAndRemoval;
+
+// Records of persistent entities deleted since the last save. Deleting an entity leaves
+// nothing tracked behind, so whatever deletes one with a DbIdentifier should add it here.
+#[derive(Unique, Debug, Default)]
+pub struct DeletedRecords(pub Vec<DbIdentifier>);
+
+// Adds the entities whose T was inserted, modified or removed since the last call to
+// changed, and starts tracking T afresh.
+fn take_changed<T: Component<Tracking = Tracked>>(all: &AllStorages, changed: &mut HashSet<EntityId>) {
+    let mut view = all.borrow::<ViewMut<T>>().unwrap();
+    // Outside a workload, removals are only reported within a window we give; this one
+    // covers everything still recorded.
+    view.override_last_removal_or_deletion(all.get_tracking_timestamp().furthest_from());
+    changed.extend(view.inserted_or_modified().iter().with_id().map(|(entity, _)| entity));
+    changed.extend(view.removed());
+    view.clear_all_removed();
+    view.clear_all_inserted_and_modified();
+}
+
+// One ent record: an entity's saved components, keyed by their type names.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EntRow {
+    pub ent_type: String,
+    pub db_id: i64,
+    pub components: Map<String, JsonValue>
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct EntKey {
+    ent_type: &'static str,
+    db_id: i64
+}
+
+// What to write to bring the database up to date.
+#[derive(Debug, Default)]
+pub struct Snapshot {
+    pub rows: Vec<EntRow>,
+    pub removed: Vec<DbIdentifier>
+}
+
+impl Snapshot {
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty() && self.removed.is_empty()
+    }
+}
+
+// Locations are saved by what holds the entity, since EntityIds don't survive a restart.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SavedLocation {
+    holder: DbIdentifier,
+    slot: Option<usize>
+}
+
+// The components saved to and loaded from ent records. Anything runtime-only, like People
+// and ThingContents, is left out and rebuilt from Location instead.
+macro_rules! persistent_components {
+    ($($component:ident),* $(,)?) => {
+        // Adds each entity's persistent components to its row, in the same order.
+        fn save_components(all: &AllStorages, entities: &[EntityId], rows: &mut [Map<String, JsonValue>]) {
+            $(
+                let view = all.borrow::<View<$component>>().unwrap();
+                for (entity, row) in entities.iter().zip(rows.iter_mut()) {
+                    if let Ok(component) = view.get(*entity) {
+                        match serde_json::to_value(component) {
+                            Ok(value) => {
+                                row.insert(stringify!($component).to_string(), value);
+                            },
+                            Err(e) => warn!("Could not save {} of {:?}: {}", stringify!($component), entity, e)
+                        }
+                    }
+                }
+            )*
+        }
+
+        // Every entity with a persistent component or Location changed since the last call.
+        fn take_all_changed(all: &AllStorages) -> HashSet<EntityId> {
+            let mut changed = HashSet::new();
+            $(
+                take_changed::<$component>(all, &mut changed);
+            )*
+            take_changed::<Location>(all, &mut changed);
+            changed
+        }
+
+        // Adds a saved component to entity. False if name isn't a persistent component.
+        fn load_component(world: &mut World, entity: EntityId, name: &str, value: JsonValue) -> Result<bool, serde_json::Error> {
+            match name {
+                $(
+                    stringify!($component) => world.add_component(entity, serde_json::from_value::<$component>(value)?),
+                )*
+                _ => return Ok(false)
+            }
+            Ok(true)
+        }
+    };
+}
+
+persistent_components!(
+    ThingType, UserAccount, Player, Title, Description, Keywords, ShortDesc, ExtraDescriptions, Weight, Scripts,
+    AffectFlags, ThingSize,
+    Race, Sensei, Sex, Position, MovePoints, MobFlags, NpcStats,
+    ItemType, ItemFlags, WearFlags, ItemValues, ItemAffects, Cost, OnlySensei, AntiSensei, OnlyRace, AntiRace, Prototype, SavedItems,
+    RoomFlags, SectorType, Exits,
+    Zone, ZoneFlags, ZoneCommands,
+    TriggerProto
+);
+
+// Rows for the given entities. Entities without a DbIdentifier aren't persistent and are
+// skipped.
+pub fn snapshot(all: &AllStorages, entities: impl IntoIterator<Item = EntityId>) -> Vec<EntRow> {
+    let ids = all.borrow::<View<DbIdentifier>>().unwrap();
+    let locations = all.borrow::<View<Location>>().unwrap();
+
+    let entities: Vec<EntityId> = entities.into_iter().filter(|entity| ids.contains(*entity)).collect();
+    let mut components = vec![Map::new(); entities.len()];
+    save_components(all, &entities, &mut components);
+
+    entities.iter().zip(components).map(|(entity, mut components)| {
+        let location = locations.get(*entity).ok().and_then(|location| {
+            let (holder, slot) = match location {
+                Location::Inventory(holder) => (holder, None),
+                Location::Equipped(holder, slot) => (holder, Some(*slot))
+            };
+            // Something held by a non-persistent entity doesn't keep its place.
+            ids.get(*holder).ok().map(|holder| SavedLocation { holder: holder.clone(), slot })
+        });
+        if let Some(location) = location {
+            components.insert("Location".to_string(), serde_json::to_value(location).unwrap());
+        }
+
+        let id = ids.get(*entity).unwrap();
+        EntRow { ent_type: id.entity_type.name().to_string(), db_id: id.id as i64, components }
+    }).collect()
+}
+
+// Everything changed or deleted since the last call.
+pub fn take_dirty(all: &AllStorages) -> Snapshot {
+    let entities = take_all_changed(all);
+    // A world without the unique has nothing to delete.
+    let removed = all.borrow::<UniqueViewMut<DeletedRecords>>()
+        .map(|mut deleted| std::mem::take(&mut deleted.0))
+        .unwrap_or_default();
+    Snapshot { rows: snapshot(all, entities), removed }
+}
+
+// Every persistent entity.
+pub fn snapshot_all(all: &AllStorages) -> Snapshot {
+    let entities: Vec<EntityId> = all.borrow::<View<DbIdentifier>>().unwrap().iter().with_id().map(|(entity, _)| entity).collect();
+    Snapshot { rows: snapshot(all, entities), removed: Vec::new() }
+}
+
+pub async fn write(db: &Surreal<Any>, snapshot: &Snapshot) -> Result<(), surrealdb::Error> {
+    for rows in snapshot.rows.chunks(BATCH) {
+        db.query("FOR $row IN $rows { UPSERT type::thing('ent', [$row.ent_type, $row.db_id]) SET ent_type = $row.ent_type, \
+                  db_id = $row.db_id, components = $row.components, time_saved = time::now() RETURN NONE; };")
+            .bind(("rows", rows.to_vec()))
+            .await?
+            .check()?;
+    }
+    if !snapshot.removed.is_empty() {
+        let keys: Vec<EntKey> = snapshot.removed.iter()
+            .map(|id| EntKey { ent_type: id.entity_type.name(), db_id: id.id as i64 })
+            .collect();
+        db.query("FOR $key IN $keys { DELETE type::thing('ent', [$key.ent_type, $key.db_id]); };")
+            .bind(("keys", keys))
+            .await?
+            .check()?;
+    }
+    Ok(())
+}
+
+// Hands snapshots to the writer task, so a pulse never waits on the database.
+#[derive(Unique)]
+pub struct Saver(mpsc::UnboundedSender<Snapshot>);
+
+// Starts a task that writes each snapshot sent to the returned Saver. A snapshot that
+// fails to write is retried ahead of the next one. The task ends once the Saver is
+// dropped and everything queued has been tried.
+pub fn spawn_writer(db: Surreal<Any>) -> (Saver, JoinHandle<()>) {
+    let (tx, mut rx) = mpsc::unbounded_channel::<Snapshot>();
+    let task = tokio::spawn(async move {
+        let mut failed: Option<Snapshot> = None;
+        while let Some(mut snapshot) = rx.recv().await {
+            // Older rows go first, so anything saved again since wins.
+            if let Some(mut earlier) = failed.take() {
+                earlier.rows.append(&mut snapshot.rows);
+                earlier.removed.append(&mut snapshot.removed);
+                snapshot = earlier;
+            }
+            if let Err(e) = write(&db, &snapshot).await {
+                warn!("Could not save {} entities; will retry with the next save: {}", snapshot.rows.len(), e);
+                failed = Some(snapshot);
+            }
+        }
+        if let Some(snapshot) = failed {
+            warn!("Exiting with {} entities unsaved.", snapshot.rows.len());
+        }
+    });
+    (Saver(tx), task)
+}
+
+// The AUTOSAVE workload's system. Does nothing until a Saver has been added.
+pub fn autosave(all: AllStoragesView) {
+    let Ok(saver) = all.borrow::<UniqueView<Saver>>() else {
+        return;
+    };
+    let snapshot = take_dirty(&all);
+    if !snapshot.is_empty() && saver.0.send(snapshot).is_err() {
+        warn!("The save writer has stopped; changes are not being saved.");
+    }
+}
+
+// What load_world found, besides the entities themselves.
+#[derive(Debug, Default)]
+pub struct LoadReport {
+    pub entities: usize,
+    pub problems: Vec<String>
+}
+
+// Creates an entity for every ent record, then indexes them and rebuilds everything
+// derived from Location. Records or components that can't be read are reported and skipped.
+pub async fn load_world(world: &mut World, db: &Surreal<Any>) -> Result<LoadReport, surrealdb::Error> {
+    let rows: Vec<EntRow> = db.query("SELECT ent_type, db_id, components FROM ent").await?.take(0)?;
+    let mut report = LoadReport::default();
+    let mut locations = Vec::new();
+
+    for row in rows {
+        let (Some(entity_type), Ok(id)) = (DbEntityType::from_name(&row.ent_type), usize::try_from(row.db_id)) else {
+            report.problems.push(format!("ent:[{}, {}] has an unknown type or a bad id", row.ent_type, row.db_id));
+            continue;
+        };
+        let entity = world.add_entity(DbIdentifier { id, entity_type });
+        report.entities += 1;
+
+        for (name, value) in row.components {
+            let loaded = if name == "Location" {
+                serde_json::from_value::<SavedLocation>(value).map(|location| locations.push((entity, location))).map(|_| true)
+            } else {
+                load_component(world, entity, &name, value)
+            };
+            match loaded {
+                Ok(true) => {},
+                Ok(false) => report.problems.push(format!("ent:[{}, {}] has an unknown component {}", row.ent_type, id, name)),
+                Err(e) => report.problems.push(format!("ent:[{}, {}] has an unreadable {}: {}", row.ent_type, id, name, e))
+            }
+        }
+    }
+
+    // Records are keyed by type and id, so there can't be duplicates.
+    if let Err(e) = sync_indexes(world) {
+        report.problems.push(e.to_string());
+    }
+    world.run(|indexes: UniqueView<DbIndexes>, mut stored: ViewMut<Location>| {
+        for (entity, location) in locations {
+            let Some(holder) = indexes.get(&location.holder.entity_type, location.holder.id) else {
+                report.problems.push(format!("{:?} is held by {:?} #{}, which doesn't exist",
+                                             entity, location.holder.entity_type, location.holder.id));
+                continue;
+            };
+            let location = match location.slot {
+                Some(slot) => Location::Equipped(holder, slot),
+                None => Location::Inventory(holder)
+            };
+            stored.add_component_unchecked(entity, location);
+        }
+    });
+
+    rebuild_contents(world);
+    // What was just loaded is already saved.
+    world.run(|all: AllStoragesView| take_all_changed(&all));
+    Ok(report)
+}
+
+// Rebuilds every ThingContents and People from Location. Characters in a room are its
+// People; everything else held goes in the holder's ThingContents.
+pub fn rebuild_contents(world: &World) {
+    world.run(|locations: View<Location>, types: View<ThingType>, mut contents: ViewMut<ThingContents>, mut people: ViewMut<People>| {
+        contents.clear();
+        people.clear();
+
+        let mut held: HashMap<EntityId, ThingContents> = HashMap::new();
+        let mut present: HashMap<EntityId, People> = HashMap::new();
+        for (entity, location) in locations.iter().with_id() {
+            match location {
+                Location::Inventory(holder) if is_person_in_room(&types, entity, *holder) => {
+                    present.entry(*holder).or_default().0.push(entity);
+                },
+                Location::Inventory(holder) => held.entry(*holder).or_default().items.push(entity),
+                Location::Equipped(holder, slot) => {
+                    held.entry(*holder).or_default().equipment.insert(*slot, entity);
+                }
+            }
+        }
+        for (holder, things) in held {
+            contents.add_component_unchecked(holder, things);
+        }
+        for (room, characters) in present {
+            people.add_component_unchecked(room, characters);
+        }
+    });
+}
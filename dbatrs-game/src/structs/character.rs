@@ -7,6 +7,7 @@ use crate::{bitflags_with_str, enum_with_str};
 
 enum_with_str! {
     #[derive(Component, Hash)]
+    #[track(Insertion, Modification, Removal)]
     pub enum Race: u8 {
         Spirit = 0, // Spirit
         Human = 1, // Human
@@ -37,6 +38,7 @@ enum_with_str! {
 
 enum_with_str! {
     #[derive(Component, Hash)]
+    #[track(Insertion, Modification, Removal)]
     pub enum Sensei: u8 {
         Commoner = 0, // Commoner
         Roshi = 1, // Roshi
@@ -56,11 +58,104 @@ enum_with_str! {
     }
 }
 
+bitflags_with_str! {
+    #[derive(Component, Default, Debug, Serialize, Deserialize)]
+    #[track(Insertion, Modification, Removal)]
+    pub struct MobFlags: u64 {
+        const SPEC = 1 << 0; // Mob has a callable spec-proc
+        const SENTINEL = 1 << 1; // Mob should not move
+        const NOSCAVENGER = 1 << 2; // Mob won't pick up items from rooms
+        const ISNPC = 1 << 3; // Automatically set on all Mobs
+        const AWARE = 1 << 4; // Mob can't be backstabbed
+        const AGGRESSIVE = 1 << 5; // Mob auto-attacks everybody nearby
+        const STAY_ZONE = 1 << 6; // Mob shouldn't wander out of zone
+        const WIMPY = 1 << 7; // Mob flees if severely injured
+        const AGGR_EVIL = 1 << 8; // Auto-attack any evil PC's
+        const AGGR_GOOD = 1 << 9; // Auto-attack any good PC's
+        const AGGR_NEUTRAL = 1 << 10; // Auto-attack any neutral PC's
+        const MEMORY = 1 << 11; // Remember attackers if attacked
+        const HELPER = 1 << 12; // Attack PCs fighting other NPCs
+        const NOCHARM = 1 << 13; // Mob can't be charmed
+        const NOSUMMON = 1 << 14; // Mob can't be summoned
+        const NOSLEEP = 1 << 15; // Mob can't be slept
+        const AUTOBALANCE = 1 << 16; // Mob stats autobalance
+        const NOBLIND = 1 << 17; // Mob can't be blinded
+        const NOKILL = 1 << 18; // Mob can't be killed
+        const NOTDEADYET = 1 << 19; // Mob being extracted
+        const MOUNTABLE = 1 << 20; // Mob is mountable
+        const RARM = 1 << 21; // Has a right arm
+        const LARM = 1 << 22; // Has a left arm
+        const RLEG = 1 << 23; // Has a right leg
+        const LLEG = 1 << 24; // Has a left leg
+        const HEAD = 1 << 25; // Has a head
+        const JUSTDESC = 1 << 26; // Only shown by its description
+        const HUSK = 1 << 27; // Is an extracted husk
+        const SPAR = 1 << 28; // Will spar
+        const DUMMY = 1 << 29; // Is a training dummy
+        const ABSORB = 1 << 30; // Absorb-type android
+        const REPAIR = 1 << 31; // Repair-type android
+        const NOPOISON = 1 << 32; // Can't be poisoned
+        const KNOWKAIO = 1 << 33; // Knows kaioken
+        const POWERUP = 1 << 34; // Powers up in combat
+    }
+}
+
+// A roll of count dice with the given number of sides, plus bonus. Written 3d8+10.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Dice {
+    pub count: u32,
+    pub sides: u32,
+    pub bonus: i64
+}
+
+// The numbers an NPC prototype is created with.
+#[derive(Component, Clone, Debug, Default, Serialize, Deserialize)]
+#[track(Insertion, Modification, Removal)]
+pub struct NpcStats {
+    pub level: i64,
+    pub hitroll: i64,
+    pub armor: i64,
+    pub hit_dice: Dice,
+    pub damage_dice: Dice,
+    pub gold: i64,
+    pub exp: i64,
+    pub alignment: i64,
+    pub position: u8,
+    pub default_position: u8
+}
+
 enum_with_str! {
     #[derive(Component)]
+    #[track(Insertion, Modification, Removal)]
     pub enum Sex : u8 {
         Neuter = 0,
         Male = 1,
         Female = 2
     }
-}
\ No newline at end of file
+}
+
+enum_with_str! {
+    // What a character is doing with their body, worst first; commands set a minimum.
+    #[derive(Component, PartialOrd, Ord, Default)]
+    #[track(Insertion, Modification, Removal)]
+    pub enum Position : u8 {
+        Dead = 0,
+        MortallyWounded = 1,
+        Incapacitated = 2,
+        Stunned = 3,
+        Sleeping = 4,
+        Resting = 5,
+        Sitting = 6,
+        Fighting = 7,
+        #[default]
+        Standing = 8
+    }
+}
+
+// Movement points. Walking costs some, depending on the terrain at both ends.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[track(Insertion, Modification, Removal)]
+pub struct MovePoints {
+    pub current: i64,
+    pub max: i64
+}
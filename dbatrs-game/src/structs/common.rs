@@ -1,4 +1,7 @@
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, hash_map::Entry},
+    fmt
+};
 use shipyard::{Component, Unique, EntityId};
 use chrono::{DateTime, Utc};
 use serde::{Serialize, Deserialize, Deserializer};
@@ -20,13 +23,16 @@ pub enum DbEntityType {
 }
 
 // This is a component used for storing the unique identifier of an entity in the database.
+// Tracked so systems::indexes can keep DbIndexes in step with it.
 #[derive(Component, Clone, Debug, Serialize, Deserialize)]
+#[track(Insertion, Modification, Deletion, Removal)]
 pub struct DbIdentifier {
     pub id: usize,
     pub entity_type: DbEntityType
 }
 
 // This unique component indexes all entities by their unique identifier in the database.
+// systems::indexes maintains it; nothing else should insert into it.
 #[derive(Unique, Clone, Debug, Default)]
 pub struct DbIndexes {
     pub zones: HashMap<usize, EntityId>,
@@ -40,8 +46,130 @@ pub struct DbIndexes {
     pub players: HashMap<usize, EntityId>
 }
 
+impl DbEntityType {
+    // The name stored in ent_type.
+    pub fn name(&self) -> &'static str {
+        match self {
+            DbEntityType::Zone => "zone",
+            DbEntityType::Room => "room",
+            DbEntityType::Player => "player",
+            DbEntityType::NpcProto => "npc_proto",
+            DbEntityType::ItemProto => "item_proto",
+            DbEntityType::ScriptProto => "script_proto",
+            DbEntityType::Shop => "shop",
+            DbEntityType::Guild => "guild",
+            DbEntityType::UserAccount => "user_account"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "zone" => DbEntityType::Zone,
+            "room" => DbEntityType::Room,
+            "player" => DbEntityType::Player,
+            "npc_proto" => DbEntityType::NpcProto,
+            "item_proto" => DbEntityType::ItemProto,
+            "script_proto" => DbEntityType::ScriptProto,
+            "shop" => DbEntityType::Shop,
+            "guild" => DbEntityType::Guild,
+            "user_account" => DbEntityType::UserAccount,
+            _ => return None
+        })
+    }
+}
+
+// Two entities with the same type and id. Only the first is indexed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateVnum {
+    pub entity_type: DbEntityType,
+    pub id: usize,
+    pub existing: EntityId,
+    pub duplicate: EntityId
+}
+
+impl fmt::Display for DuplicateVnum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} #{} is both {:?} and {:?}", self.entity_type, self.id, self.existing, self.duplicate)
+    }
+}
+
+impl std::error::Error for DuplicateVnum {}
+
+impl DbIndexes {
+    pub fn of_type(&self, entity_type: &DbEntityType) -> &HashMap<usize, EntityId> {
+        match entity_type {
+            DbEntityType::Zone => &self.zones,
+            DbEntityType::Room => &self.rooms,
+            DbEntityType::Player => &self.players,
+            DbEntityType::NpcProto => &self.npc_proto,
+            DbEntityType::ItemProto => &self.item_proto,
+            DbEntityType::ScriptProto => &self.script_proto,
+            DbEntityType::Shop => &self.shops,
+            DbEntityType::Guild => &self.guilds,
+            DbEntityType::UserAccount => &self.user_accounts
+        }
+    }
+
+    pub fn get(&self, entity_type: &DbEntityType, id: usize) -> Option<EntityId> {
+        self.of_type(entity_type).get(&id).copied()
+    }
+
+    pub fn room(&self, vnum: usize) -> Option<EntityId> {
+        self.rooms.get(&vnum).copied()
+    }
+
+    // Indexes entity under id, unless another entity already has it. Inserting the same
+    // entity again is fine.
+    pub fn insert(&mut self, entity_type: &DbEntityType, id: usize, entity: EntityId) -> Result<(), DuplicateVnum> {
+        match self.of_type_mut(entity_type).entry(id) {
+            Entry::Occupied(existing) if *existing.get() != entity => Err(DuplicateVnum {
+                entity_type: entity_type.clone(),
+                id,
+                existing: *existing.get(),
+                duplicate: entity
+            }),
+            Entry::Occupied(_) => Ok(()),
+            Entry::Vacant(vacant) => {
+                vacant.insert(entity);
+                Ok(())
+            }
+        }
+    }
+
+    // Unindexes id if it belongs to entity.
+    pub fn remove(&mut self, entity_type: &DbEntityType, id: usize, entity: EntityId) {
+        let index = self.of_type_mut(entity_type);
+        if index.get(&id) == Some(&entity) {
+            index.remove(&id);
+        }
+    }
+
+    // Unindexes entity wherever it is, when its id isn't known any more.
+    pub fn forget(&mut self, entity: EntityId) {
+        for index in [&mut self.zones, &mut self.rooms, &mut self.players, &mut self.npc_proto, &mut self.item_proto,
+                      &mut self.script_proto, &mut self.shops, &mut self.guilds, &mut self.user_accounts] {
+            index.retain(|_, e| *e != entity);
+        }
+    }
+
+    fn of_type_mut(&mut self, entity_type: &DbEntityType) -> &mut HashMap<usize, EntityId> {
+        match entity_type {
+            DbEntityType::Zone => &mut self.zones,
+            DbEntityType::Room => &mut self.rooms,
+            DbEntityType::Player => &mut self.players,
+            DbEntityType::NpcProto => &mut self.npc_proto,
+            DbEntityType::ItemProto => &mut self.item_proto,
+            DbEntityType::ScriptProto => &mut self.script_proto,
+            DbEntityType::Shop => &mut self.shops,
+            DbEntityType::Guild => &mut self.guilds,
+            DbEntityType::UserAccount => &mut self.user_accounts
+        }
+    }
+}
+
 // Each UserAccount uses this for its data storage.
 #[derive(Component, Clone, Debug, Serialize, Deserialize)]
+#[track(Insertion, Modification, Removal)]
 pub struct UserAccount {
     pub name: String,
     pub password: String,
@@ -53,11 +181,15 @@ pub struct UserAccount {
     pub admin_level: u8,
     pub rpp: usize,
     pub slots: usize,
-    pub characters: Vec<usize>
+    pub characters: Vec<usize>,
+    // Set for imported accounts, which must choose a new password before logging in.
+    #[serde(default)]
+    pub password_reset: bool
 }
 
 // Each PlayerCharacter uses this for its data storage.
 #[derive(Component, Clone, Debug, Serialize, Deserialize)]
+#[track(Insertion, Modification, Removal)]
 pub struct Player {
     pub name: String,
     pub account: usize
@@ -65,6 +197,7 @@ pub struct Player {
 
 // Used for quick identification of physical things in the game world.
 #[derive(Component, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[track(Insertion, Modification, Removal)]
 pub enum ThingType {
     Room = 0,
     Character = 1,
@@ -73,6 +206,7 @@ pub enum ThingType {
 
 // Everything except Rooms can have a Location component.
 #[derive(Component, Clone, Debug)]
+#[track(Insertion, Modification, Removal)]
 pub enum Location {
     // The EntityId here for Inventory could be an Room, Character, or Item.
     Inventory(EntityId),
@@ -83,13 +217,14 @@ pub enum Location {
 // Items, Characters, and Rooms can all store Items in an inventory.
 // We might as well use Equip here too.
 // This serves as a kind of reverse lookup for the Location component.
-#[derive(Component, Clone, Debug)]
+#[derive(Component, Clone, Debug, Default)]
 pub struct ThingContents {
     pub items: Vec<EntityId>,
     pub equipment: HashMap<usize, EntityId>
 }
 
 #[derive(Component, Clone, Debug, Serialize, Deserialize)]
+#[track(Insertion, Modification, Removal)]
 pub struct ExtraDescriptions {
     pub extras: Vec<(String, String)>
 }
@@ -97,26 +232,38 @@ pub struct ExtraDescriptions {
 // Title is used for anything with an objective name or title.
 // IE: Player Character names, Room Names, Item Names, etc.
 #[derive(Component, Clone, Debug, Serialize, Deserialize)]
+#[track(Insertion, Modification, Removal)]
 pub struct Title(pub String);
 
 // Many things have a Description that's shown when looked at.
 #[derive(Component, Clone, Debug, Serialize, Deserialize)]
+#[track(Insertion, Modification, Removal)]
 pub struct Description(pub String);
 
 // Many things have a sequence of Keywords that can be used to refer to them.
 // Mostly, this is for Items and NPCs.
 #[derive(Component, Clone, Debug, Serialize, Deserialize)]
+#[track(Insertion, Modification, Removal)]
 pub struct Keywords(pub Vec<String>);
 
 // lastly, shortdescs are used for things that are shown in lists.
 #[derive(Component, Clone, Debug, Serialize, Deserialize)]
+#[track(Insertion, Modification, Removal)]
 pub struct ShortDesc(pub String);
 
+#[derive(Component, Clone, Debug, Default, Serialize, Deserialize)]
+#[track(Insertion, Modification, Removal)]
 pub struct Weight(pub f32);
 
+// The trigger prototypes attached to a room, item or NPC, by vnum.
+#[derive(Component, Clone, Debug, Default, Serialize, Deserialize)]
+#[track(Insertion, Modification, Removal)]
+pub struct Scripts(pub Vec<usize>);
+
 
 bitflags_with_str! {
     #[derive(Component, Default, Debug, Serialize, Deserialize)]
+    #[track(Insertion, Modification, Removal)]
     pub struct AffectFlags: u64 {
         const DONTUSE = 1 << 0; // DON'T USE!
         const BLIND = 1 << 1; // Char is blind
@@ -187,6 +334,7 @@ bitflags_with_str! {
 
 enum_with_str! {
     #[derive(Component)]
+    #[track(Insertion, Modification, Removal)]
     pub enum ThingSize : u8 {
         FINE = 0,
         DIMINUTIVE = 1,
@@ -13,6 +13,7 @@ use crate::{
 
 enum_with_str! {
     #[derive(Component)]
+    #[track(Insertion, Modification, Removal)]
     pub enum ItemType : u8 {
         UNKNOWN = 0, // Unknown item type
         LIGHT = 1, // Item is a light source
@@ -135,6 +136,7 @@ enum_with_str! {
 
 bitflags_with_str! {
     #[derive(Component, Default, Debug, Serialize, Deserialize)]
+    #[track(Insertion, Modification, Removal)]
     pub struct WearFlags: u32 {
         const TAKE = 1 << 0;
         const FINGER = 1 << 1;
@@ -161,6 +163,7 @@ bitflags_with_str! {
 
 bitflags_with_str! {
     #[derive(Component, Default, Debug, Serialize, Deserialize)]
+    #[track(Insertion, Modification, Removal)]
     pub struct ItemFlags: u64 {
         const GLOW = 1 << 0; // Item is glowing
         const HUM = 1 << 1; // Item is humming
@@ -206,15 +209,86 @@ bitflags_with_str! {
 
 
 #[derive(Component, Clone, Default, Debug, Serialize, Deserialize)]
+#[track(Insertion, Modification, Removal)]
 pub struct OnlySensei(pub HashSet<Sensei>);
 
 #[derive(Component, Clone, Default, Debug, Serialize, Deserialize)]
+#[track(Insertion, Modification, Removal)]
 pub struct AntiSensei(pub HashSet<Sensei>);
 
 #[derive(Component, Clone, Default, Debug, Serialize, Deserialize)]
+#[track(Insertion, Modification, Removal)]
 pub struct OnlyRace(pub HashSet<Race>);
 
 #[derive(Component, Clone, Default, Debug, Serialize, Deserialize)]
+#[track(Insertion, Modification, Removal)]
 pub struct AntiRace(pub HashSet<Race>);
 
-pub struct Cost(pub u64);
\ No newline at end of file
+#[derive(Component, Clone, Debug, Default, Serialize, Deserialize)]
+#[track(Insertion, Modification, Removal)]
+pub struct Cost(pub u64);
+
+// The type-specific values of an item, e.g. a weapon's damage dice or a container's capacity.
+// Their meaning depends on its ItemType.
+#[derive(Component, Clone, Debug, Default, Serialize, Deserialize)]
+#[track(Insertion, Modification, Removal)]
+pub struct ItemValues(pub Vec<i64>);
+
+bitflags_with_str! {
+    // A CONTAINER's value 1.
+    #[derive(Debug, Clone, Default, Serialize, Deserialize)]
+    pub struct ContainerFlags: u8 {
+        const CLOSEABLE = 1 << 0;
+        const PICKPROOF = 1 << 1;
+        const CLOSED = 1 << 2;
+        const LOCKED = 1 << 3;
+    }
+}
+
+impl ItemValues {
+    pub fn container_flags(&self) -> ContainerFlags {
+        ContainerFlags::from_bits_retain(self.0.get(1).copied().unwrap_or(0) as u8)
+    }
+
+    pub fn set_container_flags(&mut self, flags: ContainerFlags) {
+        if self.0.len() < 2 {
+            self.0.resize(2, 0);
+        }
+        self.0[1] = flags.bits() as i64;
+    }
+
+    // A CONTAINER's value 2 is the vnum of the key that locks it, if positive.
+    pub fn container_key(&self) -> Option<usize> {
+        self.0.get(2).filter(|key| **key > 0).map(|key| *key as usize)
+    }
+}
+
+// The vnum of the item prototype an item was made from.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[track(Insertion, Modification, Removal)]
+pub struct Prototype(pub usize);
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ItemAffect {
+    pub location: i64,
+    pub modifier: i64,
+    pub specific: i64
+}
+
+// Stat modifiers granted while the item is equipped.
+#[derive(Component, Clone, Debug, Default, Serialize, Deserialize)]
+#[track(Insertion, Modification, Removal)]
+pub struct ItemAffects(pub Vec<ItemAffect>);
+
+// An item a player had when last saved, waiting to be loaded back into the world from its
+// prototype. location is 0 if carried, a wear position plus one if worn, or negative if
+// inside the nearest item before it with a smaller depth.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedItem {
+    pub vnum: usize,
+    pub location: i64
+}
+
+#[derive(Component, Clone, Debug, Default, Serialize, Deserialize)]
+#[track(Insertion, Modification, Removal)]
+pub struct SavedItems(pub Vec<SavedItem>);
@@ -4,6 +4,7 @@ pub mod zone;
 pub mod item;
 pub mod character;
 pub mod room;
+pub mod script;
 
 #[macro_export]
 macro_rules! bitflags_with_str {
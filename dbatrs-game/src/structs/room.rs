@@ -23,8 +23,29 @@ enum_with_str! {
     }
 }
 
+impl Direction {
+    // The way back, for keeping both sides of a two-way exit in step.
+    pub fn opposite(&self) -> Direction {
+        match self {
+            Direction::NORTH => Direction::SOUTH,
+            Direction::EAST => Direction::WEST,
+            Direction::SOUTH => Direction::NORTH,
+            Direction::WEST => Direction::EAST,
+            Direction::UP => Direction::DOWN,
+            Direction::DOWN => Direction::UP,
+            Direction::NORTHWEST => Direction::SOUTHEAST,
+            Direction::NORTHEAST => Direction::SOUTHWEST,
+            Direction::SOUTHEAST => Direction::NORTHWEST,
+            Direction::SOUTHWEST => Direction::NORTHEAST,
+            Direction::IN => Direction::OUT,
+            Direction::OUT => Direction::IN
+        }
+    }
+}
+
 bitflags_with_str! {
-    #[derive(Component)]
+    #[derive(Component, Default, Debug, Serialize, Deserialize)]
+    #[track(Insertion, Modification, Removal)]
     pub struct RoomFlags: u128 {
         const DARK = 1 << 0; // Dark
         const DEATH = 1 << 1; // Death trap
@@ -120,6 +141,7 @@ pub struct Exit {
 
 enum_with_str! {
     #[derive(Component)]
+    #[track(Insertion, Modification, Removal)]
     pub enum SectorType: u8 {
         INSIDE = 0,
         CITY = 1,
@@ -145,7 +167,23 @@ impl Default for SectorType {
     }
 }
 
+impl SectorType {
+    // Movement points it takes to cross; a move costs the average of both rooms.
+    pub fn move_cost(&self) -> i64 {
+        match self {
+            SectorType::INSIDE | SectorType::CITY | SectorType::SHOP | SectorType::IMPORTANT => 1,
+            SectorType::WATER_NOSWIM | SectorType::FLYING | SectorType::SPACE => 1,
+            SectorType::FIELD | SectorType::LAVA => 2,
+            SectorType::FOREST | SectorType::DESERT => 3,
+            SectorType::HILLS | SectorType::WATER_SWIM => 4,
+            SectorType::UNDERWATER => 5,
+            SectorType::MOUNTAIN => 6
+        }
+    }
+}
+
 #[derive(Component, Clone, Debug, Serialize, Deserialize, Default)]
+#[track(Insertion, Modification, Removal)]
 pub struct Exits {
     pub exits: HashMap<Direction, Exit>
 }
@@ -0,0 +1,15 @@
+use shipyard::Component;
+use serde::{Serialize, Deserialize};
+
+// A DG script, as loaded from a .trg file. Things run copies of these by vnum; see Scripts.
+#[derive(Component, Clone, Debug, Default, Serialize, Deserialize)]
+#[track(Insertion, Modification, Removal)]
+pub struct TriggerProto {
+    // 0: NPC, 1: item, 2: room.
+    pub attach_type: u8,
+    // The events that fire it. Their meaning depends on attach_type.
+    pub trigger_types: u64,
+    pub narg: i64,
+    pub arglist: String,
+    pub commands: String
+}
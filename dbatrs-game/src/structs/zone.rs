@@ -5,8 +5,54 @@ use serde::{Serialize, Deserialize, Deserializer};
 
 use crate::{bitflags_with_str, enum_with_str};
 
+use crate::structs::room::Direction;
+
+// A zone covers the rooms, items and NPCs with vnums from bottom to top, and is reset
+// every lifespan minutes according to reset_mode.
+#[derive(Component, Clone, Debug, Default, Serialize, Deserialize)]
+#[track(Insertion, Modification, Removal)]
+pub struct Zone {
+    pub bottom: usize,
+    pub top: usize,
+    pub lifespan: u32,
+    // 0: never reset, 1: reset only when no players are in it, 2: always reset.
+    pub reset_mode: u8,
+    pub builders: String,
+    pub min_level: i64,
+    pub max_level: i64
+}
+
+// One step of a zone reset. Give, Equip and Put act on the last thing loaded; if_flag
+// means only run if the previous command succeeded.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum ZoneCommand {
+    // Load an NPC into a room.
+    Mobile { if_flag: bool, mobile: usize, max: i64, room: usize },
+    // Load an item into a room.
+    Object { if_flag: bool, object: usize, max: i64, room: usize },
+    // Load an item into the last NPC's inventory.
+    Give { if_flag: bool, object: usize, max: i64 },
+    // Load an item into one of the last NPC's wear positions.
+    Equip { if_flag: bool, object: usize, max: i64, position: i64 },
+    // Load an item into a container.
+    Put { if_flag: bool, object: usize, max: i64, container: usize },
+    // 0: open, 1: closed, 2: closed and locked.
+    Door { if_flag: bool, room: usize, direction: Direction, state: u8 },
+    // Remove an item from a room.
+    Remove { if_flag: bool, room: usize, object: usize },
+    // Attach a trigger to the last thing loaded, or to a room.
+    Trigger { if_flag: bool, attach_type: u8, trigger: usize, room: Option<usize> },
+    // Set a script variable on the last thing loaded, or on a room.
+    Variable { if_flag: bool, attach_type: u8, context: i64, room: usize, name: String, value: String }
+}
+
+#[derive(Component, Clone, Debug, Default, Serialize, Deserialize)]
+#[track(Insertion, Modification, Removal)]
+pub struct ZoneCommands(pub Vec<ZoneCommand>);
+
 bitflags_with_str! {
-    #[derive(Component)]
+    #[derive(Component, Default, Debug, Serialize, Deserialize)]
+    #[track(Insertion, Modification, Removal)]
     pub struct ZoneFlags: u128 {
         const CLOSED = 1 << 0;
         const NOIMMORT = 1 << 1;
@@ -0,0 +1,49 @@
+use shipyard::{Get, UniqueView, View};
+
+use crate::{
+    structs::common::{Location, ThingType},
+    systems::{
+        commands::{COMMANDS, CommandInput},
+        look::{Sight, Viewer},
+        session::Outbox
+    }
+};
+
+// Lists the commands the actor can use, in columns.
+pub fn commands(input: CommandInput, outbox: UniqueView<Outbox>) {
+    let mut names: Vec<&str> = COMMANDS.entries()
+        .filter(|(_, command)| command.min_level <= input.level)
+        .map(|(name, _)| *name)
+        .collect();
+    names.sort_unstable();
+
+    let mut text = String::from("The following commands are available to you:\n");
+    for row in names.chunks(7) {
+        let row: Vec<String> = row.iter().map(|name| format!("{:<11}", name)).collect();
+        text.push_str(row.concat().trim_end());
+        text.push('\n');
+    }
+    outbox.text(&input.conn, text);
+}
+
+// Looks around the room, or at something in it: "look", "look sword", "look at north".
+pub fn look(input: CommandInput, outbox: UniqueView<Outbox>, locations: View<Location>, sight: Sight) {
+    let room = match locations.get(input.actor) {
+        Ok(Location::Inventory(room)) if sight.types.get(*room) == Ok(&ThingType::Room) => *room,
+        _ => {
+            outbox.text(&input.conn, "You are nowhere at all.\n");
+            return;
+        }
+    };
+    let viewer = Viewer { entity: input.actor, level: input.level };
+    let mut words = input.args.split_whitespace();
+    let target = match words.next() {
+        Some(word) if word.eq_ignore_ascii_case("at") => words.next(),
+        word => word
+    };
+    let text = match target {
+        Some(target) => sight.look_at(viewer, room, target),
+        None => sight.render_room(viewer, room)
+    };
+    outbox.text(&input.conn, text);
+}
@@ -0,0 +1,171 @@
+use phf::phf_map;
+use shipyard::{AllStorages, EntityId, Get, UniqueView, View};
+use surrealdb::RecordId;
+
+use crate::{
+    structs::{
+        character::Position,
+        common::{DbEntityType, DbIndexes, Player, UserAccount}
+    },
+    systems::session::Outbox
+};
+
+pub mod info;
+pub mod movement;
+pub mod other;
+pub mod wizard;
+
+// Admin levels, as stored in UserAccount::admin_level. Immortals see everything, builders
+// also see vnums and flags, and gods can use wizard commands.
+pub const ADMIN_IMMORTAL: u8 = 1;
+pub const ADMIN_BUILDER: u8 = 2;
+pub const ADMIN_GOD: u8 = 3;
+
+// Sent after every command.
+pub const PROMPT: &str = "> ";
+
+// What a handler is told about the command it's running.
+#[derive(Debug, Clone)]
+pub struct CommandInput {
+    pub actor: EntityId,
+    pub conn: RecordId,
+    // The actor's admin level.
+    pub level: u8,
+    // The command's full name, and the word that was typed for it.
+    pub command: &'static str,
+    pub typed: String,
+    // Everything after that word, trimmed.
+    pub args: String
+}
+
+// Runs a handler with whatever views it asks for.
+pub type Handler = fn(&AllStorages, CommandInput);
+
+#[derive(Debug)]
+pub struct Command {
+    pub handler: Handler,
+    pub min_position: Position,
+    pub min_level: u8,
+    // When an abbreviation matches several commands the highest priority wins, so "n" can
+    // be north rather than news. Ties go to the first alphabetically.
+    pub priority: u8
+}
+
+// A table entry's handler, from a plain function over views that takes a CommandInput first.
+// Given a second function, the first returns what it did, if anything, and the second is
+// handed that with the CommandInput, for telling people about it with views the first
+// couldn't share.
+macro_rules! handler {
+    ($f:path) => {
+        |all: &AllStorages, input: CommandInput| all.run_with_data($f, input)
+    };
+    ($f:path, $then:path) => {
+        |all: &AllStorages, input: CommandInput| {
+            if let Some(done) = all.run_with_data($f, input.clone()) {
+                all.run_with_data($then, (input, done));
+            }
+        }
+    };
+}
+
+// Directions come first, so "n" is north; the diagonals just after, so "s" is still south.
+pub static COMMANDS: phf::Map<&'static str, Command> = phf_map! {
+    "north" => Command { handler: handler!(movement::walk, movement::walked), min_position: Position::Standing, min_level: 0, priority: 20 },
+    "east" => Command { handler: handler!(movement::walk, movement::walked), min_position: Position::Standing, min_level: 0, priority: 20 },
+    "south" => Command { handler: handler!(movement::walk, movement::walked), min_position: Position::Standing, min_level: 0, priority: 20 },
+    "west" => Command { handler: handler!(movement::walk, movement::walked), min_position: Position::Standing, min_level: 0, priority: 20 },
+    "up" => Command { handler: handler!(movement::walk, movement::walked), min_position: Position::Standing, min_level: 0, priority: 20 },
+    "down" => Command { handler: handler!(movement::walk, movement::walked), min_position: Position::Standing, min_level: 0, priority: 20 },
+    "northwest" => Command { handler: handler!(movement::walk, movement::walked), min_position: Position::Standing, min_level: 0, priority: 19 },
+    "northeast" => Command { handler: handler!(movement::walk, movement::walked), min_position: Position::Standing, min_level: 0, priority: 19 },
+    "southeast" => Command { handler: handler!(movement::walk, movement::walked), min_position: Position::Standing, min_level: 0, priority: 19 },
+    "southwest" => Command { handler: handler!(movement::walk, movement::walked), min_position: Position::Standing, min_level: 0, priority: 19 },
+    "nw" => Command { handler: handler!(movement::walk, movement::walked), min_position: Position::Standing, min_level: 0, priority: 19 },
+    "ne" => Command { handler: handler!(movement::walk, movement::walked), min_position: Position::Standing, min_level: 0, priority: 19 },
+    "se" => Command { handler: handler!(movement::walk, movement::walked), min_position: Position::Standing, min_level: 0, priority: 19 },
+    "sw" => Command { handler: handler!(movement::walk, movement::walked), min_position: Position::Standing, min_level: 0, priority: 19 },
+    "close" => Command { handler: handler!(movement::door, movement::door_worked), min_position: Position::Sitting, min_level: 0, priority: 0 },
+    "commands" => Command { handler: handler!(info::commands), min_position: Position::Dead, min_level: 0, priority: 0 },
+    "lock" => Command { handler: handler!(movement::door, movement::door_worked), min_position: Position::Sitting, min_level: 0, priority: 0 },
+    "look" => Command { handler: handler!(info::look), min_position: Position::Resting, min_level: 0, priority: 10 },
+    "gecho" => Command { handler: handler!(wizard::gecho), min_position: Position::Dead, min_level: ADMIN_GOD, priority: 0 },
+    "open" => Command { handler: handler!(movement::door, movement::door_worked), min_position: Position::Sitting, min_level: 0, priority: 0 },
+    "pick" => Command { handler: handler!(movement::door, movement::door_worked), min_position: Position::Standing, min_level: 0, priority: 0 },
+    "quit" => Command { handler: handler!(other::quit), min_position: Position::Dead, min_level: 0, priority: 0 },
+    "save" => Command { handler: handler!(other::save), min_position: Position::Sleeping, min_level: 0, priority: 0 },
+    "unlock" => Command { handler: handler!(movement::door, movement::door_worked), min_position: Position::Sitting, min_level: 0, priority: 0 }
+};
+
+// The command a word stands for, for someone of the given admin level. A whole name always
+// means that command; otherwise the word is an abbreviation. Commands above the level are
+// treated as if they didn't exist.
+pub fn find_command(word: &str, level: u8) -> Option<(&'static str, &'static Command)> {
+    let word = word.to_lowercase();
+    if let Some((name, command)) = COMMANDS.get_entry(word.as_str()) {
+        if command.min_level <= level {
+            return Some((*name, command));
+        }
+    }
+    COMMANDS.entries()
+        .filter(|(name, command)| command.min_level <= level && name.starts_with(word.as_str()))
+        .max_by(|(a_name, a), (b_name, b)| a.priority.cmp(&b.priority).then_with(|| b_name.cmp(a_name)))
+        .map(|(name, command)| (*name, command))
+}
+
+// Why someone in this position can't do something that needs a better one.
+pub fn position_refusal(position: Position) -> &'static str {
+    match position {
+        Position::Dead => "Lie still; you are DEAD!!! :-(\n",
+        Position::MortallyWounded | Position::Incapacitated => "You are in a pretty bad shape, unable to do anything!\n",
+        Position::Stunned => "All you can do right now is think about the stars!\n",
+        Position::Sleeping => "In your dreams, or what?\n",
+        Position::Resting => "Nah... You feel too relaxed to do that..\n",
+        Position::Sitting => "Maybe you should get on your feet first?\n",
+        Position::Fighting | Position::Standing => "No way!  You're fighting for your life!\n"
+    }
+}
+
+// The admin level of the account a character belongs to. NPCs, and characters whose
+// account isn't loaded, have none.
+pub fn admin_level(all: &AllStorages, actor: EntityId) -> u8 {
+    all.run(|players: View<Player>, accounts: View<UserAccount>, indexes: UniqueView<DbIndexes>| {
+        level_of(&players, &accounts, &indexes, actor)
+    })
+}
+
+// admin_level, for a handler that already has the views.
+pub fn level_of(players: &View<Player>, accounts: &View<UserAccount>, indexes: &DbIndexes, actor: EntityId) -> u8 {
+    players.get(actor).ok()
+        .and_then(|player| indexes.get(&DbEntityType::UserAccount, player.account))
+        .and_then(|account| accounts.get(account).ok().map(|account| account.admin_level))
+        .unwrap_or(0)
+}
+
+// Runs one line of input from actor's connection, then prompts for the next.
+pub fn interpret(all: &AllStorages, actor: EntityId, conn: &RecordId, line: &str) {
+    let outbox = all.borrow::<UniqueView<Outbox>>().unwrap().clone();
+    let line = line.trim();
+    let (word, args) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    if !word.is_empty() {
+        let level = admin_level(all, actor);
+        match find_command(word, level) {
+            None => outbox.text(conn, "Huh?!?\n"),
+            Some((name, command)) => {
+                let position = all.borrow::<View<Position>>().unwrap().get(actor).copied().unwrap_or_default();
+                if position < command.min_position {
+                    outbox.text(conn, position_refusal(position));
+                } else {
+                    (command.handler)(all, CommandInput {
+                        actor,
+                        conn: conn.clone(),
+                        level,
+                        command: name,
+                        typed: word.to_string(),
+                        args: args.trim().to_string()
+                    });
+                }
+            }
+        }
+    }
+    outbox.prompt(conn, PROMPT);
+}
@@ -0,0 +1,100 @@
+use shipyard::{Borrow, BorrowInfo, EntityId, Get, UniqueView, View};
+
+use crate::{
+    structs::{
+        common::{AffectFlags, Player, UserAccount},
+        room::Direction
+    },
+    systems::{
+        commands::{self, CommandInput},
+        doors::{DoorAction, Doors, Worked},
+        look::{Sight, Viewer, capitalize, direction_name, parse_direction},
+        movement::{Paths, Route},
+        session::{Outbox, Session}
+    }
+};
+
+// Everything needed to tell the people in a room what happened there.
+#[derive(Borrow, BorrowInfo)]
+pub struct Onlookers<'v> {
+    pub outbox: UniqueView<'v, Outbox>,
+    pub sight: Sight<'v>,
+    pub sessions: View<'v, Session>,
+    pub players: View<'v, Player>,
+    pub accounts: View<'v, UserAccount>
+}
+
+impl Onlookers<'_> {
+    // Tells everyone playing in room, other than actor, what actor did, if they can see it.
+    fn announce(&self, room: EntityId, actor: EntityId, text: impl Fn(&str) -> String) {
+        let people = self.sight.people.get(room).map(|people| people.0.clone()).unwrap_or_default();
+        for person in people.into_iter().filter(|person| *person != actor) {
+            let Ok(session) = self.sessions.get(person) else {
+                continue;
+            };
+            let level = commands::level_of(&self.players, &self.accounts, &self.sight.indexes, person);
+            let viewer = Viewer { entity: person, level };
+            if self.sight.notices(viewer, room, actor) {
+                self.outbox.text(&session.conn, text(&capitalize(&self.sight.title(actor))));
+            }
+        }
+    }
+
+    // Tells everyone playing in room something nobody in particular did.
+    fn tell_room(&self, room: EntityId, text: &str) {
+        let people = self.sight.people.get(room).map(|people| people.0.clone()).unwrap_or_default();
+        for session in people.into_iter().filter_map(|person| self.sessions.get(person).ok()) {
+            self.outbox.text(&session.conn, text);
+        }
+    }
+}
+
+// Every direction command: the command's name is the way to go.
+pub fn walk(input: CommandInput, outbox: UniqueView<Outbox>, mut paths: Paths) -> Option<(Direction, Route)> {
+    let direction = parse_direction(input.command)?;
+    match paths.walk(input.actor, input.level, direction) {
+        Ok(route) => Some((direction, route)),
+        Err(blocked) => {
+            outbox.text(&input.conn, format!("{}\n", blocked));
+            None
+        }
+    }
+}
+
+// Shows the walker where they've arrived, and tells both rooms, unless they're sneaking.
+pub fn walked((input, (direction, route)): (CommandInput, (Direction, Route)), onlookers: Onlookers) {
+    if !onlookers.sight.affects.get(input.actor).is_ok_and(|affects| affects.contains(AffectFlags::SNEAK)) {
+        onlookers.announce(route.from, input.actor, |name| format!("{} leaves {}.\n", name, direction_name(direction)));
+        onlookers.announce(route.to, input.actor, |name| format!("{} has arrived.\n", name));
+    }
+    let viewer = Viewer { entity: input.actor, level: input.level };
+    onlookers.outbox.text(&input.conn, onlookers.sight.render_room(viewer, route.to));
+}
+
+// open, close, lock, unlock and pick, on doors and containers alike.
+pub fn door(input: CommandInput, outbox: UniqueView<Outbox>, mut doors: Doors) -> Option<(DoorAction, Worked)> {
+    let action = DoorAction::from_command(input.command)?;
+    match doors.work(input.actor, action, &input.args) {
+        Ok(worked) => Some((action, worked)),
+        Err(e) => {
+            outbox.text(&input.conn, format!("{}\n", e));
+            None
+        }
+    }
+}
+
+// Tells whoever worked a door or container that it worked, and everyone who saw it, on
+// both sides of a door.
+pub fn door_worked((input, (action, worked)): (CommandInput, (DoorAction, Worked)), onlookers: Onlookers) {
+    if action == DoorAction::Pick {
+        onlookers.outbox.text(&input.conn, "The lock quickly yields to your skills.\n");
+        onlookers.announce(worked.room, input.actor, |name| format!("{} skillfully picks the lock on {}.\n", name, worked.name));
+    } else {
+        onlookers.outbox.text(&input.conn, "Okay.\n");
+        onlookers.announce(worked.room, input.actor, |name| format!("{} {}s {}.\n", name, action.verb(), worked.name));
+    }
+    if let (Some(other_side), DoorAction::Open | DoorAction::Close) = (worked.other_side, action) {
+        let state = if action == DoorAction::Open { "opened" } else { "closed" };
+        onlookers.tell_room(other_side, &capitalize(&format!("{} is {} from the other side.\n", worked.name, state)));
+    }
+}
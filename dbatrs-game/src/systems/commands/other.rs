@@ -0,0 +1,29 @@
+use shipyard::{Get, UniqueView, View};
+
+use dbatrs_shared::protocol::ToPortal;
+
+use crate::{
+    structs::{character::Position, common::Title},
+    systems::{
+        commands::CommandInput,
+        session::Outbox
+    }
+};
+
+// Only the whole word quits, so a stray abbreviation can't.
+pub fn quit(input: CommandInput, outbox: UniqueView<Outbox>, positions: View<Position>) {
+    if input.typed.to_lowercase() != input.command {
+        outbox.text(&input.conn, "You have to type quit--no less, to quit!\n");
+    } else if positions.get(input.actor) == Ok(&Position::Fighting) {
+        outbox.text(&input.conn, "No way!  You're fighting for your life!\n");
+    } else {
+        outbox.text(&input.conn, "Goodbye, friend.. Come back soon!\n");
+        outbox.send(ToPortal::Disconnect { conn: input.conn, reason: None });
+    }
+}
+
+// Whatever changed is saved with the next autosave anyway, so there's nothing to do here.
+pub fn save(input: CommandInput, outbox: UniqueView<Outbox>, titles: View<Title>) {
+    let name = titles.get(input.actor).map(|title| title.0.as_str()).unwrap_or("yourself");
+    outbox.text(&input.conn, format!("Saving {}.\n", name));
+}
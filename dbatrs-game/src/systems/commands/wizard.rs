@@ -0,0 +1,17 @@
+use shipyard::UniqueView;
+
+use crate::systems::{
+    commands::CommandInput,
+    session::{Outbox, Sessions}
+};
+
+// Sends a message to everyone playing.
+pub fn gecho(input: CommandInput, outbox: UniqueView<Outbox>, sessions: UniqueView<Sessions>) {
+    if input.args.is_empty() {
+        outbox.text(&input.conn, "That must be a mistake...\n");
+        return;
+    }
+    for conn in sessions.0.keys() {
+        outbox.text(conn, format!("{}\n", input.args));
+    }
+}
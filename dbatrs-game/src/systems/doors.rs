@@ -0,0 +1,304 @@
+use std::fmt;
+
+use shipyard::{Borrow, BorrowInfo, EntityId, Get, UniqueView, View, ViewMut};
+
+use crate::{
+    structs::{
+        common::{DbEntityType, DbIdentifier, DbIndexes, Keywords, Location, ThingContents, ThingType, Title},
+        item::{ContainerFlags, ItemType, ItemValues, Prototype},
+        room::{Direction, Exit, ExitFlags, Exits}
+    },
+    systems::look::{capitalize, keyword_matches, parse_direction}
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DoorAction {
+    Open,
+    Close,
+    Lock,
+    Unlock,
+    Pick
+}
+
+impl DoorAction {
+    pub fn from_command(command: &str) -> Option<DoorAction> {
+        match command {
+            "open" => Some(DoorAction::Open),
+            "close" => Some(DoorAction::Close),
+            "lock" => Some(DoorAction::Lock),
+            "unlock" => Some(DoorAction::Unlock),
+            "pick" => Some(DoorAction::Pick),
+            _ => None
+        }
+    }
+
+    pub fn verb(&self) -> &'static str {
+        match self {
+            DoorAction::Open => "open",
+            DoorAction::Close => "close",
+            DoorAction::Lock => "lock",
+            DoorAction::Unlock => "unlock",
+            DoorAction::Pick => "pick"
+        }
+    }
+}
+
+// Why a door or container can't be worked. Displays as what the actor is told.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DoorError {
+    NoArgument(DoorAction),
+    Nowhere,
+    // Nothing by that keyword, or that way.
+    NotHere(String),
+    NoExitThere,
+    NotThere(String),
+    // Not a door, or not a container that closes.
+    Cannot(DoorAction),
+    AlreadyOpen,
+    AlreadyClosed,
+    StillOpen,
+    Locked,
+    NotLocked,
+    NoKeyhole,
+    NoKey,
+    Pickproof
+}
+
+impl fmt::Display for DoorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DoorError::NoArgument(action) => write!(f, "{} what?", capitalize(action.verb())),
+            DoorError::Nowhere => write!(f, "You are nowhere at all."),
+            DoorError::NotHere(keyword) => write!(f, "There doesn't seem to be a {} here.", keyword),
+            DoorError::NoExitThere => write!(f, "I really don't see how you can do that there."),
+            DoorError::NotThere(keyword) => write!(f, "I see no {} there.", keyword),
+            DoorError::Cannot(action) => write!(f, "You can't {} that!", action.verb()),
+            DoorError::AlreadyOpen => write!(f, "It's already open!"),
+            DoorError::AlreadyClosed => write!(f, "It's already closed!"),
+            DoorError::StillOpen => write!(f, "But it's currently open!"),
+            DoorError::Locked => write!(f, "It seems to be locked."),
+            DoorError::NotLocked => write!(f, "Oh.. it wasn't locked, after all.."),
+            DoorError::NoKeyhole => write!(f, "Odd - you can't seem to find a keyhole."),
+            DoorError::NoKey => write!(f, "You don't seem to have the proper key."),
+            DoorError::Pickproof => write!(f, "It resists your attempts to pick it.")
+        }
+    }
+}
+
+impl std::error::Error for DoorError {}
+
+// What can be opened: an exit of a room, or a container.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DoorTarget {
+    Exit(EntityId, Direction),
+    Item(EntityId)
+}
+
+// What got worked, for telling people about it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Worked {
+    pub room: EntityId,
+    pub target: DoorTarget,
+    // "the gate", or a container's title.
+    pub name: String,
+    // The room on the far side of a two-way exit, which saw it change too.
+    pub other_side: Option<EntityId>
+}
+
+// The state of a door or container's lock, whichever it is.
+#[derive(Debug, Clone, Copy, Default)]
+struct Lock {
+    closeable: bool,
+    closed: bool,
+    locked: bool,
+    pickproof: bool,
+    key: Option<usize>
+}
+
+// A door's name in messages: the first word of its keywords.
+pub fn door_name(exit: &Exit) -> &str {
+    exit.keyword.split_whitespace().next().unwrap_or("door")
+}
+
+// Everything needed to open, close, lock, unlock and pick doors and containers.
+#[derive(Borrow, BorrowInfo)]
+pub struct Doors<'v> {
+    pub types: View<'v, ThingType>,
+    pub locations: View<'v, Location>,
+    pub contents: View<'v, ThingContents>,
+    pub keywords: View<'v, Keywords>,
+    pub titles: View<'v, Title>,
+    pub item_types: View<'v, ItemType>,
+    pub ids: View<'v, DbIdentifier>,
+    pub prototypes: View<'v, Prototype>,
+    pub exits: ViewMut<'v, Exits>,
+    pub values: ViewMut<'v, ItemValues>,
+    pub indexes: UniqueView<'v, DbIndexes>
+}
+
+impl Doors<'_> {
+    // The vnum of the prototype an item was made from, or its own if it's a prototype.
+    fn vnum(&self, item: EntityId) -> Option<usize> {
+        if let Ok(prototype) = self.prototypes.get(item) {
+            return Some(prototype.0);
+        }
+        self.ids.get(item).ok().filter(|id| id.entity_type == DbEntityType::ItemProto).map(|id| id.id)
+    }
+
+    pub fn has_key(&self, actor: EntityId, key: usize) -> bool {
+        self.contents.get(actor).is_ok_and(|contents| {
+            contents.items.iter().chain(contents.equipment.values())
+                .any(|item| self.item_types.get(*item) == Ok(&ItemType::KEY) && self.vnum(*item) == Some(key))
+        })
+    }
+
+    // Carried items come before those on the floor.
+    fn find_item(&self, actor: EntityId, room: EntityId, keyword: &str) -> Option<EntityId> {
+        let carried = self.contents.get(actor).map(|contents| contents.items.clone()).unwrap_or_default();
+        let floor = self.contents.get(room).map(|contents| contents.items.clone()).unwrap_or_default();
+        carried.into_iter().chain(floor)
+            .find(|item| self.keywords.get(*item).is_ok_and(|keywords| keyword_matches(&keywords.0.join(" "), keyword)))
+    }
+
+    // An exit by its keyword, optionally only the one that way. A lone direction means
+    // that way's exit, whatever it's called, even if another's keyword starts the same.
+    fn find_exit(&self, room: EntityId, keyword: &str, direction: Option<&str>) -> Result<Direction, DoorError> {
+        let exits = self.exits.get(room).ok();
+        let exit = |direction: Direction| exits.and_then(|exits| exits.exits.get(&direction));
+        match direction {
+            Some(direction) => {
+                let direction = parse_direction(direction).ok_or(DoorError::NoExitThere)?;
+                let exit = exit(direction).ok_or(DoorError::NoExitThere)?;
+                if exit.keyword.is_empty() || keyword_matches(&exit.keyword, keyword) {
+                    Ok(direction)
+                } else {
+                    Err(DoorError::NotThere(keyword.to_string()))
+                }
+            },
+            None => {
+                let by_keyword = || exits.and_then(|exits| {
+                    exits.exits.iter()
+                        .filter(|(_, exit)| keyword_matches(&exit.keyword, keyword))
+                        .map(|(direction, _)| *direction)
+                        .min_by_key(|direction| direction.value())
+                });
+                parse_direction(keyword).filter(|direction| exit(*direction).is_some())
+                    .or_else(by_keyword)
+                    .ok_or_else(|| DoorError::NotHere(keyword.to_string()))
+            }
+        }
+    }
+
+    fn lock(&self, target: DoorTarget) -> Lock {
+        match target {
+            DoorTarget::Exit(room, direction) => {
+                let Some(exit) = self.exits.get(room).ok().and_then(|exits| exits.exits.get(&direction)) else {
+                    return Lock::default();
+                };
+                Lock {
+                    closeable: exit.flags.contains(ExitFlags::ISDOOR),
+                    closed: exit.flags.contains(ExitFlags::CLOSED),
+                    locked: exit.flags.contains(ExitFlags::LOCKED),
+                    pickproof: exit.flags.contains(ExitFlags::PICKPROOF),
+                    key: exit.key
+                }
+            },
+            DoorTarget::Item(item) => {
+                let is_container = self.item_types.get(item) == Ok(&ItemType::CONTAINER);
+                let values = self.values.get(item).ok();
+                let flags = values.map(|values| values.container_flags()).unwrap_or_default();
+                Lock {
+                    closeable: is_container && flags.contains(ContainerFlags::CLOSEABLE),
+                    closed: flags.contains(ContainerFlags::CLOSED),
+                    locked: flags.contains(ContainerFlags::LOCKED),
+                    pickproof: flags.contains(ContainerFlags::PICKPROOF),
+                    key: values.and_then(|values| values.container_key())
+                }
+            }
+        }
+    }
+
+    // Sets a door's CLOSED and LOCKED flags, and the far side's too if it leads back here.
+    fn set_exit(&mut self, room: EntityId, direction: Direction, closed: bool, locked: bool) -> Option<EntityId> {
+        let set = |flags: &mut ExitFlags| {
+            flags.set(ExitFlags::CLOSED, closed);
+            flags.set(ExitFlags::LOCKED, locked);
+        };
+        let destination = {
+            let mut exits = (&mut self.exits).get(room).ok()?;
+            let exit = exits.exits.get_mut(&direction)?;
+            set(&mut exit.flags);
+            exit.destination
+        };
+
+        let here = self.ids.get(room).ok()?.id;
+        let other = self.indexes.room(destination).filter(|other| *other != room)?;
+        let mut exits = (&mut self.exits).get(other).ok()?;
+        let back = exits.exits.get_mut(&direction.opposite()).filter(|back| back.destination == here)?;
+        set(&mut back.flags);
+        Some(other)
+    }
+
+    // actor works the door or container args names: "door", "door east", "east" or "chest".
+    pub fn work(&mut self, actor: EntityId, action: DoorAction, args: &str) -> Result<Worked, DoorError> {
+        let mut words = args.split_whitespace();
+        let keyword = words.next().ok_or(DoorError::NoArgument(action))?;
+        let direction = words.next();
+        let room = match self.locations.get(actor) {
+            Ok(Location::Inventory(room)) if self.types.get(*room) == Ok(&ThingType::Room) => *room,
+            _ => return Err(DoorError::Nowhere)
+        };
+        let item = direction.is_none().then(|| self.find_item(actor, room, keyword)).flatten();
+        let target = match item {
+            Some(item) => DoorTarget::Item(item),
+            None => DoorTarget::Exit(room, self.find_exit(room, keyword, direction)?)
+        };
+
+        let lock = self.lock(target);
+        if !lock.closeable {
+            return Err(DoorError::Cannot(action));
+        }
+        match action {
+            DoorAction::Open if !lock.closed => return Err(DoorError::AlreadyOpen),
+            DoorAction::Close if lock.closed => return Err(DoorError::AlreadyClosed),
+            DoorAction::Lock | DoorAction::Unlock | DoorAction::Pick if !lock.closed => return Err(DoorError::StillOpen),
+            DoorAction::Open | DoorAction::Lock if lock.locked => return Err(DoorError::Locked),
+            DoorAction::Unlock | DoorAction::Pick if !lock.locked => return Err(DoorError::NotLocked),
+            _ => {}
+        }
+        if matches!(action, DoorAction::Lock | DoorAction::Unlock | DoorAction::Pick) {
+            let key = lock.key.ok_or(DoorError::NoKeyhole)?;
+            if action == DoorAction::Pick && lock.pickproof {
+                return Err(DoorError::Pickproof);
+            }
+            if action != DoorAction::Pick && !self.has_key(actor, key) {
+                return Err(DoorError::NoKey);
+            }
+        }
+
+        let (closed, locked) = match action {
+            DoorAction::Open => (false, false),
+            DoorAction::Close => (true, false),
+            DoorAction::Lock => (true, true),
+            DoorAction::Unlock | DoorAction::Pick => (true, false)
+        };
+        let (name, other_side) = match target {
+            DoorTarget::Exit(room, direction) => {
+                let other_side = self.set_exit(room, direction, closed, locked);
+                let exits = self.exits.get(room).unwrap();
+                (format!("the {}", door_name(&exits.exits[&direction])), other_side)
+            },
+            DoorTarget::Item(item) => {
+                if let Ok(mut values) = (&mut self.values).get(item) {
+                    let mut flags = values.container_flags();
+                    flags.set(ContainerFlags::CLOSED, closed);
+                    flags.set(ContainerFlags::LOCKED, locked);
+                    values.set_container_flags(flags);
+                }
+                let name = self.titles.get(item).map(|title| title.0.clone()).unwrap_or_else(|_| "something".to_string());
+                (name, None)
+            }
+        };
+        Ok(Worked { room, target, name, other_side })
+    }
+}
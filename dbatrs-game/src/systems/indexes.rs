@@ -0,0 +1,50 @@
+use shipyard::{IntoIter, IntoWithId, UniqueViewMut, ViewMut, World};
+use tracing::error;
+
+use crate::structs::common::{DbIdentifier, DbIndexes, DuplicateVnum};
+
+// Brings DbIndexes up to date with every DbIdentifier added, changed, removed or deleted
+// since it last ran. A duplicate is left out of the index and reported once all other changes have
+// been applied, so one bad spawn doesn't stop the rest being indexed.
+pub fn update_indexes(mut ids: ViewMut<DbIdentifier>, mut indexes: UniqueViewMut<DbIndexes>) -> Result<(), DuplicateVnum> {
+    for (entity, id) in ids.deleted() {
+        indexes.remove(&id.entity_type, id.id, entity);
+    }
+    for entity in ids.removed() {
+        indexes.forget(entity);
+    }
+
+    // Adding a DbIdentifier over an existing one only counts as a modification, and the old
+    // id is gone by now, so a changed entity is unindexed wherever it was and indexed again.
+    for (entity, _) in ids.modified().iter().with_id() {
+        indexes.forget(entity);
+    }
+
+    let mut duplicate = None;
+    for (entity, id) in ids.inserted_or_modified().iter().with_id() {
+        if let Err(e) = indexes.insert(&id.entity_type, id.id, entity) {
+            duplicate.get_or_insert(e);
+        }
+    }
+
+    ids.clear_all_removed_and_deleted();
+    ids.clear_all_inserted_and_modified();
+    duplicate.map_or(Ok(()), Err)
+}
+
+// update_indexes as the game loop runs it. A duplicate is a bug in whatever spawned it, but
+// it's already been left out of the index, so it's logged rather than cutting the pulse
+// short before input is handled.
+pub fn index_pulse(ids: ViewMut<DbIdentifier>, indexes: UniqueViewMut<DbIndexes>) {
+    if let Err(e) = update_indexes(ids, indexes) {
+        error!("Left out of DbIndexes: {}", e);
+    }
+}
+
+// Runs update_indexes outside the game loop, such as right after spawning.
+pub fn sync_indexes(world: &World) -> Result<(), DuplicateVnum> {
+    if world.borrow::<UniqueViewMut<DbIndexes>>().is_err() {
+        world.add_unique(DbIndexes::default());
+    }
+    world.run(update_indexes)
+}
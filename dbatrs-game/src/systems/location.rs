@@ -0,0 +1,289 @@
+use std::{collections::HashSet, fmt};
+
+use shipyard::{
+    AddComponent, Borrow, BorrowInfo, EntitiesView, EntityId, Get, IntoIter, IntoWithId, Remove, Unique, UniqueViewMut,
+    View, ViewMut
+};
+use tracing::warn;
+
+use crate::structs::{
+    common::{Location, ThingContents, ThingType},
+    room::People
+};
+
+// Where move_to puts something.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Destination {
+    // In a room: among its People if it's a character, otherwise on the floor.
+    Room(EntityId),
+    // Carried by a character.
+    Inventory(EntityId),
+    // Inside an item.
+    Container(EntityId),
+    // Worn or wielded by a character, in the given equipment slot.
+    Equipment(EntityId, usize)
+}
+
+impl Destination {
+    pub fn holder(&self) -> EntityId {
+        match self {
+            Destination::Room(holder) | Destination::Inventory(holder) | Destination::Container(holder) => *holder,
+            Destination::Equipment(holder, _) => *holder
+        }
+    }
+
+    fn holder_type(&self) -> ThingType {
+        match self {
+            Destination::Room(_) => ThingType::Room,
+            Destination::Inventory(_) | Destination::Equipment(..) => ThingType::Character,
+            Destination::Container(_) => ThingType::Item
+        }
+    }
+
+    fn location(&self) -> Location {
+        match self {
+            Destination::Equipment(holder, slot) => Location::Equipped(*holder, *slot),
+            _ => Location::Inventory(self.holder())
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MoveError {
+    // Rooms, and entities that aren't things at all, stay where they are.
+    NotMovable(EntityId),
+    // The destination isn't the kind of thing that can hold it that way.
+    WrongHolder { holder: EntityId, expected: ThingType },
+    // The move would put something inside itself.
+    Cycle,
+    // Something else is already in that equipment slot.
+    SlotTaken(EntityId)
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MoveError::NotMovable(entity) => write!(f, "{:?} can't be moved", entity),
+            MoveError::WrongHolder { holder, expected } => write!(f, "{:?} isn't a {:?}", holder, expected),
+            MoveError::Cycle => write!(f, "that would put something inside itself"),
+            MoveError::SlotTaken(current) => write!(f, "that slot is taken by {:?}", current)
+        }
+    }
+}
+
+impl std::error::Error for MoveError {}
+
+// One change of location. from is None if the entity was nowhere, and to is None if it
+// was detached.
+#[derive(Debug, Clone)]
+pub struct MoveEvent {
+    pub entity: EntityId,
+    pub from: Option<Location>,
+    pub to: Option<Location>
+}
+
+// Every move since this pulse started. Whatever reacts to moves has to run later in the
+// same pulse, as clear_move_events drops them when the next one starts.
+#[derive(Unique, Debug, Default)]
+pub struct MoveEvents(pub Vec<MoveEvent>);
+
+// Runs first each PULSE, so moves never carry over into the next pulse.
+pub fn clear_move_events(mut events: UniqueViewMut<MoveEvents>) {
+    events.0.clear();
+}
+
+// Characters in a room are its People; everything else a thing holds is in its
+// ThingContents.
+pub fn is_person_in_room(types: &View<ThingType>, entity: EntityId, holder: EntityId) -> bool {
+    types.get(holder) == Ok(&ThingType::Room) && types.get(entity) == Ok(&ThingType::Character)
+}
+
+// Everything needed to move things around. Location, ThingContents and People must only
+// be changed through this, so they never disagree. Needs the MoveEvents unique,
+// which GameLoop::new adds.
+#[derive(Borrow, BorrowInfo)]
+pub struct Mover<'v> {
+    pub types: View<'v, ThingType>,
+    pub locations: ViewMut<'v, Location>,
+    pub contents: ViewMut<'v, ThingContents>,
+    pub people: ViewMut<'v, People>,
+    pub events: UniqueViewMut<'v, MoveEvents>
+}
+
+impl Mover<'_> {
+    pub fn move_to(&mut self, entity: EntityId, destination: Destination) -> Result<(), MoveError> {
+        if !matches!(self.types.get(entity), Ok(ThingType::Character) | Ok(ThingType::Item)) {
+            return Err(MoveError::NotMovable(entity));
+        }
+        let holder = destination.holder();
+        let expected = destination.holder_type();
+        if self.types.get(holder) != Ok(&expected) {
+            return Err(MoveError::WrongHolder { holder, expected });
+        }
+        if self.holds(entity, holder) {
+            return Err(MoveError::Cycle);
+        }
+        if let Destination::Equipment(_, slot) = destination {
+            let current = self.contents.get(holder).ok().and_then(|contents| contents.equipment.get(&slot).copied());
+            if let Some(current) = current.filter(|current| *current != entity) {
+                return Err(MoveError::SlotTaken(current));
+            }
+        }
+
+        let from = self.take(entity);
+        let to = destination.location();
+        if is_person_in_room(&self.types, entity, holder) {
+            match (&mut self.people).get(holder) {
+                Ok(mut people) => people.0.push(entity),
+                Err(_) => self.people.add_component_unchecked(holder, People(vec![entity]))
+            }
+        } else {
+            if (&mut self.contents).get(holder).is_err() {
+                self.contents.add_component_unchecked(holder, ThingContents::default());
+            }
+            let mut contents = (&mut self.contents).get(holder).unwrap();
+            match destination {
+                Destination::Equipment(_, slot) => {
+                    contents.equipment.insert(slot, entity);
+                },
+                _ => contents.items.push(entity)
+            }
+        }
+        self.locations.add_component_unchecked(entity, to.clone());
+
+        self.events.0.push(MoveEvent { entity, from, to: Some(to) });
+        Ok(())
+    }
+
+    // Takes entity out of wherever it is and leaves it with no Location, such as before
+    // it's deleted.
+    pub fn detach(&mut self, entity: EntityId) -> Option<Location> {
+        let from = self.take(entity)?;
+        self.events.0.push(MoveEvent { entity, from: Some(from.clone()), to: None });
+        Some(from)
+    }
+
+    // Whether container is thing, or holds it at any depth.
+    pub fn holds(&self, container: EntityId, thing: EntityId) -> bool {
+        let mut current = thing;
+        // Bounded, in case the invariants are already broken.
+        for _ in 0..=self.locations.len() {
+            if current == container {
+                return true;
+            }
+            match self.locations.get(current) {
+                Ok(Location::Inventory(holder)) | Ok(Location::Equipped(holder, _)) => current = *holder,
+                Err(_) => return false
+            }
+        }
+        false
+    }
+
+    fn take(&mut self, entity: EntityId) -> Option<Location> {
+        let from = self.locations.remove(entity)?;
+        match &from {
+            Location::Inventory(holder) if is_person_in_room(&self.types, entity, *holder) => {
+                if let Ok(mut people) = (&mut self.people).get(*holder) {
+                    people.0.retain(|e| *e != entity);
+                }
+            },
+            Location::Inventory(holder) => {
+                if let Ok(mut contents) = (&mut self.contents).get(*holder) {
+                    contents.items.retain(|e| *e != entity);
+                }
+            },
+            Location::Equipped(holder, slot) => {
+                if let Ok(mut contents) = (&mut self.contents).get(*holder) {
+                    if contents.equipment.get(slot) == Some(&entity) {
+                        contents.equipment.remove(slot);
+                    }
+                }
+            }
+        }
+        Some(from)
+    }
+}
+
+// Everything wrong with Location, ThingContents and People across the world; empty when
+// they all agree.
+pub fn check_locations(entities: &EntitiesView, types: &View<ThingType>, locations: &View<Location>,
+                       contents: &View<ThingContents>, people: &View<People>) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    for (entity, location) in locations.iter().with_id() {
+        if types.get(entity) == Ok(&ThingType::Room) {
+            problems.push(format!("room {:?} has a Location", entity));
+        }
+        let (holder, slot) = match location {
+            Location::Inventory(holder) => (*holder, None),
+            Location::Equipped(holder, slot) => (*holder, Some(*slot))
+        };
+        if !entities.is_alive(holder) {
+            problems.push(format!("{:?} is held by {:?}, which has been deleted", entity, holder));
+            continue;
+        }
+        let listed = match slot {
+            Some(slot) => contents.get(holder).is_ok_and(|c| c.equipment.get(&slot) == Some(&entity)),
+            None if is_person_in_room(types, entity, holder) => people.get(holder).is_ok_and(|p| p.0.contains(&entity)),
+            None => contents.get(holder).is_ok_and(|c| c.items.contains(&entity))
+        };
+        if !listed {
+            problems.push(format!("{:?} is in {:?}, which doesn't list it", entity, holder));
+        }
+
+        let mut current = holder;
+        for _ in 0..=locations.len() {
+            if current == entity {
+                problems.push(format!("{:?} is inside itself", entity));
+                break;
+            }
+            match locations.get(current) {
+                Ok(Location::Inventory(next)) | Ok(Location::Equipped(next, _)) => current = *next,
+                Err(_) => break
+            }
+        }
+    }
+
+    let mut seen = HashSet::new();
+    let mut check_listed = |entity: EntityId, holder: EntityId, expected: Location, problems: &mut Vec<String>| {
+        if !seen.insert(entity) {
+            problems.push(format!("{:?} is listed in more than one place", entity));
+        }
+        let matches = match (locations.get(entity), &expected) {
+            (Ok(Location::Inventory(a)), Location::Inventory(b)) => a == b,
+            (Ok(Location::Equipped(a, x)), Location::Equipped(b, y)) => a == b && x == y,
+            _ => false
+        };
+        if !matches {
+            problems.push(format!("{:?} lists {:?}, whose Location is {:?}", holder, entity, locations.get(entity).ok()));
+        }
+    };
+    for (holder, things) in contents.iter().with_id() {
+        for entity in things.items.iter() {
+            if is_person_in_room(types, *entity, holder) {
+                problems.push(format!("{:?} is in the contents of room {:?} instead of its People", entity, holder));
+            }
+            check_listed(*entity, holder, Location::Inventory(holder), &mut problems);
+        }
+        for (slot, entity) in things.equipment.iter() {
+            check_listed(*entity, holder, Location::Equipped(holder, *slot), &mut problems);
+        }
+    }
+    for (room, present) in people.iter().with_id() {
+        for entity in present.0.iter() {
+            if !is_person_in_room(types, *entity, room) {
+                problems.push(format!("{:?} is in the People of {:?}, which only rooms have for characters", entity, room));
+            }
+            check_listed(*entity, room, Location::Inventory(room), &mut problems);
+        }
+    }
+    problems
+}
+
+// A debug system that warns about every broken location invariant.
+pub fn check_invariants(entities: EntitiesView, types: View<ThingType>, locations: View<Location>,
+                        contents: View<ThingContents>, people: View<People>) {
+    for problem in check_locations(&entities, &types, &locations, &contents, &people) {
+        warn!("Location invariant broken: {}", problem);
+    }
+}
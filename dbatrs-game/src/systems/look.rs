@@ -0,0 +1,341 @@
+use shipyard::{Borrow, BorrowInfo, EntityId, Get, UniqueView, View};
+
+use crate::{
+    structs::{
+        character::Position,
+        common::{
+            AffectFlags, DbEntityType, DbIdentifier, DbIndexes, Description, ExtraDescriptions, Keywords, ShortDesc,
+            ThingContents, ThingType, Title
+        },
+        item::{ItemFlags, Prototype},
+        room::{Direction, Exit, ExitFlags, Exits, People, RoomFlags, SectorType}
+    },
+    systems::{
+        commands::{ADMIN_BUILDER, ADMIN_IMMORTAL},
+        doors::door_name
+    }
+};
+
+// Everything needed to describe what someone can see.
+#[derive(Borrow, BorrowInfo)]
+pub struct Sight<'v> {
+    pub types: View<'v, ThingType>,
+    pub ids: View<'v, DbIdentifier>,
+    pub titles: View<'v, Title>,
+    pub descriptions: View<'v, Description>,
+    pub short_descs: View<'v, ShortDesc>,
+    pub keywords: View<'v, Keywords>,
+    pub extras: View<'v, ExtraDescriptions>,
+    pub exits: View<'v, Exits>,
+    pub room_flags: View<'v, RoomFlags>,
+    pub sectors: View<'v, SectorType>,
+    pub people: View<'v, People>,
+    pub contents: View<'v, ThingContents>,
+    pub positions: View<'v, Position>,
+    pub affects: View<'v, AffectFlags>,
+    pub item_flags: View<'v, ItemFlags>,
+    pub prototypes: View<'v, Prototype>,
+    pub indexes: UniqueView<'v, DbIndexes>
+}
+
+// Someone looking, and the admin level they look with.
+#[derive(Debug, Clone, Copy)]
+pub struct Viewer {
+    pub entity: EntityId,
+    pub level: u8
+}
+
+// Exits in the order they're listed.
+const DIRECTIONS: [Direction; 12] = [
+    Direction::NORTH, Direction::EAST, Direction::SOUTH, Direction::WEST, Direction::UP, Direction::DOWN,
+    Direction::NORTHWEST, Direction::NORTHEAST, Direction::SOUTHEAST, Direction::SOUTHWEST, Direction::IN, Direction::OUT
+];
+
+pub fn direction_name(direction: Direction) -> String {
+    direction.to_str().to_lowercase()
+}
+
+// The direction a word names, allowing abbreviations such as "n" or "nw".
+pub fn parse_direction(word: &str) -> Option<Direction> {
+    let word = word.to_lowercase();
+    let short = match word.as_str() {
+        "nw" => Some(Direction::NORTHWEST),
+        "ne" => Some(Direction::NORTHEAST),
+        "se" => Some(Direction::SOUTHEAST),
+        "sw" => Some(Direction::SOUTHWEST),
+        _ => None
+    };
+    short.or_else(|| DIRECTIONS.into_iter().find(|d| !word.is_empty() && direction_name(*d).starts_with(&word)))
+}
+
+// Whether a keyword list, such as "long sword", has a word starting with word.
+pub fn keyword_matches(keywords: &str, word: &str) -> bool {
+    let word = word.to_lowercase();
+    !word.is_empty() && keywords.split_whitespace().any(|keyword| keyword.to_lowercase().starts_with(&word))
+}
+
+// Sentences start with a capital, even when a title like "a guard" doesn't.
+pub fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new()
+    }
+}
+
+fn with_newline(mut text: String) -> String {
+    if !text.ends_with('\n') {
+        text.push('\n');
+    }
+    text
+}
+
+fn position_phrase(position: Position) -> &'static str {
+    match position {
+        Position::Dead => "is lying here, dead.",
+        Position::MortallyWounded => "is lying here, mortally wounded.",
+        Position::Incapacitated => "is lying here, incapacitated.",
+        Position::Stunned => "is lying here, stunned.",
+        Position::Sleeping => "is sleeping here.",
+        Position::Resting => "is resting here.",
+        Position::Sitting => "is sitting here.",
+        Position::Fighting => "is here, fighting.",
+        Position::Standing => "is standing here."
+    }
+}
+
+impl Sight<'_> {
+    fn affected(&self, entity: EntityId, flag: AffectFlags) -> bool {
+        self.affects.get(entity).is_ok_and(|affects| affects.contains(flag))
+    }
+
+    pub fn can_see_in_dark(&self, viewer: Viewer) -> bool {
+        viewer.level >= ADMIN_IMMORTAL || self.affected(viewer.entity, AffectFlags::INFRAVISION)
+    }
+
+    pub fn is_blind(&self, viewer: Viewer) -> bool {
+        viewer.level < ADMIN_IMMORTAL && self.affected(viewer.entity, AffectFlags::BLIND)
+    }
+
+    pub fn is_dark(&self, room: EntityId) -> bool {
+        self.room_flags.get(room).is_ok_and(|flags| flags.contains(RoomFlags::DARK))
+    }
+
+    pub fn can_see_character(&self, viewer: Viewer, target: EntityId) -> bool {
+        if viewer.entity == target || viewer.level >= ADMIN_IMMORTAL {
+            return true;
+        }
+        let invisible = self.affected(target, AffectFlags::INVISIBLE) && !self.affected(viewer.entity, AffectFlags::DETECT_INVIS);
+        !invisible && !self.affected(target, AffectFlags::HIDE)
+    }
+
+    // Whether viewer, in room, can make out target there at all.
+    pub fn notices(&self, viewer: Viewer, room: EntityId, target: EntityId) -> bool {
+        !self.is_blind(viewer) && (!self.is_dark(room) || self.can_see_in_dark(viewer)) && self.can_see_character(viewer, target)
+    }
+
+    pub fn can_see_item(&self, viewer: Viewer, item: EntityId) -> bool {
+        viewer.level >= ADMIN_IMMORTAL
+            || !self.item_flags.get(item).is_ok_and(|flags| flags.contains(ItemFlags::INVISIBLE))
+            || self.affected(viewer.entity, AffectFlags::DETECT_INVIS)
+    }
+
+    pub fn title(&self, entity: EntityId) -> String {
+        self.titles.get(entity).map(|title| title.0.clone()).unwrap_or_else(|_| "something".to_string())
+    }
+
+    // Exits someone can see. Secret exits only show once they're open, unless the viewer
+    // is an immortal.
+    pub fn visible_exits(&self, viewer: Viewer, room: EntityId) -> Vec<(Direction, &Exit)> {
+        let Ok(exits) = self.exits.get(room) else {
+            return Vec::new();
+        };
+        DIRECTIONS.into_iter()
+            .filter_map(|direction| exits.exits.get(&direction).map(|exit| (direction, exit)))
+            .filter(|(_, exit)| self.indexes.room(exit.destination).is_some())
+            .filter(|(_, exit)| {
+                let hidden = exit.flags.contains(ExitFlags::SECRET)
+                    && (exit.flags.contains(ExitFlags::CLOSED) || !exit.flags.contains(ExitFlags::ISDOOR));
+                !hidden || viewer.level >= ADMIN_IMMORTAL
+            })
+            .collect()
+    }
+
+    fn exits_line(&self, viewer: Viewer, room: EntityId) -> String {
+        let exits: Vec<String> = self.visible_exits(viewer, room).into_iter().map(|(direction, exit)| {
+            let mut name = direction_name(direction);
+            if exit.flags.contains(ExitFlags::CLOSED) {
+                name.push_str("(closed)");
+            }
+            if exit.flags.contains(ExitFlags::SECRET) && viewer.level >= ADMIN_BUILDER {
+                name.push_str("(secret)");
+            }
+            name
+        }).collect();
+        if exits.is_empty() {
+            "[ Exits: None! ]\n".to_string()
+        } else {
+            format!("[ Exits: {} ]\n", exits.join(" "))
+        }
+    }
+
+    // The vnum of the prototype something was made from, or its own if it's a prototype.
+    fn vnum(&self, entity: EntityId) -> Option<usize> {
+        if let Ok(prototype) = self.prototypes.get(entity) {
+            return Some(prototype.0);
+        }
+        self.ids.get(entity).ok()
+            .filter(|id| matches!(id.entity_type, DbEntityType::ItemProto | DbEntityType::NpcProto))
+            .map(|id| id.id)
+    }
+
+    // A line of a room listing, with the vnum and flags of what it shows if viewer is a
+    // builder.
+    fn listing_line(&self, viewer: Viewer, entity: EntityId, line: String) -> String {
+        if viewer.level < ADMIN_BUILDER {
+            return line;
+        }
+        let flags = match self.types.get(entity) {
+            Ok(ThingType::Item) => self.item_flags.get(entity).map(|flags| ItemFlags::from_bits_retain(flags.bits()).to_str()),
+            _ => self.affects.get(entity).map(|flags| AffectFlags::from_bits_retain(flags.bits()).to_str())
+        }.unwrap_or_default();
+        let mut text = match self.vnum(entity) {
+            Some(vnum) => format!("[{}] {}", vnum, line.trim_end()),
+            None => line.trim_end().to_string()
+        };
+        if !flags.is_empty() {
+            text.push_str(&format!(" [ {} ]", flags));
+        }
+        text.push('\n');
+        text
+    }
+
+    // How a character appears in a room listing: an NPC's room line if it has one,
+    // otherwise their name and what they're doing.
+    fn person_line(&self, person: EntityId) -> String {
+        match self.short_descs.get(person) {
+            Ok(line) if !line.0.trim().is_empty() => with_newline(line.0.trim_end().to_string()),
+            _ => {
+                let position = self.positions.get(person).copied().unwrap_or_default();
+                format!("{} {}\n", capitalize(&self.title(person)), position_phrase(position))
+            }
+        }
+    }
+
+    // The room as viewer sees it on looking around or arriving.
+    pub fn render_room(&self, viewer: Viewer, room: EntityId) -> String {
+        if self.is_blind(viewer) {
+            return "You can't see a damned thing, you're blind!\n".to_string();
+        }
+        if self.is_dark(room) && !self.can_see_in_dark(viewer) {
+            return "It is pitch black...\n".to_string();
+        }
+
+        let mut text = String::new();
+        if viewer.level >= ADMIN_BUILDER {
+            let vnum = self.ids.get(room).map(|id| id.id).unwrap_or_default();
+            let flags = self.room_flags.get(room).map(|flags| RoomFlags::from_bits_retain(flags.bits()).to_str()).unwrap_or_default();
+            let sector = self.sectors.get(room).copied().unwrap_or_default();
+            let flags = if flags.is_empty() { "NOBITS".to_string() } else { flags };
+            text.push_str(&format!("[{:5}] {} [ {} ] [ {} ]\n", vnum, self.title(room), flags, sector.to_str()));
+        } else {
+            text.push_str(&format!("{}\n", self.title(room)));
+        }
+        if let Ok(description) = self.descriptions.get(room) {
+            text.push_str(&with_newline(description.0.clone()));
+        }
+        text.push_str(&self.exits_line(viewer, room));
+
+        if let Ok(contents) = self.contents.get(room) {
+            for item in contents.items.iter().filter(|item| self.can_see_item(viewer, **item)) {
+                if let Ok(line) = self.short_descs.get(*item) {
+                    if !line.0.trim().is_empty() {
+                        text.push_str(&self.listing_line(viewer, *item, with_newline(line.0.trim_end().to_string())));
+                    }
+                }
+            }
+        }
+        if let Ok(people) = self.people.get(room) {
+            for person in people.0.iter().filter(|p| **p != viewer.entity && self.can_see_character(viewer, **p)) {
+                text.push_str(&self.listing_line(viewer, *person, self.person_line(*person)));
+            }
+        }
+        text
+    }
+
+    fn extra_description(&self, entity: EntityId, word: &str) -> Option<String> {
+        let extras = self.extras.get(entity).ok()?;
+        extras.extras.iter().find(|(keywords, _)| keyword_matches(keywords, word)).map(|(_, text)| text.clone())
+    }
+
+    fn character_matches(&self, entity: EntityId, word: &str) -> bool {
+        match self.keywords.get(entity) {
+            Ok(keywords) => keyword_matches(&keywords.0.join(" "), word),
+            Err(_) => keyword_matches(&self.title(entity), word)
+        }
+    }
+
+    // What viewer sees on looking at something in room: a direction, a character, or
+    // anything with a matching extra description or keyword.
+    pub fn look_at(&self, viewer: Viewer, room: EntityId, target: &str) -> String {
+        if self.is_blind(viewer) {
+            return "You can't see a damned thing, you're blind!\n".to_string();
+        }
+        if self.is_dark(room) && !self.can_see_in_dark(viewer) {
+            return "It is pitch black...\n".to_string();
+        }
+
+        if let Some(direction) = parse_direction(target) {
+            if let Some((_, exit)) = self.visible_exits(viewer, room).into_iter().find(|(d, _)| *d == direction) {
+                let mut text = if exit.description.trim().is_empty() {
+                    "You see nothing special.\n".to_string()
+                } else {
+                    with_newline(exit.description.clone())
+                };
+                if exit.flags.contains(ExitFlags::ISDOOR) && !exit.keyword.is_empty() {
+                    let state = if exit.flags.contains(ExitFlags::CLOSED) { "closed" } else { "open" };
+                    text.push_str(&format!("The {} is {}.\n", door_name(exit), state));
+                }
+                return text;
+            }
+            return "You see nothing special.\n".to_string();
+        }
+
+        let people = self.people.get(room).map(|people| people.0.clone()).unwrap_or_default();
+        let person = people.into_iter()
+            .find(|person| self.can_see_character(viewer, *person) && self.character_matches(*person, target));
+        if let Some(person) = person {
+            return match self.descriptions.get(person) {
+                Ok(description) if !description.0.trim().is_empty() => with_newline(description.0.clone()),
+                _ => format!("You see nothing special about {}.\n", self.title(person))
+            };
+        }
+
+        // What viewer is carrying and wearing comes before what's in the room.
+        let mut items = Vec::new();
+        if let Ok(carried) = self.contents.get(viewer.entity) {
+            items.extend(carried.items.iter().copied());
+            items.extend(carried.equipment.values().copied());
+        }
+        if let Ok(floor) = self.contents.get(room) {
+            items.extend(floor.items.iter().copied());
+        }
+        let items: Vec<EntityId> = items.into_iter().filter(|item| self.can_see_item(viewer, *item)).collect();
+
+        if let Some(text) = items.iter().find_map(|item| self.extra_description(*item, target)) {
+            return with_newline(text);
+        }
+        if let Some(text) = self.extra_description(room, target) {
+            return with_newline(text);
+        }
+        let item = items.into_iter()
+            .find(|item| self.keywords.get(*item).is_ok_and(|keywords| keyword_matches(&keywords.0.join(" "), target)));
+        match item {
+            Some(item) => match self.descriptions.get(item) {
+                Ok(description) if !description.0.trim().is_empty() => with_newline(description.0.clone()),
+                _ => "You see nothing special..\n".to_string()
+            },
+            None => "You do not see that here.\n".to_string()
+        }
+    }
+}
@@ -1 +1,8 @@
+pub mod commands;
+pub mod doors;
+pub mod indexes;
+pub mod location;
+pub mod look;
+pub mod movement;
+pub mod session;
 pub mod utils;
\ No newline at end of file
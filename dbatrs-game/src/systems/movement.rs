@@ -0,0 +1,170 @@
+use std::fmt;
+
+use shipyard::{Borrow, BorrowInfo, EntityId, Get, UniqueView, View, ViewMut};
+use tracing::warn;
+
+use crate::{
+    structs::{
+        character::MovePoints,
+        common::{AffectFlags, DbIndexes, Location, Player, ThingType},
+        item::ItemType,
+        room::{Direction, ExitFlags, Exits, RoomFlags, SectorType}
+    },
+    systems::{
+        commands::{ADMIN_GOD, ADMIN_IMMORTAL},
+        doors::door_name,
+        location::{Destination, Mover}
+    }
+};
+
+// Why someone can't go the way they tried. Displays as what they're told.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Blocked {
+    // No exit that way, or none they know of.
+    NoExit,
+    // A closed door, by its keyword.
+    Closed(String),
+    NeedBoat,
+    NeedWaterBreath,
+    NeedVehicle,
+    // A TUNNEL room with someone in it already.
+    Tunnel,
+    // A PRIVATE room with two people in it already.
+    Private,
+    GodRoom,
+    // A NOMOB room, for an NPC.
+    NoMob,
+    Exhausted
+}
+
+impl fmt::Display for Blocked {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Blocked::NoExit => write!(f, "Alas, you cannot go that way..."),
+            Blocked::Closed(keyword) => write!(f, "The {} seems to be closed.", keyword),
+            Blocked::NeedBoat => write!(f, "You need a boat to go there."),
+            Blocked::NeedWaterBreath => write!(f, "You would drown without being able to breathe water."),
+            Blocked::NeedVehicle => write!(f, "You need a vehicle to go there."),
+            Blocked::Tunnel => write!(f, "There isn't enough room there for more than one person!"),
+            Blocked::Private => write!(f, "There's a private conversation going on in that room."),
+            Blocked::GodRoom => write!(f, "You aren't godly enough to use that room!"),
+            Blocked::NoMob => write!(f, "You can't go in there."),
+            Blocked::Exhausted => write!(f, "You are too exhausted.")
+        }
+    }
+}
+
+impl std::error::Error for Blocked {}
+
+// A move that's allowed: where from and to, and what it costs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Route {
+    pub from: EntityId,
+    pub to: EntityId,
+    pub cost: i64
+}
+
+// Everything needed to decide whether someone can walk out of a room, and to walk them.
+#[derive(Borrow, BorrowInfo)]
+pub struct Paths<'v> {
+    pub mover: Mover<'v>,
+    pub exits: View<'v, Exits>,
+    pub room_flags: View<'v, RoomFlags>,
+    pub sectors: View<'v, SectorType>,
+    pub item_types: View<'v, ItemType>,
+    pub affects: View<'v, AffectFlags>,
+    pub players: View<'v, Player>,
+    pub move_points: ViewMut<'v, MovePoints>,
+    pub indexes: UniqueView<'v, DbIndexes>
+}
+
+impl Paths<'_> {
+    fn affected(&self, entity: EntityId, flag: AffectFlags) -> bool {
+        self.affects.get(entity).is_ok_and(|affects| affects.contains(flag))
+    }
+
+    fn flagged(&self, room: EntityId, flag: RoomFlags) -> bool {
+        self.room_flags.get(room).is_ok_and(|flags| flags.contains(flag))
+    }
+
+    // Whether actor carries or wears an item of this type.
+    fn has_item(&self, actor: EntityId, item_type: ItemType) -> bool {
+        self.mover.contents.get(actor).is_ok_and(|contents| {
+            contents.items.iter().chain(contents.equipment.values())
+                .any(|item| self.item_types.get(*item) == Ok(&item_type))
+        })
+    }
+
+    fn head_count(&self, room: EntityId) -> usize {
+        self.mover.people.get(room).map(|people| people.0.len()).unwrap_or(0)
+    }
+
+    // Where actor, of the given admin level, ends up going this way, or why they can't.
+    // Immortals aren't stopped by terrain, crowds or tiredness, only by walls and doors.
+    pub fn route(&self, actor: EntityId, level: u8, direction: Direction) -> Result<Route, Blocked> {
+        let from = match self.mover.locations.get(actor) {
+            Ok(Location::Inventory(room)) if self.mover.types.get(*room) == Ok(&ThingType::Room) => *room,
+            _ => return Err(Blocked::NoExit)
+        };
+        let exit = self.exits.get(from).ok().and_then(|exits| exits.exits.get(&direction)).ok_or(Blocked::NoExit)?;
+        let to = self.indexes.room(exit.destination).ok_or(Blocked::NoExit)?;
+        if exit.flags.contains(ExitFlags::CLOSED) {
+            // Closed secret doors look like walls.
+            if exit.flags.contains(ExitFlags::SECRET) && level < ADMIN_IMMORTAL {
+                return Err(Blocked::NoExit);
+            }
+            return Err(Blocked::Closed(door_name(exit).to_string()));
+        }
+
+        let npc = !self.players.contains(actor);
+        if npc && self.flagged(to, RoomFlags::NOMOB) {
+            return Err(Blocked::NoMob);
+        }
+        if self.flagged(to, RoomFlags::GODROOM) && level < ADMIN_GOD {
+            return Err(Blocked::GodRoom);
+        }
+        if level >= ADMIN_IMMORTAL {
+            return Ok(Route { from, to, cost: 0 });
+        }
+
+        let sector = self.sectors.get(to).copied().unwrap_or_default();
+        match sector {
+            SectorType::WATER_NOSWIM if !self.affected(actor, AffectFlags::FLYING) && !self.has_item(actor, ItemType::BOAT) => {
+                return Err(Blocked::NeedBoat);
+            },
+            SectorType::UNDERWATER if !self.affected(actor, AffectFlags::WATERBREATH) => return Err(Blocked::NeedWaterBreath),
+            _ => {}
+        }
+        // Until characters can board ships, carrying one will do.
+        if (sector == SectorType::SPACE || self.flagged(to, RoomFlags::VEHICLE)) && !self.has_item(actor, ItemType::VEHICLE) {
+            return Err(Blocked::NeedVehicle);
+        }
+        if self.flagged(to, RoomFlags::TUNNEL) && self.head_count(to) >= 1 {
+            return Err(Blocked::Tunnel);
+        }
+        if self.flagged(to, RoomFlags::PRIVATE) && self.head_count(to) >= 2 {
+            return Err(Blocked::Private);
+        }
+
+        // NPCs, and characters who have never had movement points, walk for free.
+        let here = self.sectors.get(from).copied().unwrap_or_default();
+        let cost = if npc { 0 } else { (here.move_cost() + sector.move_cost()) / 2 };
+        if self.move_points.get(actor).is_ok_and(|points| points.current < cost) {
+            return Err(Blocked::Exhausted);
+        }
+        Ok(Route { from, to, cost })
+    }
+
+    // Walks actor out of their room, paying the cost, and returns the route they took.
+    pub fn walk(&mut self, actor: EntityId, level: u8, direction: Direction) -> Result<Route, Blocked> {
+        let route = self.route(actor, level, direction)?;
+        if let Err(e) = self.mover.move_to(actor, Destination::Room(route.to)) {
+            warn!("Could not walk {:?} to {:?}: {}", actor, route.to, e);
+            return Err(Blocked::NoExit);
+        }
+        if let Ok(mut points) = (&mut self.move_points).get(actor) {
+            points.current -= route.cost;
+        }
+        Ok(route)
+    }
+}
@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+
+use shipyard::{
+    AddComponent, AllStorages, AllStoragesView, Component, EntityId, Get, Remove, Unique, UniqueView, UniqueViewMut,
+    View, ViewMut
+};
+use surrealdb::RecordId;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tracing::warn;
+
+use dbatrs_shared::protocol::ToPortal;
+
+use crate::{
+    link::LinkEvent,
+    structs::common::{DbEntityType, DbIndexes, Location, Title},
+    systems::{
+        commands,
+        location::{Destination, Mover},
+        look::{Sight, Viewer}
+    }
+};
+
+// The connection playing a character. Not persistent: nobody is connected after a restart.
+#[derive(Component, Debug, Clone, PartialEq, Eq)]
+pub struct Session {
+    pub conn: RecordId
+}
+
+// Which character each connection is playing.
+#[derive(Unique, Debug, Default)]
+pub struct Sessions(pub HashMap<RecordId, EntityId>);
+
+// Events from the portal link, waiting for the next pulse.
+#[derive(Unique, Debug)]
+pub struct Inbox(pub UnboundedReceiver<LinkEvent>);
+
+// Messages for the portal link.
+#[derive(Unique, Debug, Clone)]
+pub struct Outbox(pub UnboundedSender<ToPortal>);
+
+impl Outbox {
+    // The link only stops when the game does, so there's nothing to do if it's gone.
+    pub fn send(&self, msg: ToPortal) {
+        let _ = self.0.send(msg);
+    }
+
+    pub fn text(&self, conn: &RecordId, text: impl Into<String>) {
+        self.send(ToPortal::Text { conn: conn.clone(), text: text.into() });
+    }
+
+    pub fn prompt(&self, conn: &RecordId, text: impl Into<String>) {
+        self.send(ToPortal::Prompt { conn: conn.clone(), text: text.into() });
+    }
+}
+
+// The room vnum characters start in when they aren't anywhere yet.
+#[derive(Unique, Debug, Clone, Copy)]
+pub struct StartRoom(pub usize);
+
+// Handles everything the link has sent since the last pulse. Does nothing without an
+// Inbox and Outbox, which run adds once the link is up.
+pub fn handle_input(all: AllStoragesView) {
+    let Ok(outbox) = all.borrow::<UniqueView<Outbox>>().map(|outbox| outbox.clone()) else {
+        return;
+    };
+    let events: Vec<LinkEvent> = match all.borrow::<UniqueViewMut<Inbox>>() {
+        Ok(mut inbox) => std::iter::from_fn(|| inbox.0.try_recv().ok()).collect(),
+        Err(_) => return
+    };
+
+    for event in events {
+        match event {
+            LinkEvent::Login { conn, pc } => login(&all, &outbox, conn, pc),
+            LinkEvent::Input { conn, command } => {
+                let actor = all.borrow::<UniqueView<Sessions>>().unwrap().0.get(&conn).copied();
+                match actor {
+                    Some(actor) => commands::interpret(&all, actor, &conn, &command),
+                    None => outbox.text(&conn, "You aren't playing a character.\n")
+                }
+            },
+            LinkEvent::Logout { conn } => logout(&all, &conn)
+        }
+    }
+}
+
+fn login(all: &AllStorages, outbox: &Outbox, conn: RecordId, pc: Option<usize>) {
+    let character = pc.and_then(|pc| all.borrow::<UniqueView<DbIndexes>>().unwrap().get(&DbEntityType::Player, pc));
+    let Some(character) = character else {
+        outbox.text(&conn, "You have no character to play.\n");
+        return;
+    };
+
+    all.run(|mut sessions: UniqueViewMut<Sessions>, mut views: ViewMut<Session>| {
+        // One connection per character: a new login takes over from the old one.
+        if let Ok(old) = views.get(character) {
+            if old.conn != conn {
+                sessions.0.remove(&old.conn);
+                outbox.send(ToPortal::Disconnect {
+                    conn: old.conn.clone(),
+                    reason: Some("Someone else has logged in as this character.\n".to_string())
+                });
+            }
+        }
+        views.add_component_unchecked(character, Session { conn: conn.clone() });
+        sessions.0.insert(conn.clone(), character);
+    });
+
+    let placed = all.run(|locations: View<Location>, start: UniqueView<StartRoom>, indexes: UniqueView<DbIndexes>| {
+        match locations.get(character) {
+            Ok(_) => Ok(None),
+            Err(_) => indexes.room(start.0).map(Some).ok_or(start.0)
+        }
+    });
+    match placed {
+        Ok(Some(room)) => {
+            if let Err(e) = all.borrow::<Mover>().unwrap().move_to(character, Destination::Room(room)) {
+                warn!("Could not put {:?} in the start room: {}", character, e);
+            }
+        },
+        Ok(None) => {},
+        Err(vnum) => warn!("The start room #{} doesn't exist; {:?} is nowhere.", vnum, character)
+    }
+
+    let name = all.borrow::<View<Title>>().unwrap().get(character).map(|title| title.0.clone()).unwrap_or_default();
+    outbox.text(&conn, format!("Welcome, {}.\n", name));
+    let viewer = Viewer { entity: character, level: commands::admin_level(all, character) };
+    let room = all.borrow::<View<Location>>().unwrap().get(character).ok().and_then(|location| match location {
+        Location::Inventory(room) => Some(*room),
+        Location::Equipped(..) => None
+    });
+    if let Some(room) = room {
+        outbox.text(&conn, all.run(|sight: Sight| sight.render_room(viewer, room)));
+    }
+    outbox.prompt(&conn, commands::PROMPT);
+}
+
+// The character stays in the world; only the connection goes.
+fn logout(all: &AllStorages, conn: &RecordId) {
+    all.run(|mut sessions: UniqueViewMut<Sessions>, mut views: ViewMut<Session>| {
+        if let Some(character) = sessions.0.remove(conn) {
+            if views.get(character).is_ok_and(|session| session.conn == *conn) {
+                views.remove(character);
+            }
+        }
+    });
+}
@@ -0,0 +1,119 @@
+mod common;
+
+use shipyard::{EntityId, Get, View};
+use surrealdb::RecordId;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+
+use dbatrs_shared::protocol::ToPortal;
+use dbatrs_game::{
+    game_loop::{GameLoop, PULSE},
+    link::LinkEvent,
+    structs::{
+        character::Position,
+        common::{DbEntityType, DbIdentifier, Location, Player, ThingType, Title}
+    },
+    systems::commands::{ADMIN_GOD, find_command}
+};
+
+struct Game {
+    game: GameLoop,
+    events: UnboundedSender<LinkEvent>,
+    output: UnboundedReceiver<ToPortal>,
+    room: EntityId,
+    hero: EntityId
+}
+
+fn game(admin_level: u8) -> Game {
+    let common::Game { mut game, events, output, .. } = common::game();
+    let room = game.world.add_entity((
+        DbIdentifier { id: 300, entity_type: DbEntityType::Room },
+        ThingType::Room,
+        Title("The Temple".to_string())
+    ));
+    game.world.add_entity(common::account(1, admin_level));
+    let hero = game.world.add_entity((
+        DbIdentifier { id: 42, entity_type: DbEntityType::Player },
+        ThingType::Character,
+        Player { name: "Kakarot".to_string(), account: 1 },
+        Title("Kakarot".to_string())
+    ));
+    Game { game, events, output, room, hero }
+}
+
+fn conn() -> RecordId {
+    RecordId::from(("conn", "one"))
+}
+
+impl Game {
+    // Sends each event, runs a pulse, and returns the text sent back, prompts left out.
+    fn run(&mut self, events: Vec<LinkEvent>) -> Vec<ToPortal> {
+        for event in events {
+            self.events.send(event).unwrap();
+        }
+        self.game.run_workload(PULSE);
+        std::iter::from_fn(|| self.output.try_recv().ok())
+            .filter(|msg| !matches!(msg, ToPortal::Prompt { .. }))
+            .collect()
+    }
+
+    fn input(&mut self, command: &str) -> Vec<ToPortal> {
+        self.run(vec![LinkEvent::Input { conn: conn(), command: command.to_string() }])
+    }
+}
+
+fn text(text: &str) -> ToPortal {
+    ToPortal::Text { conn: conn(), text: text.to_string() }
+}
+
+#[test]
+fn abbreviations_resolve_by_level() {
+    assert_eq!(find_command("com", 0).map(|(name, _)| name), Some("commands"));
+    assert_eq!(find_command("SAVE", 0).map(|(name, _)| name), Some("save"));
+    // Directions win their abbreviations, cardinal ones first.
+    assert_eq!(find_command("s", 0).map(|(name, _)| name), Some("south"));
+    assert_eq!(find_command("n", 0).map(|(name, _)| name), Some("north"));
+    assert_eq!(find_command("northe", 0).map(|(name, _)| name), Some("northeast"));
+    // Mortals don't see wizard commands at all, even by their full name.
+    assert_eq!(find_command("g", 0).map(|(name, _)| name), None);
+    assert_eq!(find_command("gecho", 0).map(|(name, _)| name), None);
+    assert_eq!(find_command("g", ADMIN_GOD).map(|(name, _)| name), Some("gecho"));
+    assert_eq!(find_command("xyzzy", ADMIN_GOD).map(|(name, _)| name), None);
+}
+
+#[test]
+fn logging_in_binds_the_connection_and_places_the_character() {
+    let mut game = game(0);
+    let replies = game.run(vec![LinkEvent::Login { conn: conn(), pc: Some(42) }]);
+    assert_eq!(replies, vec![text("Welcome, Kakarot.\n"), text("The Temple\n[ Exits: None! ]\n")]);
+    let location = game.game.world.run(|locations: View<Location>| locations.get(game.hero).cloned());
+    assert!(matches!(location, Ok(Location::Inventory(room)) if room == game.room));
+
+    assert_eq!(game.input("xyzzy"), vec![text("Huh?!?\n")]);
+    assert_eq!(game.input("l"), vec![text("The Temple\n[ Exits: None! ]\n")]);
+    assert_eq!(game.input("sav"), vec![text("Saving Kakarot.\n")]);
+    assert_eq!(game.input("gecho hello"), vec![text("Huh?!?\n")]);
+    assert_eq!(game.input("qui"), vec![text("You have to type quit--no less, to quit!\n")]);
+
+    // Once logged out, input goes nowhere.
+    game.run(vec![LinkEvent::Logout { conn: conn() }]);
+    assert_eq!(game.input("save"), vec![text("You aren't playing a character.\n")]);
+}
+
+#[test]
+fn position_and_admin_level_are_checked() {
+    let mut game = game(ADMIN_GOD);
+    game.run(vec![LinkEvent::Login { conn: conn(), pc: Some(42) }]);
+    assert_eq!(game.input("gecho hello"), vec![text("hello\n")]);
+
+    game.game.world.add_component(game.hero, Position::Stunned);
+    assert_eq!(game.input("save"), vec![text("All you can do right now is think about the stars!\n")]);
+    // Commands anyone can use in any position still work.
+    assert_eq!(game.input("gecho hello"), vec![text("hello\n")]);
+}
+
+#[test]
+fn a_login_without_a_character_is_told_so() {
+    let mut game = game(0);
+    assert_eq!(game.run(vec![LinkEvent::Login { conn: conn(), pc: None }]), vec![text("You have no character to play.\n")]);
+    assert_eq!(game.run(vec![LinkEvent::Login { conn: conn(), pc: Some(7) }]), vec![text("You have no character to play.\n")]);
+}
@@ -0,0 +1,110 @@
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+
+use chrono::Utc;
+use shipyard::EntityId;
+use surrealdb::RecordId;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+use dbatrs_shared::{GameConf, protocol::ToPortal};
+use dbatrs_game::{
+    game_loop::{GameLoop, PULSE},
+    link::LinkEvent,
+    structs::{
+        common::{DbEntityType, DbIdentifier, UserAccount},
+        room::{Exit, ExitFlags}
+    },
+    systems::session::{Inbox, Outbox}
+};
+
+pub fn conf() -> GameConf {
+    GameConf {
+        pulses_per_second: 10,
+        violence_pulses: 2,
+        mobile_pulses: 3,
+        zone_pulses: 3,
+        affect_pulses: 5,
+        autosave_pulses: 10,
+        legacy_world: String::new(),
+        start_room: 300
+    }
+}
+
+// An account entity with the given admin level.
+pub fn account(id: usize, admin_level: u8) -> (DbIdentifier, UserAccount) {
+    let now = Utc::now();
+    (DbIdentifier { id, entity_type: DbEntityType::UserAccount }, UserAccount {
+        name: format!("account{}", id),
+        password: String::new(),
+        email: String::new(),
+        created: now,
+        last_login: now,
+        last_logout: now,
+        last_password_change: now,
+        admin_level,
+        rpp: 0,
+        slots: 5,
+        characters: Vec::new(),
+        password_reset: false
+    })
+}
+
+// A GameLoop with an empty world, plus the link's ends of its Inbox and Outbox. Tests that
+// build a map keep its rooms by vnum, and the character they play as hero.
+pub struct Game {
+    pub game: GameLoop,
+    pub events: UnboundedSender<LinkEvent>,
+    pub output: UnboundedReceiver<ToPortal>,
+    pub rooms: HashMap<usize, EntityId>,
+    pub hero: EntityId
+}
+
+pub fn game() -> Game {
+    let game = GameLoop::new(&conf()).unwrap();
+    let (events, inbox) = mpsc::unbounded_channel();
+    let (outbox, output) = mpsc::unbounded_channel();
+    game.world.add_unique(Inbox(inbox));
+    game.world.add_unique(Outbox(outbox));
+    Game { game, events, output, rooms: HashMap::new(), hero: EntityId::dead() }
+}
+
+impl Game {
+    // Sends each event, runs a pulse, and returns the text each connection was sent, by
+    // connection name.
+    pub fn run(&mut self, events: Vec<LinkEvent>) -> HashMap<String, String> {
+        for event in events {
+            self.events.send(event).unwrap();
+        }
+        self.game.run_workload(PULSE);
+        texts(&mut self.output)
+    }
+
+    pub fn input(&mut self, who: &str, command: &str) -> HashMap<String, String> {
+        self.run(vec![LinkEvent::Input { conn: conn(who), command: command.to_string() }])
+    }
+}
+
+pub fn exit(destination: usize) -> Exit {
+    Exit { destination, ..Default::default() }
+}
+
+// A closed door that key 900 locks.
+pub fn gate(destination: usize) -> Exit {
+    Exit { keyword: "gate iron".to_string(), key: Some(900), flags: ExitFlags::ISDOOR | ExitFlags::CLOSED, ..exit(destination) }
+}
+
+pub fn conn(name: &str) -> RecordId {
+    RecordId::from(("conn", name))
+}
+
+// The text each connection has been sent so far, by connection name.
+pub fn texts(output: &mut UnboundedReceiver<ToPortal>) -> HashMap<String, String> {
+    let mut texts: HashMap<String, String> = HashMap::new();
+    while let Ok(msg) = output.try_recv() {
+        if let ToPortal::Text { conn, text } = msg {
+            texts.entry(conn.key().to_string()).or_default().push_str(&text);
+        }
+    }
+    texts
+}
@@ -0,0 +1,161 @@
+mod common;
+
+use std::collections::HashMap;
+
+use shipyard::{EntityId, Get, UniqueView, View};
+
+use dbatrs_game::{
+    link::LinkEvent,
+    structs::{
+        common::{DbEntityType, DbIdentifier, DbIndexes, Keywords, Player, ThingType, Title},
+        item::{ContainerFlags, ItemType, ItemValues, Prototype},
+        room::{Direction, Exit, ExitFlags, Exits}
+    },
+    systems::location::{Destination, Mover}
+};
+
+use common::{Game, exit, gate};
+
+fn game() -> Game {
+    let mut game = common::game();
+
+    for (vnum, title) in [(300, "The Temple"), (301, "The Market"), (302, "The Garden"), (303, "The Crypt")] {
+        let entity = game.game.world.add_entity((
+            DbIdentifier { id: vnum, entity_type: DbEntityType::Room },
+            ThingType::Room,
+            Title(title.to_string())
+        ));
+        game.rooms.insert(vnum, entity);
+    }
+    let hatch = Exit { keyword: "entrance hatch".to_string(), flags: ExitFlags::ISDOOR | ExitFlags::CLOSED, ..exit(303) };
+    let temple_exits = Exits { exits: HashMap::from([
+        (Direction::NORTH, exit(301)),
+        (Direction::EAST, gate(302)),
+        (Direction::DOWN, hatch)
+    ]) };
+    game.game.world.add_component(game.rooms[&300], temple_exits);
+    game.game.world.add_component(game.rooms[&302], Exits { exits: HashMap::from([(Direction::WEST, gate(300))]) });
+
+    game.game.world.add_entity(common::account(1, 0));
+    game.game.world.add_entity(common::account(2, 0));
+    game.hero = game.game.world.add_entity((
+        DbIdentifier { id: 42, entity_type: DbEntityType::Player },
+        ThingType::Character,
+        Player { name: "Kakarot".to_string(), account: 1 },
+        Title("Kakarot".to_string())
+    ));
+    game.game.world.add_entity((
+        DbIdentifier { id: 43, entity_type: DbEntityType::Player },
+        ThingType::Character,
+        Player { name: "Bulma".to_string(), account: 2 },
+        Title("Bulma".to_string())
+    ));
+
+    game.run(vec![
+        LinkEvent::Login { conn: common::conn("one"), pc: Some(42) },
+        LinkEvent::Login { conn: common::conn("two"), pc: Some(43) }
+    ]);
+    game
+}
+
+impl Game {
+    // Puts a new item somewhere.
+    fn item(&mut self, item: (ThingType, ItemType, Keywords, Title), destination: Destination) -> EntityId {
+        let item = self.game.world.add_entity(item);
+        self.game.world.run(|mut mover: Mover| mover.move_to(item, destination)).unwrap();
+        item
+    }
+
+    fn exit_flags(&self, room: usize, direction: Direction) -> ExitFlags {
+        self.game.world.run(|exits: View<Exits>| {
+            ExitFlags::from_bits_retain(exits.get(self.rooms[&room]).unwrap().exits[&direction].flags.bits())
+        })
+    }
+}
+
+fn thing(item_type: ItemType, keyword: &str, title: &str) -> (ThingType, ItemType, Keywords, Title) {
+    (ThingType::Item, item_type, Keywords(vec![keyword.to_string()]), Title(title.to_string()))
+}
+
+#[test]
+fn doors_open_and_close_on_both_sides() {
+    let mut game = game();
+    let garden = Destination::Room(game.rooms[&302]);
+    let friend = game.game.world.run(|indexes: UniqueView<DbIndexes>| indexes.get(&DbEntityType::Player, 43).unwrap());
+    game.game.world.run(|mut mover: Mover| mover.move_to(friend, garden)).unwrap();
+
+    let texts = game.input("one", "open gate");
+    assert_eq!(texts["one"], "Okay.\n");
+    assert_eq!(texts["two"], "The gate is opened from the other side.\n");
+    assert!(!game.exit_flags(300, Direction::EAST).contains(ExitFlags::CLOSED));
+    assert!(!game.exit_flags(302, Direction::WEST).contains(ExitFlags::CLOSED));
+    assert_eq!(game.input("one", "open east")["one"], "It's already open!\n");
+
+    let texts = game.input("two", "close gate west");
+    assert_eq!(texts["two"], "Okay.\n");
+    assert_eq!(texts["one"], "The gate is closed from the other side.\n");
+    assert!(game.exit_flags(300, Direction::EAST).contains(ExitFlags::CLOSED));
+    // An exit without keywords answers to any, but only doors close.
+    assert_eq!(game.input("one", "close gate north")["one"], "You can't close that!\n");
+    assert_eq!(game.input("one", "close gate in")["one"], "I really don't see how you can do that there.\n");
+    assert_eq!(game.input("one", "open portcullis")["one"], "There doesn't seem to be a portcullis here.\n");
+    assert_eq!(game.input("one", "open")["one"], "Open what?\n");
+}
+
+#[test]
+fn a_direction_comes_before_a_keyword() {
+    let mut game = game();
+    // "e" starts the hatch's "entrance" too, but means east.
+    assert_eq!(game.input("one", "open e")["one"], "Okay.\n");
+    assert!(!game.exit_flags(300, Direction::EAST).contains(ExitFlags::CLOSED));
+    assert!(game.exit_flags(300, Direction::DOWN).contains(ExitFlags::CLOSED));
+    assert_eq!(game.input("one", "open ent")["one"], "Okay.\n");
+    assert!(!game.exit_flags(300, Direction::DOWN).contains(ExitFlags::CLOSED));
+}
+
+#[test]
+fn locks_need_the_key_or_a_pick() {
+    let mut game = game();
+    assert_eq!(game.input("one", "lock gate")["one"], "You don't seem to have the proper key.\n");
+    let key = game.item(thing(ItemType::KEY, "key", "an iron key"), Destination::Inventory(game.hero));
+    game.game.world.add_component(key, Prototype(900));
+
+    assert_eq!(game.input("one", "lock gate")["one"], "Okay.\n");
+    assert_eq!(game.exit_flags(302, Direction::WEST).bits(), (ExitFlags::ISDOOR | ExitFlags::CLOSED | ExitFlags::LOCKED).bits());
+    assert_eq!(game.input("one", "open gate")["one"], "It seems to be locked.\n");
+    assert_eq!(game.input("one", "unlock gate")["one"], "Okay.\n");
+    assert_eq!(game.input("one", "unlock gate")["one"], "Oh.. it wasn't locked, after all..\n");
+    assert_eq!(game.input("one", "lock north")["one"], "You can't lock that!\n");
+
+    // Bulma has no key, but can pick the lock unless it's pickproof.
+    game.input("one", "lock gate");
+    assert_eq!(game.input("two", "pick gate")["two"], "The lock quickly yields to your skills.\n");
+    assert!(!game.exit_flags(302, Direction::WEST).contains(ExitFlags::LOCKED));
+    game.input("one", "lock gate");
+    game.game.world.run(|mut exits: shipyard::ViewMut<Exits>| {
+        let mut exits = (&mut exits).get(game.rooms[&300]).unwrap();
+        exits.exits.get_mut(&Direction::EAST).unwrap().flags.insert(ExitFlags::PICKPROOF);
+    });
+    assert_eq!(game.input("two", "pick gate")["two"], "It resists your attempts to pick it.\n");
+}
+
+#[test]
+fn containers_open_close_and_lock() {
+    let mut game = game();
+    let chest = game.item(thing(ItemType::CONTAINER, "chest", "a wooden chest"), Destination::Room(game.rooms[&300]));
+    let flags = ContainerFlags::CLOSEABLE | ContainerFlags::CLOSED | ContainerFlags::LOCKED;
+    game.game.world.add_component(chest, ItemValues(vec![50, flags.bits() as i64, 901]));
+    game.item(thing(ItemType::OTHER, "rock", "a rock"), Destination::Room(game.rooms[&300]));
+    let key = game.item(thing(ItemType::KEY, "key", "a small key"), Destination::Inventory(game.hero));
+    game.game.world.add_component(key, Prototype(901));
+
+    assert_eq!(game.input("one", "open chest")["one"], "It seems to be locked.\n");
+    let texts = game.input("one", "unlock chest");
+    assert_eq!(texts["one"], "Okay.\n");
+    assert_eq!(texts["two"], "Kakarot unlocks a wooden chest.\n");
+    assert_eq!(game.input("one", "open chest")["one"], "Okay.\n");
+    let flags = game.game.world.run(|values: View<ItemValues>| values.get(chest).unwrap().container_flags().bits());
+    assert_eq!(flags, ContainerFlags::CLOSEABLE.bits());
+    assert_eq!(game.input("one", "lock chest")["one"], "But it's currently open!\n");
+    assert_eq!(game.input("one", "open rock")["one"], "You can't open that!\n");
+}
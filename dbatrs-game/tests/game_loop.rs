@@ -0,0 +1,38 @@
+mod common;
+
+use shipyard::{EntityId, UniqueView, UniqueViewMut};
+
+use dbatrs_game::{
+    game_loop::{AFFECT, AUTOSAVE, GameLoop, MOBILE, PULSE, Pulse, VIOLENCE, ZONE},
+    systems::location::{MoveEvent, MoveEvents}
+};
+
+#[test]
+fn schedules_run_on_their_own_intervals() {
+    let game = GameLoop::new(&common::conf()).unwrap();
+    assert_eq!(game.due(0), vec![PULSE, VIOLENCE, MOBILE, ZONE, AFFECT, AUTOSAVE]);
+    assert_eq!(game.due(1), vec![PULSE]);
+    assert_eq!(game.due(4), vec![PULSE, VIOLENCE]);
+    assert_eq!(game.due(6), vec![PULSE, VIOLENCE, MOBILE, ZONE]);
+    assert_eq!(game.due(15), vec![PULSE, MOBILE, ZONE, AFFECT]);
+}
+
+#[test]
+fn pulses_are_counted_in_the_world() {
+    let mut game = GameLoop::new(&common::conf()).unwrap();
+    for _ in 0..3 {
+        game.run_pulse();
+    }
+    // The last pulse run was number 2.
+    assert_eq!(*game.world.borrow::<UniqueView<Pulse>>().unwrap(), Pulse(2));
+}
+
+#[test]
+fn move_events_do_not_carry_over() {
+    let mut game = GameLoop::new(&common::conf()).unwrap();
+    game.world.run(|mut events: UniqueViewMut<MoveEvents>| {
+        events.0.push(MoveEvent { entity: EntityId::dead(), from: None, to: None });
+    });
+    game.run_pulse();
+    assert!(game.world.borrow::<UniqueView<MoveEvents>>().unwrap().0.is_empty());
+}
@@ -0,0 +1,100 @@
+mod common;
+
+use shipyard::{Remove, UniqueView, ViewMut, World};
+
+use dbatrs_game::{
+    game_loop::{GameLoop, PULSE},
+    link::LinkEvent,
+    structs::common::{DbEntityType, DbIdentifier, DbIndexes, Player, ThingType, Title},
+    systems::indexes::sync_indexes
+};
+
+fn room(id: usize) -> (DbIdentifier, ThingType) {
+    (DbIdentifier { id, entity_type: DbEntityType::Room }, ThingType::Room)
+}
+
+#[test]
+fn spawning_and_deleting_updates_the_index() {
+    let mut world = World::new();
+    let first = world.add_entity(room(3001));
+    let second = world.add_entity(room(3002));
+    let zone = world.add_entity(DbIdentifier { id: 30, entity_type: DbEntityType::Zone });
+    sync_indexes(&world).unwrap();
+    {
+        let indexes = world.borrow::<UniqueView<DbIndexes>>().unwrap();
+        assert_eq!(indexes.room(3001), Some(first));
+        assert_eq!(indexes.get(&DbEntityType::Room, 3002), Some(second));
+        assert_eq!(indexes.get(&DbEntityType::Zone, 30), Some(zone));
+        // The same vnum means something different for each type.
+        assert_eq!(indexes.get(&DbEntityType::Zone, 3001), None);
+    }
+
+    world.delete_entity(first);
+    world.run(|mut ids: ViewMut<DbIdentifier>| ids.remove(second));
+    sync_indexes(&world).unwrap();
+    let indexes = world.borrow::<UniqueView<DbIndexes>>().unwrap();
+    assert_eq!(indexes.room(3001), None);
+    assert_eq!(indexes.room(3002), None);
+    assert_eq!(indexes.get(&DbEntityType::Zone, 30), Some(zone));
+}
+
+#[test]
+fn a_duplicate_vnum_is_an_error_and_keeps_the_first() {
+    let mut world = World::new();
+    let first = world.add_entity(room(3001));
+    sync_indexes(&world).unwrap();
+    let second = world.add_entity(room(3001));
+
+    let e = sync_indexes(&world).unwrap_err();
+    assert_eq!((e.entity_type, e.id, e.existing, e.duplicate), (DbEntityType::Room, 3001, first, second));
+    assert_eq!(world.borrow::<UniqueView<DbIndexes>>().unwrap().room(3001), Some(first));
+
+    // Deleting the duplicate leaves the original indexed.
+    world.delete_entity(second);
+    sync_indexes(&world).unwrap();
+    assert_eq!(world.borrow::<UniqueView<DbIndexes>>().unwrap().room(3001), Some(first));
+}
+
+#[test]
+fn a_changed_id_is_indexed_again() {
+    let mut world = World::new();
+    let entity = world.add_entity(room(3001));
+    sync_indexes(&world).unwrap();
+
+    world.add_component(entity, DbIdentifier { id: 3005, entity_type: DbEntityType::Room });
+    sync_indexes(&world).unwrap();
+    let indexes = world.borrow::<UniqueView<DbIndexes>>().unwrap();
+    assert_eq!(indexes.room(3001), None);
+    assert_eq!(indexes.room(3005), Some(entity));
+}
+
+#[test]
+fn a_duplicate_vnum_does_not_hold_up_input() {
+    let mut game = common::game();
+    let temple = game.game.world.add_entity(room(300));
+    game.game.world.add_entity(common::account(1, 0));
+    game.game.world.add_entity((
+        DbIdentifier { id: 42, entity_type: DbEntityType::Player },
+        ThingType::Character,
+        Player { name: "Kakarot".to_string(), account: 1 },
+        Title("Kakarot".to_string())
+    ));
+    game.game.run_workload(PULSE);
+
+    game.game.world.add_entity(room(300));
+    assert!(game.run(vec![LinkEvent::Login { conn: common::conn("one"), pc: Some(42) }]).contains_key("one"));
+    assert_eq!(game.game.world.borrow::<UniqueView<DbIndexes>>().unwrap().room(300), Some(temple));
+}
+
+#[test]
+fn the_game_loop_indexes_new_entities_each_pulse() {
+    let mut game = GameLoop::new(&common::conf()).unwrap();
+    game.run_workload(PULSE);
+    let entity = game.world.add_entity(room(3001));
+    game.run_workload(PULSE);
+    assert_eq!(game.world.borrow::<UniqueView<DbIndexes>>().unwrap().room(3001), Some(entity));
+
+    game.world.delete_entity(entity);
+    game.run_workload(PULSE);
+    assert_eq!(game.world.borrow::<UniqueView<DbIndexes>>().unwrap().room(3001), None);
+}
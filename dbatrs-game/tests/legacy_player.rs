@@ -0,0 +1,142 @@
+use std::{fs, path::PathBuf};
+
+use shipyard::{Get, UniqueView, View, World};
+use serde_json::json;
+
+use dbatrs_shared::{TotalConf, SurrealConf, SurrealEngine, db::connect_root, migrate};
+use dbatrs_game::{
+    legacy::player::{import_players, set_password},
+    structs::{
+        character::{Race, Sensei, Sex},
+        common::{DbIndexes, Player, UserAccount},
+        item::{SavedItem, SavedItems}
+    }
+};
+
+const ACCOUNT: &str = "\
+Goku
+aBcDeFgHiJkLm
+goku@example.com
+4
+250
+Kakarot
+Empty
+Ghost
+Empty
+Empty
+1
+0
+";
+
+const PLAYER: &str = "\
+Name: Kakarot
+Id  : 42
+Race: 2
+Clas: 1
+Sex : 1
+Levl: 30
+Desc:
+A spiky-haired fighter.
+~
+Skil:
+401 50
+0 0
+Room: 300
+";
+
+const OBJECTS: &str = "\
+0 0 0 0 0 0
+#150
+Loc : 0
+Name: key brass~
+#151
+Loc : 17
+#bad
+$~
+";
+
+fn lib_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("dbatrs-legacy-players-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    for sub in ["user/G", "plrfiles/K-O", "plrobjs/K-O"] {
+        fs::create_dir_all(dir.join(sub)).unwrap();
+    }
+    fs::write(dir.join("user/G/goku.usr"), ACCOUNT).unwrap();
+    fs::write(dir.join("plrfiles/K-O/kakarot.plr"), PLAYER).unwrap();
+    fs::write(dir.join("plrobjs/K-O/kakarot.new"), OBJECTS).unwrap();
+    dir
+}
+
+#[tokio::test]
+async fn imports_accounts_and_their_characters() {
+    let conf = TotalConf {
+        surreal: SurrealConf {
+            engine: SurrealEngine::Memory,
+            namespace: "test".to_string(),
+            database: "test".to_string(),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let db = connect_root(&conf).await.unwrap();
+    migrate::migrate(&db).await.unwrap();
+
+    let dir = lib_dir();
+    let mut world = World::new();
+    let report = import_players(&mut world, &db, &dir).await;
+    assert_eq!((report.accounts, report.players), (1, 1), "{:?}", report.problems);
+    // The bad item, and Ghost, who has no player file.
+    assert_eq!(report.problems.len(), 2, "{:?}", report.problems);
+    assert_eq!(report.problems[0].line, 7);
+    assert!(report.problems[1].message.contains("Ghost"));
+
+    let indexes = world.borrow::<UniqueView<DbIndexes>>().unwrap();
+    let (account, player) = (indexes.user_accounts[&1], indexes.players[&42]);
+    world.run(|accounts: View<UserAccount>, players: View<Player>, races: View<Race>, senseis: View<Sensei>,
+               sexes: View<Sex>, items: View<SavedItems>| {
+        let account = accounts.get(account).unwrap();
+        assert_eq!((account.rpp, account.slots, account.admin_level), (250, 4, 1));
+        assert_eq!(account.characters, vec![42]);
+        assert!(account.password_reset);
+
+        assert_eq!(players.get(player).unwrap().account, 1);
+        assert_eq!(*races.get(player).unwrap(), Race::Saiyan);
+        assert_eq!(*senseis.get(player).unwrap(), Sensei::Roshi);
+        assert_eq!(*sexes.get(player).unwrap(), Sex::Male);
+        assert_eq!(items.get(player).unwrap().0, vec![SavedItem { vnum: 150, location: 0 }, SavedItem { vnum: 151, location: 17 }]);
+    });
+    drop(indexes);
+
+    let user: Option<serde_json::Value> = db.query("SELECT name, password_reset, rpp FROM ONLY user:goku")
+        .await.unwrap().take(0).unwrap();
+    assert_eq!(user, Some(json!({"name": "Goku", "password_reset": true, "rpp": 250})));
+    let pc: Option<serde_json::Value> = db.query("SELECT <string> user AS user, race, array::len(inventory) AS items FROM ONLY pc:42")
+        .await.unwrap().take(0).unwrap();
+    assert_eq!(pc, Some(json!({"user": "user:goku", "race": "Saiyan", "items": 2})));
+
+    // Nobody can sign in to an imported account until it has a new password.
+    let signin = db.query("SELECT * FROM user WHERE email = 'goku@example.com' AND !password_reset").await.unwrap()
+        .take::<Vec<serde_json::Value>>(0).unwrap();
+    assert!(signin.is_empty());
+
+    // Importing again doesn't duplicate anything, even with a new account sorting first.
+    fs::create_dir_all(dir.join("user/A")).unwrap();
+    fs::write(dir.join("user/A/abe.usr"), ACCOUNT.replace("Goku", "Abe").replace("goku@", "abe@").replace("Kakarot", "Empty")).unwrap();
+    let again = import_players(&mut World::new(), &db, &dir).await;
+    assert_eq!((again.accounts, again.players), (1, 0), "{:?}", again.problems);
+    let names: Vec<String> = db.query("SELECT VALUE name FROM user ORDER BY name").await.unwrap().take(0).unwrap();
+    assert_eq!(names, ["Abe", "Goku"]);
+    // Abe sorts first, but Goku already has the first id.
+    let ids: Vec<serde_json::Value> = db.query("SELECT name, account_id FROM user ORDER BY name").await.unwrap().take(0).unwrap();
+    assert_eq!(ids, [json!({"name": "Abe", "account_id": 2}), json!({"name": "Goku", "account_id": 1})]);
+
+    // An admin can give an imported account a password to sign in with.
+    assert!(set_password(&db, " GOKU ", "kamehameha").await.unwrap());
+    assert!(!set_password(&db, "vegeta", "final flash").await.unwrap());
+    let signin = db.query("SELECT VALUE name FROM user WHERE email = 'goku@example.com' AND !password_reset \
+                           AND crypto::argon2::compare(password, 'kamehameha')").await.unwrap()
+        .take::<Vec<String>>(0).unwrap();
+    assert_eq!(signin, ["Goku"]);
+
+    let _ = fs::remove_dir_all(&dir);
+}
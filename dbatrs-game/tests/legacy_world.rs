@@ -0,0 +1,228 @@
+use std::{fs, path::PathBuf};
+
+use shipyard::{Get, UniqueView, View, World};
+
+use dbatrs_game::{
+    legacy::world::import_world,
+    structs::{
+        character::{MobFlags, NpcStats, Race, Sex},
+        common::{DbIndexes, Description, ExtraDescriptions, Keywords, Scripts, Title, Weight},
+        item::{Cost, ItemAffects, ItemType, ItemValues, WearFlags},
+        room::{Direction, ExitFlags, Exits, RoomFlags, SectorType},
+        script::TriggerProto,
+        zone::{Zone, ZoneCommand, ZoneCommands}
+    }
+};
+
+const ROOMS: &str = "\
+#100
+The Lookout~
+A windswept ledge.
+Far below, the city sprawls.
+~
+1 ad 0 0 0 2
+D0
+A heavy gate.
+~
+gate~
+1 150 101
+E
+city sprawl~
+Smoke rises from a thousand chimneys.
+~
+S
+T 5
+#101
+The Gatehouse~
+Cramped and dark.
+~
+1 0 0 0 0 0
+D2
+~
+~
+0 -1 100
+D5
+~
+~
+0 -1 -1
+S
+$~
+";
+
+const OBJECTS: &str = "\
+#150
+key brass~
+a brass key~
+A brass key lies here.~
+~
+18 0 0 0 0 ao 0 0 0 0 0 0 0
+0 0 0 0
+1 10 0 0
+E
+key~
+It is stamped with the number 101.
+~
+A
+13 5
+$~
+";
+
+const MOBILES: &str = "\
+#200
+guard~
+the gate guard~
+A gate guard stands here.
+~
+He looks bored.
+~
+ab 0 0 0 0 0 0 0 500 E
+12 2 4 10d10+50 2d6+3
+100 1200 3 0
+8 8 1
+Size: 5
+E
+$~
+";
+
+const ZONES: &str = "\
+#1
+Builder~
+The Lookout~
+100 199 30 2 0 0 0 0 1 100
+M 0 200 1 101 (the gate guard)
+G 1 150 1 (a brass key)
+D 0 100 0 2 (gate)
+V 0 2 0 100 visited 1
+S
+$~
+";
+
+const TRIGGERS: &str = "\
+#5
+Greet~
+2 g 100
+~
+%send% %actor% The wind howls.
+~
+$~
+";
+
+fn world_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("dbatrs-legacy-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    for (kind, text) in [("wld", ROOMS), ("obj", OBJECTS), ("mob", MOBILES), ("zon", ZONES), ("trg", TRIGGERS)] {
+        fs::create_dir_all(dir.join(kind)).unwrap();
+        fs::write(dir.join(kind).join(format!("1.{}", kind)), text).unwrap();
+        fs::write(dir.join(kind).join("index"), format!("1.{}\n$\n", kind)).unwrap();
+    }
+    dir
+}
+
+#[test]
+fn imports_every_kind_of_record() {
+    let dir = world_dir("all");
+    let mut world = World::new();
+    let report = import_world(&mut world, &dir);
+    assert!(report.problems.is_empty(), "{:?}", report.problems);
+    assert_eq!((report.zones, report.rooms, report.items, report.npcs, report.triggers), (1, 2, 1, 1, 1));
+
+    let indexes = world.borrow::<UniqueView<DbIndexes>>().unwrap();
+    let lookout = indexes.rooms[&100];
+    world.run(|titles: View<Title>, descs: View<Description>, flags: View<RoomFlags>, sectors: View<SectorType>,
+               exits: View<Exits>, extras: View<ExtraDescriptions>, scripts: View<Scripts>| {
+        assert_eq!(titles.get(lookout).unwrap().0, "The Lookout");
+        assert_eq!(descs.get(lookout).unwrap().0, "A windswept ledge.\nFar below, the city sprawls.\n");
+        assert_eq!(flags.get(lookout).unwrap().bits(), (RoomFlags::DARK | RoomFlags::INDOORS).bits());
+        assert_eq!(*sectors.get(lookout).unwrap(), SectorType::FIELD);
+
+        let north = &exits.get(lookout).unwrap().exits[&Direction::NORTH];
+        assert_eq!((north.destination, north.key, north.keyword.as_str()), (101, Some(150), "gate"));
+        assert_eq!(north.flags.bits(), ExitFlags::ISDOOR.bits());
+        assert_eq!(extras.get(lookout).unwrap().extras[0].0, "city sprawl");
+        assert_eq!(scripts.get(lookout).unwrap().0, vec![5]);
+
+        // The exit to -1 goes nowhere, so only south is kept.
+        let gatehouse = &exits.get(indexes.rooms[&101]).unwrap().exits;
+        assert_eq!(gatehouse.len(), 1);
+        assert_eq!(gatehouse[&Direction::SOUTH].key, None);
+    });
+
+    let key = indexes.item_proto[&150];
+    world.run(|keywords: View<Keywords>, types: View<ItemType>, wear: View<WearFlags>, values: View<ItemValues>,
+               weights: View<Weight>, costs: View<Cost>, affects: View<ItemAffects>| {
+        assert_eq!(keywords.get(key).unwrap().0, vec!["key", "brass"]);
+        assert_eq!(*types.get(key).unwrap(), ItemType::KEY);
+        assert_eq!(wear.get(key).unwrap().bits(), 1 << 0 | 1 << 14);
+        assert_eq!(values.get(key).unwrap().0, vec![0, 0, 0, 0]);
+        assert_eq!((weights.get(key).unwrap().0, costs.get(key).unwrap().0), (1.0, 10));
+        assert_eq!((affects.get(key).unwrap().0[0].location, affects.get(key).unwrap().0[0].modifier), (13, 5));
+    });
+
+    let guard = indexes.npc_proto[&200];
+    world.run(|flags: View<MobFlags>, stats: View<NpcStats>, races: View<Race>, sexes: View<Sex>| {
+        assert_eq!(flags.get(guard).unwrap().bits(), 0b11);
+        let stats = stats.get(guard).unwrap();
+        assert_eq!((stats.level, stats.alignment, stats.gold, stats.exp), (12, 500, 100, 1200));
+        assert_eq!((stats.hit_dice.count, stats.hit_dice.sides, stats.hit_dice.bonus), (10, 10, 50));
+        assert_eq!(*races.get(guard).unwrap(), Race::from_value(3).unwrap());
+        assert_eq!(*sexes.get(guard).unwrap(), Sex::Male);
+    });
+
+    let zone = indexes.zones[&1];
+    world.run(|zones: View<Zone>, commands: View<ZoneCommands>, triggers: View<TriggerProto>| {
+        let zone_data = zones.get(zone).unwrap();
+        assert_eq!((zone_data.bottom, zone_data.top, zone_data.builders.as_str()), (100, 199, "Builder"));
+        let commands = &commands.get(zone).unwrap().0;
+        assert_eq!(commands.len(), 4);
+        assert!(matches!(commands[0], ZoneCommand::Mobile { if_flag: false, mobile: 200, max: 1, room: 101 }));
+        assert!(matches!(commands[2], ZoneCommand::Door { room: 100, direction: Direction::NORTH, state: 2, .. }));
+        assert!(matches!(&commands[3], ZoneCommand::Variable { name, value, .. } if name == "visited" && value == "1"));
+
+        let greet = triggers.get(indexes.script_proto[&5]).unwrap();
+        assert_eq!((greet.attach_type, greet.trigger_types, greet.narg), (2, 1 << 6, 100));
+        assert_eq!(greet.commands, "%send% %actor% The wind howls.\n");
+    });
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn reports_bad_records_and_keeps_going() {
+    let dir = world_dir("bad");
+    fs::write(dir.join("wld").join("1.wld"), "\
+#300
+Broken~
+No flags follow.
+~
+S
+#301
+Fine~
+Still loaded.
+~
+0 0 0
+S
+#301
+Again~
+A duplicate.
+~
+0 0 0
+S
+$~
+").unwrap();
+
+    let mut world = World::new();
+    let report = import_world(&mut world, &dir);
+    assert_eq!(report.rooms, 1);
+    assert_eq!(report.problems.len(), 2, "{:?}", report.problems);
+    assert!(report.problems[0].file.ends_with("wld/1.wld"));
+    assert_eq!(report.problems[0].line, 5);
+    assert_eq!(report.problems[1].line, 12);
+    assert!(report.problems[1].message.contains("duplicate"));
+
+    let indexes = world.borrow::<UniqueView<DbIndexes>>().unwrap();
+    assert!(indexes.rooms.contains_key(&301));
+    assert!(!indexes.rooms.contains_key(&300));
+    drop(indexes);
+
+    let _ = fs::remove_dir_all(&dir);
+}
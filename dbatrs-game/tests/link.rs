@@ -0,0 +1,35 @@
+use serde_json::Value as JsonValue;
+use surrealdb::RecordId;
+
+use dbatrs_shared::{TotalConf, SurrealConf, SurrealEngine, db::connect_root, migrate};
+use dbatrs_game::link::session_character;
+
+#[tokio::test]
+async fn sessions_start_with_the_first_character_and_are_reused() {
+    let conf = TotalConf {
+        surreal: SurrealConf {
+            engine: SurrealEngine::Memory,
+            namespace: "test".to_string(),
+            database: "test".to_string(),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let db = connect_root(&conf).await.unwrap();
+    migrate::migrate(&db).await.unwrap();
+    db.query("CREATE user:1 SET email = 'goku@example.com', password = 'x'; \
+              CREATE user:2 SET email = 'vegeta@example.com', password = 'x'; \
+              CREATE pc:42 SET name = 'Kakarot', lower_name = 'Kakarot', user = user:1; \
+              CREATE pc:7 SET name = 'Gohan', lower_name = 'Gohan', user = user:1;")
+        .await.unwrap().check().unwrap();
+
+    let goku = RecordId::from(("user", 1));
+    assert_eq!(session_character(&db, &goku).await.unwrap(), Some(7));
+    // The session is kept, so the same character comes back.
+    db.query("UPDATE pc:7 SET user = NONE").await.unwrap().check().unwrap();
+    assert_eq!(session_character(&db, &goku).await.unwrap(), Some(7));
+    let sessions: Vec<JsonValue> = db.query("SELECT VALUE <string> pc FROM game_session").await.unwrap().take(0).unwrap();
+    assert_eq!(sessions, vec![JsonValue::from("pc:7")]);
+
+    assert_eq!(session_character(&db, &RecordId::from(("user", 2))).await.unwrap(), None);
+}
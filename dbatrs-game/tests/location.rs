@@ -0,0 +1,111 @@
+use shipyard::{EntitiesView, EntityId, Get, UniqueView, View, ViewMut, World};
+
+use dbatrs_game::{
+    structs::{
+        common::{Location, ThingContents, ThingType},
+        room::People
+    },
+    systems::location::{Destination, MoveError, MoveEvents, Mover, check_locations}
+};
+
+struct Things {
+    world: World,
+    room: EntityId,
+    other_room: EntityId,
+    hero: EntityId,
+    bag: EntityId,
+    pouch: EntityId,
+    sword: EntityId
+}
+
+fn things() -> Things {
+    let mut world = World::new();
+    world.add_unique(MoveEvents::default());
+    let room = world.add_entity(ThingType::Room);
+    let other_room = world.add_entity(ThingType::Room);
+    let hero = world.add_entity(ThingType::Character);
+    let bag = world.add_entity(ThingType::Item);
+    let pouch = world.add_entity(ThingType::Item);
+    let sword = world.add_entity(ThingType::Item);
+    Things { world, room, other_room, hero, bag, pouch, sword }
+}
+
+fn move_to(world: &World, entity: EntityId, destination: Destination) -> Result<(), MoveError> {
+    world.run(|mut mover: Mover| mover.move_to(entity, destination))
+}
+
+fn problems(world: &World) -> Vec<String> {
+    world.run(|entities: EntitiesView, types: View<ThingType>, locations: View<Location>, contents: View<ThingContents>,
+               people: View<People>| check_locations(&entities, &types, &locations, &contents, &people))
+}
+
+#[test]
+fn moves_keep_every_index_in_step() {
+    let t = things();
+    move_to(&t.world, t.hero, Destination::Room(t.room)).unwrap();
+    move_to(&t.world, t.bag, Destination::Room(t.room)).unwrap();
+    move_to(&t.world, t.pouch, Destination::Container(t.bag)).unwrap();
+    move_to(&t.world, t.sword, Destination::Equipment(t.hero, 16)).unwrap();
+    assert!(problems(&t.world).is_empty(), "{:?}", problems(&t.world));
+
+    t.world.run(|people: View<People>, contents: View<ThingContents>| {
+        assert_eq!(people.get(t.room).unwrap().0, vec![t.hero]);
+        assert_eq!(contents.get(t.room).unwrap().items, vec![t.bag]);
+        assert_eq!(contents.get(t.bag).unwrap().items, vec![t.pouch]);
+        assert_eq!(contents.get(t.hero).unwrap().equipment[&16], t.sword);
+    });
+
+    // Moving takes things out of where they were.
+    move_to(&t.world, t.hero, Destination::Room(t.other_room)).unwrap();
+    move_to(&t.world, t.sword, Destination::Inventory(t.hero)).unwrap();
+    move_to(&t.world, t.bag, Destination::Inventory(t.hero)).unwrap();
+    assert!(problems(&t.world).is_empty(), "{:?}", problems(&t.world));
+    t.world.run(|people: View<People>, contents: View<ThingContents>| {
+        assert!(people.get(t.room).unwrap().0.is_empty());
+        assert!(contents.get(t.room).unwrap().items.is_empty());
+        assert!(contents.get(t.hero).unwrap().equipment.is_empty());
+        assert_eq!(contents.get(t.hero).unwrap().items, vec![t.sword, t.bag]);
+    });
+
+    let events = t.world.borrow::<UniqueView<MoveEvents>>().unwrap();
+    assert_eq!(events.0.len(), 7);
+    assert!(events.0[0].from.is_none());
+    assert!(matches!(events.0[4].from, Some(Location::Inventory(room)) if room == t.room));
+    drop(events);
+
+    assert_eq!(t.world.run(|mut mover: Mover| mover.detach(t.sword)).map(|_| ()), Some(()));
+    assert!(t.world.borrow::<View<Location>>().unwrap().get(t.sword).is_err());
+    assert!(problems(&t.world).is_empty(), "{:?}", problems(&t.world));
+}
+
+#[test]
+fn bad_moves_are_rejected() {
+    let t = things();
+    move_to(&t.world, t.pouch, Destination::Container(t.bag)).unwrap();
+    move_to(&t.world, t.sword, Destination::Equipment(t.hero, 16)).unwrap();
+
+    assert_eq!(move_to(&t.world, t.bag, Destination::Container(t.bag)), Err(MoveError::Cycle));
+    assert_eq!(move_to(&t.world, t.bag, Destination::Container(t.pouch)), Err(MoveError::Cycle));
+    assert_eq!(move_to(&t.world, t.room, Destination::Room(t.other_room)), Err(MoveError::NotMovable(t.room)));
+    assert_eq!(move_to(&t.world, t.bag, Destination::Room(t.hero)),
+               Err(MoveError::WrongHolder { holder: t.hero, expected: ThingType::Room }));
+    assert_eq!(move_to(&t.world, t.bag, Destination::Equipment(t.hero, 16)), Err(MoveError::SlotTaken(t.sword)));
+    // Re-equipping the same slot is fine.
+    move_to(&t.world, t.sword, Destination::Equipment(t.hero, 16)).unwrap();
+    assert!(problems(&t.world).is_empty(), "{:?}", problems(&t.world));
+}
+
+#[test]
+fn the_check_finds_drift() {
+    let t = things();
+    move_to(&t.world, t.bag, Destination::Room(t.room)).unwrap();
+    move_to(&t.world, t.hero, Destination::Room(t.room)).unwrap();
+
+    // Changed behind the Mover's back.
+    t.world.run(|mut locations: ViewMut<Location>, mut contents: ViewMut<ThingContents>| {
+        (&mut locations).get(t.bag).unwrap().clone_from(&Location::Inventory(t.other_room));
+        (&mut contents).get(t.room).unwrap().items.push(t.sword);
+    });
+    let found = problems(&t.world);
+    assert_eq!(found.len(), 3, "{:?}", found);
+}
@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+
+use shipyard::{EntityId, World};
+
+use dbatrs_game::{
+    structs::{
+        common::{
+            AffectFlags, DbEntityType, DbIdentifier, Description, ExtraDescriptions, Keywords, ShortDesc, ThingType, Title
+        },
+        item::{ItemFlags, Prototype},
+        room::{Direction, Exit, ExitFlags, Exits, RoomFlags, SectorType}
+    },
+    systems::{
+        commands::{ADMIN_BUILDER, ADMIN_IMMORTAL},
+        indexes::sync_indexes,
+        location::{Destination, MoveEvents, Mover},
+        look::{Sight, Viewer}
+    }
+};
+
+struct Scene {
+    world: World,
+    temple: EntityId,
+    cellar: EntityId,
+    hero: EntityId
+}
+
+fn exit(destination: usize, flags: ExitFlags) -> Exit {
+    Exit { destination, flags, keyword: "door".to_string(), ..Default::default() }
+}
+
+fn room(world: &mut World, vnum: usize, title: &str) -> EntityId {
+    world.add_entity((DbIdentifier { id: vnum, entity_type: DbEntityType::Room }, ThingType::Room, Title(title.to_string())))
+}
+
+fn scene() -> Scene {
+    let mut world = World::new();
+    world.add_unique(MoveEvents::default());
+
+    let temple = room(&mut world, 3001, "The Temple Of Midgaard");
+    let cellar = room(&mut world, 3002, "A Dark Cellar");
+    room(&mut world, 3003, "The Altar");
+    room(&mut world, 3004, "A Hidden Passage");
+    world.add_component(temple, (
+        Description("You are in the southern end of the temple hall.\n".to_string()),
+        ExtraDescriptions { extras: vec![("altar stone".to_string(), "The altar is carved from white marble.".to_string())] },
+        RoomFlags::INDOORS | RoomFlags::PEACEFUL,
+        SectorType::INSIDE,
+        Exits { exits: HashMap::from([
+            (Direction::DOWN, Exit { description: "Steps lead down into the dark.".to_string(), ..exit(3002, ExitFlags::empty()) }),
+            (Direction::NORTH, exit(3003, ExitFlags::ISDOOR | ExitFlags::CLOSED)),
+            (Direction::WEST, exit(3004, ExitFlags::ISDOOR | ExitFlags::CLOSED | ExitFlags::SECRET)),
+            // Leads nowhere that exists.
+            (Direction::EAST, exit(9999, ExitFlags::empty()))
+        ]) }
+    ));
+    world.add_component(cellar, RoomFlags::DARK);
+
+    let hero = world.add_entity((ThingType::Character, Title("Kakarot".to_string())));
+    let guard = world.add_entity((
+        DbIdentifier { id: 3060, entity_type: DbEntityType::NpcProto },
+        ThingType::Character,
+        Keywords(vec!["guard".to_string(), "cityguard".to_string()]),
+        Title("the cityguard".to_string()),
+        ShortDesc("A cityguard stands here.".to_string()),
+        Description("He looks bored.\n".to_string())
+    ));
+    let friend = world.add_entity((ThingType::Character, Title("Bulma".to_string())));
+    let ghost = world.add_entity((ThingType::Character, Title("a ghost".to_string()), AffectFlags::INVISIBLE));
+    let sword = world.add_entity((
+        ThingType::Item,
+        Prototype(3021),
+        Keywords(vec!["sword".to_string(), "long".to_string()]),
+        Title("a long sword".to_string()),
+        ShortDesc("A long sword lies here.".to_string()),
+        ExtraDescriptions { extras: vec![("sword blade".to_string(), "The blade is notched.".to_string())] }
+    ));
+    let ring = world.add_entity((ThingType::Item, ShortDesc("A ring is here.".to_string()), ItemFlags::INVISIBLE));
+    sync_indexes(&world).unwrap();
+
+    world.run(|mut mover: Mover| {
+        for thing in [hero, guard, friend, ghost, sword, ring] {
+            mover.move_to(thing, Destination::Room(temple)).unwrap();
+        }
+    });
+    Scene { world, temple, cellar, hero }
+}
+
+fn look(scene: &Scene, level: u8, room: EntityId, target: Option<&str>) -> String {
+    let viewer = Viewer { entity: scene.hero, level };
+    scene.world.run(|sight: Sight| match target {
+        Some(target) => sight.look_at(viewer, room, target),
+        None => sight.render_room(viewer, room)
+    })
+}
+
+#[test]
+fn a_room_shows_what_a_mortal_can_see() {
+    let scene = scene();
+    assert_eq!(look(&scene, 0, scene.temple, None), "\
+The Temple Of Midgaard
+You are in the southern end of the temple hall.
+[ Exits: north(closed) down ]
+A long sword lies here.
+A cityguard stands here.
+Bulma is standing here.
+");
+}
+
+#[test]
+fn builders_see_vnums_flags_and_hidden_things() {
+    let scene = scene();
+    let text = look(&scene, ADMIN_BUILDER, scene.temple, None);
+    assert!(text.starts_with("[ 3001] The Temple Of Midgaard [ INDOORS,PEACEFUL ] [ INSIDE ]\n"), "{}", text);
+    assert!(text.contains("[ Exits: north(closed) west(closed)(secret) down ]\n"), "{}", text);
+    assert!(text.contains("\n[3021] A long sword lies here.\n"), "{}", text);
+    assert!(text.contains("\nA ring is here. [ INVISIBLE ]\n"), "{}", text);
+    assert!(text.contains("\n[3060] A cityguard stands here.\n"), "{}", text);
+    assert!(text.contains("\nA ghost is standing here. [ INVISIBLE ]\n"), "{}", text);
+}
+
+#[test]
+fn immortals_see_hidden_things_without_tags() {
+    let scene = scene();
+    assert_eq!(look(&scene, ADMIN_IMMORTAL, scene.temple, None), "\
+The Temple Of Midgaard
+You are in the southern end of the temple hall.
+[ Exits: north(closed) west(closed) down ]
+A long sword lies here.
+A ring is here.
+A cityguard stands here.
+Bulma is standing here.
+A ghost is standing here.
+");
+}
+
+#[test]
+fn darkness_hides_the_room_without_infravision() {
+    let mut scene = scene();
+    assert_eq!(look(&scene, 0, scene.cellar, None), "It is pitch black...\n");
+    scene.world.add_component(scene.hero, AffectFlags::INFRAVISION);
+    assert_eq!(look(&scene, 0, scene.cellar, None), "A Dark Cellar\n[ Exits: None! ]\n");
+}
+
+#[test]
+fn looking_at_things() {
+    let scene = scene();
+    assert_eq!(look(&scene, 0, scene.temple, Some("guard")), "He looks bored.\n");
+    assert_eq!(look(&scene, 0, scene.temple, Some("bulma")), "You see nothing special about Bulma.\n");
+    assert_eq!(look(&scene, 0, scene.temple, Some("blade")), "The blade is notched.\n");
+    assert_eq!(look(&scene, 0, scene.temple, Some("altar")), "The altar is carved from white marble.\n");
+    assert_eq!(look(&scene, 0, scene.temple, Some("d")), "Steps lead down into the dark.\n");
+    assert_eq!(look(&scene, 0, scene.temple, Some("north")), "You see nothing special.\nThe door is closed.\n");
+    // Hidden things can't be looked at either.
+    assert_eq!(look(&scene, 0, scene.temple, Some("ghost")), "You do not see that here.\n");
+    assert_eq!(look(&scene, 0, scene.temple, Some("west")), "You see nothing special.\n");
+    assert_eq!(look(&scene, 0, scene.temple, Some("unicorn")), "You do not see that here.\n");
+}
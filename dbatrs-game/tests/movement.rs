@@ -0,0 +1,169 @@
+mod common;
+
+use std::collections::HashMap;
+
+use shipyard::{EntityId, Get, View};
+
+use dbatrs_game::{
+    link::LinkEvent,
+    structs::{
+        character::MovePoints,
+        common::{AffectFlags, DbEntityType, DbIdentifier, Location, Player, ThingType, Title},
+        item::ItemType,
+        room::{Direction, Exit, ExitFlags, Exits, RoomFlags, SectorType}
+    },
+    systems::{
+        commands::{ADMIN_GOD, ADMIN_IMMORTAL},
+        location::{Destination, Mover},
+        movement::{Blocked, Paths}
+    }
+};
+
+use common::{Game, exit, gate};
+
+fn game() -> Game {
+    let mut game = common::game();
+
+    let mut room = |vnum: usize, title: &str, sector: SectorType, flags: RoomFlags| {
+        let entity = game.game.world.add_entity((
+            DbIdentifier { id: vnum, entity_type: DbEntityType::Room },
+            ThingType::Room,
+            Title(title.to_string()),
+            sector,
+            flags
+        ));
+        game.rooms.insert(vnum, entity);
+    };
+    room(300, "The Temple", SectorType::INSIDE, RoomFlags::empty());
+    room(301, "The Market", SectorType::CITY, RoomFlags::empty());
+    room(302, "The Garden", SectorType::FIELD, RoomFlags::empty());
+    room(303, "The Lake", SectorType::WATER_NOSWIM, RoomFlags::empty());
+    room(304, "The Lake Bed", SectorType::UNDERWATER, RoomFlags::empty());
+    room(305, "Outer Space", SectorType::SPACE, RoomFlags::empty());
+    room(306, "A Narrow Tunnel", SectorType::INSIDE, RoomFlags::TUNNEL);
+    room(307, "The Throne Room", SectorType::INSIDE, RoomFlags::GODROOM);
+    room(308, "The Shrine", SectorType::INSIDE, RoomFlags::NOMOB);
+    room(309, "A Mountain Pass", SectorType::MOUNTAIN, RoomFlags::empty());
+    let temple_exits = Exits { exits: HashMap::from([
+        (Direction::NORTH, exit(301)),
+        (Direction::EAST, gate(302)),
+        (Direction::WEST, exit(303)),
+        (Direction::DOWN, exit(304)),
+        (Direction::UP, exit(305)),
+        (Direction::SOUTH, exit(306)),
+        (Direction::NORTHEAST, exit(307)),
+        (Direction::SOUTHEAST, exit(308)),
+        (Direction::NORTHWEST, exit(309)),
+        (Direction::SOUTHWEST, Exit { flags: ExitFlags::ISDOOR | ExitFlags::CLOSED | ExitFlags::SECRET, ..exit(302) })
+    ]) };
+    game.game.world.add_component(game.rooms[&300], temple_exits);
+    game.game.world.add_component(game.rooms[&301], Exits { exits: HashMap::from([(Direction::SOUTH, exit(300))]) });
+    game.game.world.add_component(game.rooms[&302], Exits { exits: HashMap::from([(Direction::WEST, gate(300))]) });
+
+    game.game.world.add_entity(common::account(1, 0));
+    game.game.world.add_entity(common::account(2, 0));
+    game.hero = game.game.world.add_entity((
+        DbIdentifier { id: 42, entity_type: DbEntityType::Player },
+        ThingType::Character,
+        Player { name: "Kakarot".to_string(), account: 1 },
+        Title("Kakarot".to_string()),
+        MovePoints { current: 10, max: 10 }
+    ));
+    game.game.world.add_entity((
+        DbIdentifier { id: 43, entity_type: DbEntityType::Player },
+        ThingType::Character,
+        Player { name: "Bulma".to_string(), account: 2 },
+        Title("Bulma".to_string())
+    ));
+    let miner = game.game.world.add_entity((ThingType::Character, Title("a miner".to_string())));
+    game.game.world.run(|mut mover: Mover| mover.move_to(miner, Destination::Room(game.rooms[&306]))).unwrap();
+
+    game.run(vec![
+        LinkEvent::Login { conn: common::conn("one"), pc: Some(42) },
+        LinkEvent::Login { conn: common::conn("two"), pc: Some(43) }
+    ]);
+    game
+}
+
+impl Game {
+    fn room_of(&self, entity: EntityId) -> Option<EntityId> {
+        self.game.world.run(|locations: View<Location>| match locations.get(entity) {
+            Ok(Location::Inventory(room)) => Some(*room),
+            _ => None
+        })
+    }
+
+    fn route(&self, level: u8, direction: Direction) -> Result<usize, Blocked> {
+        let route = self.game.world.run(|paths: Paths| paths.route(self.hero, level, direction))?;
+        Ok(*self.rooms.iter().find(|(_, room)| **room == route.to).unwrap().0)
+    }
+}
+
+#[test]
+fn walking_moves_the_character_and_tells_both_rooms() {
+    let mut game = game();
+    let texts = game.input("one", "n");
+    assert_eq!(texts["one"], "The Market\n[ Exits: south ]\n");
+    assert_eq!(texts["two"], "Kakarot leaves north.\n");
+    assert_eq!(game.room_of(game.hero), Some(game.rooms[&301]));
+    let points = game.game.world.run(|points: View<MovePoints>| *points.get(game.hero).unwrap());
+    assert_eq!(points.current, 9);
+
+    let texts = game.input("two", "north");
+    assert_eq!(texts["one"], "Bulma has arrived.\n");
+
+    // Sneaking goes unnoticed.
+    game.game.world.add_component(game.hero, AffectFlags::SNEAK);
+    let texts = game.input("one", "s");
+    assert!(!texts.contains_key("two"));
+    assert_eq!(game.room_of(game.hero), Some(game.rooms[&300]));
+}
+
+#[test]
+fn walls_and_doors_stop_everyone() {
+    let mut game = game();
+    assert_eq!(game.input("one", "e")["one"], "The gate seems to be closed.\n");
+    assert_eq!(game.input("one", "sw")["one"], "Alas, you cannot go that way...\n");
+    assert_eq!(game.route(ADMIN_IMMORTAL, Direction::SOUTHWEST), Err(Blocked::Closed("door".to_string())));
+    assert_eq!(game.route(ADMIN_GOD, Direction::EAST), Err(Blocked::Closed("gate".to_string())));
+    assert_eq!(game.route(ADMIN_GOD, Direction::IN), Err(Blocked::NoExit));
+    assert_eq!(game.room_of(game.hero), Some(game.rooms[&300]));
+}
+
+#[test]
+fn sectors_need_the_right_gear() {
+    let mut game = game();
+    assert_eq!(game.route(0, Direction::WEST), Err(Blocked::NeedBoat));
+    assert_eq!(game.route(0, Direction::DOWN), Err(Blocked::NeedWaterBreath));
+    assert_eq!(game.route(0, Direction::UP), Err(Blocked::NeedVehicle));
+
+    game.game.world.add_component(game.hero, AffectFlags::FLYING | AffectFlags::WATERBREATH);
+    let ship = game.game.world.add_entity((ThingType::Item, ItemType::VEHICLE));
+    game.game.world.run(|mut mover: Mover| mover.move_to(ship, Destination::Inventory(game.hero))).unwrap();
+    assert_eq!(game.route(0, Direction::WEST), Ok(303));
+    assert_eq!(game.route(0, Direction::DOWN), Ok(304));
+    assert_eq!(game.route(0, Direction::UP), Ok(305));
+}
+
+#[test]
+fn rooms_restrict_who_comes_in() {
+    let mut game = game();
+    assert_eq!(game.route(0, Direction::SOUTH), Err(Blocked::Tunnel));
+    assert_eq!(game.route(ADMIN_IMMORTAL, Direction::SOUTH), Ok(306));
+    assert_eq!(game.route(ADMIN_IMMORTAL, Direction::NORTHEAST), Err(Blocked::GodRoom));
+    assert_eq!(game.route(ADMIN_GOD, Direction::NORTHEAST), Ok(307));
+    assert_eq!(game.route(0, Direction::SOUTHEAST), Ok(308));
+
+    // NPCs are kept out of NOMOB rooms.
+    game.game.world.delete_component::<Player>(game.hero);
+    assert_eq!(game.route(0, Direction::SOUTHEAST), Err(Blocked::NoMob));
+}
+
+#[test]
+fn terrain_costs_movement_points() {
+    let mut game = game();
+    game.game.world.add_component(game.hero, MovePoints { current: 2, max: 10 });
+    // Inside costs 1 and a mountain 6, so the pass costs 3.
+    assert_eq!(game.input("one", "nw")["one"], "You are too exhausted.\n");
+    assert_eq!(game.route(ADMIN_IMMORTAL, Direction::NORTHWEST), Ok(309));
+}
@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+
+use shipyard::{AllStoragesView, Get, UniqueView, UniqueViewMut, View, ViewMut, World};
+use surrealdb::{Surreal, engine::any::Any};
+
+use dbatrs_shared::{TotalConf, SurrealConf, SurrealEngine, db::connect_root, migrate};
+use dbatrs_game::{
+    persist::{self, DeletedRecords},
+    structs::{
+        common::{DbEntityType, DbIdentifier, DbIndexes, Location, ThingContents, ThingType, Title},
+        room::{Direction, Exit, Exits, People, RoomFlags},
+        zone::ZoneFlags
+    }
+};
+
+async fn database() -> Surreal<Any> {
+    let conf = TotalConf {
+        surreal: SurrealConf {
+            engine: SurrealEngine::Memory,
+            namespace: "test".to_string(),
+            database: "test".to_string(),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let db = connect_root(&conf).await.unwrap();
+    migrate::migrate(&db).await.unwrap();
+    db
+}
+
+fn id(id: usize, entity_type: DbEntityType) -> DbIdentifier {
+    DbIdentifier { id, entity_type }
+}
+
+fn save_dirty(world: &World) -> persist::Snapshot {
+    world.run(|all: AllStoragesView| persist::take_dirty(&all))
+}
+
+#[tokio::test]
+async fn entities_survive_a_round_trip() {
+    let db = database().await;
+    let mut world = World::new();
+    world.add_unique(DeletedRecords::default());
+
+    let exits = HashMap::from([(Direction::NORTH, Exit { destination: 101, ..Default::default() })]);
+    let room = world.add_entity((id(100, DbEntityType::Room), ThingType::Room, Title("The Lookout".to_string()),
+                                 RoomFlags::DARK, Exits { exits }));
+    world.add_entity((id(100, DbEntityType::Zone), ZoneFlags::QUEST));
+    let player = world.add_entity((id(42, DbEntityType::Player), ThingType::Character, Location::Inventory(room)));
+    world.add_entity((id(150, DbEntityType::ItemProto), ThingType::Item, Location::Equipped(player, 3)));
+    // Not persistent, so never saved.
+    world.add_entity((ThingType::Item, Location::Inventory(room)));
+
+    let snapshot = save_dirty(&world);
+    assert_eq!(snapshot.rows.len(), 4);
+    persist::write(&db, &snapshot).await.unwrap();
+    assert!(save_dirty(&world).is_empty());
+
+    let mut loaded = World::new();
+    let report = persist::load_world(&mut loaded, &db).await.unwrap();
+    assert_eq!(report.entities, 4);
+    assert!(report.problems.is_empty(), "{:?}", report.problems);
+    // Nothing loaded needs saving again.
+    assert!(save_dirty(&loaded).is_empty());
+
+    let indexes = loaded.borrow::<UniqueView<DbIndexes>>().unwrap();
+    let (room, player, item) = (indexes.rooms[&100], indexes.players[&42], indexes.item_proto[&150]);
+    assert!(indexes.zones.contains_key(&100));
+    loaded.run(|titles: View<Title>, flags: View<RoomFlags>, exits: View<Exits>, locations: View<Location>,
+                people: View<People>, contents: View<ThingContents>| {
+        assert_eq!(titles.get(room).unwrap().0, "The Lookout");
+        assert_eq!(flags.get(room).unwrap().bits(), RoomFlags::DARK.bits());
+        assert_eq!(exits.get(room).unwrap().exits[&Direction::NORTH].destination, 101);
+        assert!(matches!(locations.get(player).unwrap(), Location::Inventory(holder) if *holder == room));
+
+        // Runtime-only components come back from Location.
+        assert_eq!(people.get(room).unwrap().0, vec![player]);
+        assert_eq!(contents.get(player).unwrap().equipment[&3], item);
+        assert!(contents.get(room).is_err());
+    });
+}
+
+#[tokio::test]
+async fn only_changes_are_saved() {
+    let db = database().await;
+    let mut world = World::new();
+    world.add_unique(DeletedRecords::default());
+    let first = world.add_entity((id(1, DbEntityType::Room), Title("One".to_string())));
+    let second = world.add_entity((id(2, DbEntityType::Room), Title("Two".to_string())));
+    let third = world.add_entity((id(3, DbEntityType::Room), Title("Three".to_string())));
+    persist::write(&db, &save_dirty(&world)).await.unwrap();
+
+    // Changes are picked up without anyone marking them; deletions have to be recorded.
+    world.run(|mut titles: ViewMut<Title>| (&mut titles).get(first).unwrap().0 = "Uno".to_string());
+    world.remove::<(Title,)>(third);
+    world.delete_entity(second);
+    world.run(|mut deleted: UniqueViewMut<DeletedRecords>| deleted.0.push(id(2, DbEntityType::Room)));
+    let snapshot = save_dirty(&world);
+    assert_eq!((snapshot.rows.len(), snapshot.removed.len()), (2, 1));
+    persist::write(&db, &snapshot).await.unwrap();
+
+    let mut loaded = World::new();
+    persist::load_world(&mut loaded, &db).await.unwrap();
+    let indexes = loaded.borrow::<UniqueView<DbIndexes>>().unwrap();
+    assert_eq!(indexes.rooms.len(), 2);
+    let titles = loaded.borrow::<View<Title>>().unwrap();
+    assert_eq!(titles.get(indexes.rooms[&1]).unwrap().0, "Uno");
+    assert!(titles.get(indexes.rooms[&3]).is_err());
+}
@@ -0,0 +1,54 @@
+use std::{
+    sync::Arc,
+    time::Duration
+};
+
+use surrealdb::{Surreal, engine::any::Any};
+use tokio::time;
+use tracing::{info, warn};
+
+use dbatrs_shared::TotalConf;
+
+// Removes conn records (and their queued input/output) matching the WHERE clause, then
+// the game_session of any user who just lost their last connection. Those sessions would
+// otherwise hold their character through the unique_character index. Sessions of users
+// who had no conn to begin with are the game's business and are left alone.
+async fn remove_conns(db: &Surreal<Any>, condition: &str, portal: &str, stale_secs: u64) -> Result<usize, surrealdb::Error> {
+    let query = format!("LET $conns = (SELECT id, user FROM conn WHERE {});
+        DELETE conn_input WHERE conn INSIDE $conns.id;
+        DELETE conn_output WHERE conn INSIDE $conns.id;
+        DELETE conn WHERE id INSIDE $conns.id;
+        DELETE game_session WHERE user INSIDE $conns.user AND user NOTINSIDE (SELECT VALUE user FROM conn);
+        RETURN array::len($conns);", condition);
+
+    let mut response = db.query(query)
+        .bind(("portal", portal.to_string()))
+        .bind(("stale", stale_secs))
+        .await?;
+    let removed: Option<usize> = response.take(5)?;
+    Ok(removed.unwrap_or(0))
+}
+
+// Called at startup. Anything tagged with our portal ID is left over from a previous run
+// that didn't shut down cleanly, since no connections exist yet.
+pub async fn remove_orphans(db: &Surreal<Any>, portal: &str) -> Result<usize, surrealdb::Error> {
+    remove_conns(db, "portal = $portal", portal, 0).await
+}
+
+// Removes conn records from any portal that haven't had a heartbeat in stale_secs.
+pub async fn remove_stale(db: &Surreal<Any>, stale_secs: u64) -> Result<usize, surrealdb::Error> {
+    remove_conns(db, "time_system_activity < time::now() - duration::from::secs($stale)", "", stale_secs).await
+}
+
+// Runs forever, periodically sweeping stale connections left by crashed portals.
+pub async fn run_sweeper(conf: Arc<TotalConf>, db: Surreal<Any>) {
+    let mut interval = time::interval(Duration::from_secs(conf.portal.sweep_interval));
+    loop {
+        interval.tick().await;
+        match remove_stale(&db, conf.portal.stale_timeout).await {
+            Ok(0) => {},
+            Ok(count) => info!("Removed {} stale connection records.", count),
+            Err(e) => warn!("Stale connection sweep failed: {}", e)
+        }
+    }
+}
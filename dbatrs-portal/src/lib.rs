@@ -1 +1,60 @@
-pub mod telnet;
\ No newline at end of file
+use std::{
+    future::Future,
+    sync::Arc
+};
+
+use futures::future::join_all;
+use tokio::sync::mpsc;
+use surrealdb::{Surreal, engine::any::Any};
+use tracing::{info, warn};
+
+use dbatrs_shared::TotalConf;
+
+use crate::telnet::listen::{TelnetListener, Msg2Listener};
+
+pub mod cleanup;
+pub mod link;
+pub mod telnet;
+
+// Runs the portal until shutdown resolves and every connection has closed, or the
+// shutdown timeout passes. db is a root connection with migrations already applied.
+pub async fn run(conf: Arc<TotalConf>, db: Surreal<Any>, shutdown: impl Future<Output = ()>) -> Result<(), Box<dyn std::error::Error>> {
+    let mut v = Vec::new();
+
+    // Anything left tagged with our ID is from a previous run that didn't exit cleanly.
+    match cleanup::remove_orphans(&db, &conf.portal.id).await {
+        Ok(0) => {},
+        Ok(count) => info!("Removed {} orphaned connection records from a previous run.", count),
+        Err(e) => warn!("Could not remove orphaned connection records: {}", e)
+    }
+
+    let shared_db = conf.surreal.engine.is_embedded().then(|| db.clone());
+    tokio::spawn(cleanup::run_sweeper(conf.clone(), db.clone()));
+
+    // Unbounded, so the listener never blocks on the link while the link is waiting on it.
+    let (tx_game, rx_game) = mpsc::unbounded_channel();
+
+    info!("Starting up {} telnet listener(s)...", conf.portal.listeners.len());
+    let mut telnet_acceptor = TelnetListener::new(conf.clone(), shared_db, tx_game).await?;
+
+    let tx_telnet = telnet_acceptor.tx_telnet.clone();
+    tokio::spawn(link::run(conf.clone(), db, rx_game, tx_telnet.clone()));
+    v.push(tokio::spawn(async move {telnet_acceptor.run().await;}));
+
+    info!("Starting all tasks...");
+    let mut tasks = join_all(v);
+
+    tokio::select! {
+        _ = &mut tasks => {},
+        _ = shutdown => {
+            info!("Shutdown requested; closing connections...");
+            let _ = tx_telnet.send(Msg2Listener::Shutdown).await;
+
+            let timeout = std::time::Duration::from_secs(conf.portal.shutdown_timeout);
+            if tokio::time::timeout(timeout, &mut tasks).await.is_err() {
+                warn!("Connections did not close within {} seconds; exiting anyway.", conf.portal.shutdown_timeout);
+            }
+        }
+    }
+    Ok(())
+}
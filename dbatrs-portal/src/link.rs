@@ -0,0 +1,76 @@
+use std::{
+    sync::Arc,
+    time::Duration
+};
+
+use surrealdb::{Surreal, engine::any::Any};
+use tokio::{
+    sync::mpsc,
+    time
+};
+use tracing::{info, warn};
+
+use dbatrs_shared::{
+    TotalConf,
+    protocol::ToGame,
+    transport::{self, PortalLink, Transport}
+};
+
+use crate::telnet::listen::Msg2Listener;
+
+// Carries ToGame messages from every connection to the game, and hands whatever the game
+// sends back to the listener for routing. Reconnects whenever the link drops.
+pub async fn run(conf: Arc<TotalConf>, db: Surreal<Any>, mut rx_game: mpsc::UnboundedReceiver<ToGame>,
+                 tx_listener: mpsc::Sender<Msg2Listener>) {
+    let retry = Duration::from_secs(conf.transport.reconnect_interval);
+    loop {
+        let mut link: PortalLink = match transport::connect_portal(&conf, db.clone()).await {
+            Ok(link) => link,
+            Err(e) => {
+                warn!("Could not link to the game over the {:?} transport: {}", conf.transport.kind, e);
+                // Nobody is listening, so input is dropped until we're back.
+                let wait = time::sleep(retry);
+                tokio::pin!(wait);
+                loop {
+                    tokio::select! {
+                        _ = &mut wait => break,
+                        msg = rx_game.recv() => if msg.is_none() { return; }
+                    }
+                }
+                continue;
+            }
+        };
+
+        info!("Linked to the game over the {:?} transport.", conf.transport.kind);
+        let _ = tx_listener.send(Msg2Listener::GameLinked).await;
+
+        loop {
+            tokio::select! {
+                msg = rx_game.recv() => {
+                    let Some(msg) = msg else {
+                        return;
+                    };
+                    if let Err(e) = link.send(msg).await {
+                        warn!("Lost the game link: {}", e);
+                        break;
+                    }
+                },
+                msg = link.recv() => {
+                    match msg {
+                        Ok(Some(msg)) => {
+                            let _ = tx_listener.send(Msg2Listener::Game(msg)).await;
+                        },
+                        Ok(None) => {
+                            warn!("The game closed the link.");
+                            break;
+                        },
+                        Err(e) => {
+                            warn!("Lost the game link: {}", e);
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -3,58 +3,75 @@ use std::{
     sync::Arc
 };
 
-use futures::future::join_all;
-
 use tokio;
 use tokio::sync::mpsc::{Sender, Receiver, channel};
 
 use tracing::{error, info, Level};
-use tracing_subscriber;
-use tracing_subscriber::layer::SubscriberExt;
-use tracing_subscriber::{prelude::*, fmt};
-
-use dbatrs_shared::TotalConf;
 
-use dbatrs_portal::{
-    telnet::listen::TelnetListener
-};
+use clap::{Parser, Subcommand};
+
+use dbatrs_shared::{ConfArgs, TotalConf, migrate};
+
+#[derive(Parser, Debug)]
+#[command(name = "dbatrs-portal", version)]
+struct Cli {
+    #[command(flatten)]
+    conf: ConfArgs,
+    #[command(subcommand)]
+    command: Option<Command>
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Apply pending schema migrations, then exit.
+    Migrate {
+        /// Only list applied and pending migrations.
+        #[arg(long)]
+        status: bool
+    }
+}
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let cli = Cli::parse();
 
-    // This check will also ensure we're in the right directory.
-    let conf = Arc::new(TotalConf::set("devel")?);
+    let conf: TotalConf = match cli.conf.load() {
+        Ok(conf) => conf,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
 
-    // Create a formatting layer. Customize this as needed (targets, time, etc.)
-    let fmt_layer = fmt::layer()
-        .with_target(true)
-        .with_level(true);
+    if cli.conf.check_config {
+        println!("Configuration for mode '{}' in {} is valid.", cli.conf.mode, cli.conf.config_dir.display());
+        return Ok(());
+    }
 
-    // Create the tracy profiling layer.
-    let tracy_layer = tracy_full::tracing::TracyLayer;
+    if conf.surreal.engine.is_embedded() {
+        eprintln!("surreal.engine is '{:?}', but embedded databases only work with dbatrs-dev.", conf.surreal.engine);
+        std::process::exit(1);
+    }
 
-    // Build a composite subscriber that includes the logging filter, formatted logging, and Tracy profiling.
-    let subscriber = tracing_subscriber::registry()
-        .with(fmt_layer)
-        .with(tracy_layer);
+    if let Some(Command::Migrate { status }) = cli.command {
+        let db = dbatrs_shared::db::connect_root(&conf).await?;
+        return Ok(migrate::run_migrate(&db, status).await?);
+    }
 
-    // Set this as the global default subscriber.
-    tracing::subscriber::set_global_default(subscriber)
-        .expect("Failed to set global subscriber");
+    let conf = Arc::new(conf);
 
-    info!("dbatrs-portal starting up...");
-
-    let mut v = Vec::new();
+    dbatrs_shared::logging::init(&conf, "portal")?;
 
-    info!("Starting up telnet acceptor on {}...", conf.portal.telnet);
-    let mut telnet_acceptor = TelnetListener::new(conf.clone()).await?;
+    info!("dbatrs-portal starting up...");
 
-    let tx_telnet = telnet_acceptor.tx_telnet.clone();
-    v.push(tokio::spawn(async move {telnet_acceptor.run().await;}));
+    let db = dbatrs_shared::db::connect_root(&conf).await?;
+    if let Err(e) = migrate::startup(&db, &conf.surreal).await {
+        error!("{}", e);
+        std::process::exit(1);
+    }
 
-    info!("Starting all tasks...");
-    join_all(v).await;
+    dbatrs_portal::run(conf, db, dbatrs_shared::shutdown::wait_for_signal()).await?;
 
     info!("dbatrs-portal shutting down.");
     Ok(())
-}
\ No newline at end of file
+}
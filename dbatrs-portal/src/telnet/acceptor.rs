@@ -0,0 +1,234 @@
+use std::{
+    fs,
+    io::BufReader,
+    net::{Ipv4Addr, SocketAddr},
+    sync::Arc,
+    time::Duration
+};
+
+use futures::{SinkExt, StreamExt};
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, DuplexStream},
+    net::TcpListener,
+    sync::mpsc,
+    time
+};
+#[cfg(unix)]
+use tokio::net::UnixListener;
+use tokio_rustls::{
+    TlsAcceptor,
+    rustls::{ServerConfig, crypto::ring}
+};
+use tokio_tungstenite::{WebSocketStream, tungstenite::Message};
+use tracing::{error, info};
+
+use dbatrs_shared::{ListenAddr, ListenerConf, ListenerProtocol, TotalConf};
+
+use crate::telnet::{
+    listen::Msg2Listener,
+    proxy
+};
+
+// A client that hasn't finished PROXY, TLS or WebSocket handshakes within this long is dropped.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Any stream a TelnetProtocol can run over.
+pub trait ClientStream: AsyncRead + AsyncWrite + Send + Sync + Unpin {}
+
+impl<T: AsyncRead + AsyncWrite + Send + Sync + Unpin> ClientStream for T {}
+
+// A connection that has finished its listener's handshakes and is ready for telnet.
+pub struct Accepted {
+    pub stream: Box<dyn ClientStream>,
+    pub addr: SocketAddr,
+    pub tls: bool,
+    // The listener is behind a proxy, which may pass the client's address on in MNES.
+    pub proxied: bool,
+    pub banner: Option<String>
+}
+
+enum Bound {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener)
+}
+
+// What happens to each connection between accept and telnet. Cloned into a task per
+// connection, so a slow handshake doesn't hold up the next accept.
+#[derive(Clone)]
+struct Handshake {
+    protocol: ListenerProtocol,
+    proxy: bool,
+    tls: Option<TlsAcceptor>,
+    banner: Option<String>
+}
+
+// Accepts clients on one of portal.listeners and passes them to the TelnetListener.
+pub struct Acceptor {
+    address: String,
+    bound: Bound,
+    handshake: Handshake
+}
+
+impl Acceptor {
+    pub async fn bind(conf: &TotalConf, listener: &ListenerConf) -> Result<Self, Box<dyn std::error::Error>> {
+        let bound = match listener.listen_addr() {
+            Some(ListenAddr::Tcp(addr)) => Bound::Tcp(bind_tcp(addr)?),
+            #[cfg(unix)]
+            Some(ListenAddr::Unix(path)) => {
+                let path = conf.resolve_path(&path);
+                dbatrs_shared::transport::remove_stale_socket(&path)?;
+                Bound::Unix(UnixListener::bind(path)?)
+            },
+            _ => return Err(format!("can't listen on '{}'", listener.address).into())
+        };
+
+        let tls = match listener.protocol {
+            ListenerProtocol::Tls => Some(load_tls(conf, listener)?),
+            _ => None
+        };
+
+        info!("Listening for {:?} clients on {}{}.", listener.protocol, listener.address,
+              if listener.proxy { " behind a proxy" } else { "" });
+        Ok(Self {
+            address: listener.address.clone(),
+            bound,
+            handshake: Handshake {
+                protocol: listener.protocol,
+                proxy: listener.proxy,
+                tls,
+                banner: (!listener.banner.is_empty()).then(|| listener.banner.clone())
+            }
+        })
+    }
+
+    pub async fn run(self, tx_listener: mpsc::Sender<Msg2Listener>) {
+        loop {
+            let accepted = match &self.bound {
+                Bound::Tcp(listener) => listener.accept().await.map(|(stream, addr)| {
+                    let _ = stream.set_nodelay(true);
+                    self.spawn_handshake(stream, addr, tx_listener.clone());
+                }),
+                // Unix peers have no address of their own; a proxy in front can supply one.
+                #[cfg(unix)]
+                Bound::Unix(listener) => listener.accept().await.map(|(stream, _)| {
+                    self.spawn_handshake(stream, SocketAddr::from((Ipv4Addr::LOCALHOST, 0)), tx_listener.clone());
+                })
+            };
+            if let Err(e) = accepted {
+                error!("Error accepting connection on {}: {}", self.address, e);
+                // Usually out of file descriptors; give some a chance to close.
+                time::sleep(Duration::from_millis(100)).await;
+            }
+        }
+    }
+
+    fn spawn_handshake<S>(&self, stream: S, addr: SocketAddr, tx_listener: mpsc::Sender<Msg2Listener>)
+        where S: AsyncRead + AsyncWrite + Send + Sync + Unpin + 'static {
+        let handshake = self.handshake.clone();
+        tokio::spawn(async move {
+            match time::timeout(HANDSHAKE_TIMEOUT, handshake.run(stream, addr)).await {
+                Ok(Ok(accepted)) => {
+                    let _ = tx_listener.send(Msg2Listener::Accepted(accepted)).await;
+                },
+                Ok(Err(e)) => info!("Connection from {} failed its handshake: {}", addr, e),
+                Err(_) => info!("Connection from {} timed out during its handshake.", addr)
+            }
+        });
+    }
+}
+
+impl Handshake {
+    async fn run<S>(self, mut stream: S, mut addr: SocketAddr) -> Result<Accepted, Box<dyn std::error::Error + Send + Sync>>
+        where S: AsyncRead + AsyncWrite + Send + Sync + Unpin + 'static {
+        // The PROXY header comes first, even ahead of TLS.
+        if self.proxy {
+            if let Some(client) = proxy::read_header(&mut stream).await? {
+                addr = client;
+            }
+        }
+
+        let stream: Box<dyn ClientStream> = match (self.protocol, &self.tls) {
+            (ListenerProtocol::Tls, Some(tls)) => Box::new(tls.accept(stream).await?),
+            (ListenerProtocol::WebSocket, _) => Box::new(bridge_websocket(tokio_tungstenite::accept_async(stream).await?)),
+            _ => Box::new(stream)
+        };
+
+        Ok(Accepted {
+            stream,
+            addr,
+            tls: self.protocol == ListenerProtocol::Tls,
+            proxied: self.proxy,
+            banner: self.banner
+        })
+    }
+}
+
+// IPv6 listeners are made IPv6-only so an IPv4 listener can share the port, as most
+// systems would otherwise have [::] claim IPv4 too.
+fn bind_tcp(addr: SocketAddr) -> std::io::Result<TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    if addr.is_ipv6() {
+        socket.set_only_v6(true)?;
+    }
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(1024)?;
+    TcpListener::from_std(socket.into())
+}
+
+fn load_tls(conf: &TotalConf, listener: &ListenerConf) -> Result<TlsAcceptor, Box<dyn std::error::Error>> {
+    let cert_path = conf.resolve_path(&listener.tls_cert);
+    let key_path = conf.resolve_path(&listener.tls_key);
+    let certs = rustls_pemfile::certs(&mut BufReader::new(fs::File::open(&cert_path)?))
+        .collect::<Result<Vec<_>, _>>()?;
+    let key = rustls_pemfile::private_key(&mut BufReader::new(fs::File::open(&key_path)?))?
+        .ok_or_else(|| format!("no private key in {}", key_path.display()))?;
+
+    let config = ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+        .with_safe_default_protocol_versions()?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)?;
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+// TelnetProtocol wants a byte stream, so WebSocket messages are unpacked into one half of
+// an in-memory pipe, and whatever it writes to the other half goes out as binary messages.
+fn bridge_websocket<S>(ws: WebSocketStream<S>) -> DuplexStream
+    where S: AsyncRead + AsyncWrite + Send + Unpin + 'static {
+    let (ours, theirs) = tokio::io::duplex(64 * 1024);
+    tokio::spawn(async move {
+        let (mut ws_tx, mut ws_rx) = ws.split();
+        let (mut rd, mut wr) = tokio::io::split(theirs);
+        let mut buf = vec![0u8; 8192];
+        loop {
+            tokio::select! {
+                msg = ws_rx.next() => {
+                    let data = match msg {
+                        Some(Ok(Message::Binary(data))) => data,
+                        Some(Ok(Message::Text(text))) => text.into_bytes(),
+                        // Pings are answered by tungstenite itself.
+                        Some(Ok(Message::Ping(_) | Message::Pong(_) | Message::Frame(_))) => continue,
+                        Some(Ok(Message::Close(_)) | Err(_)) | None => break
+                    };
+                    if wr.write_all(&data).await.is_err() {
+                        break;
+                    }
+                },
+                read = rd.read(&mut buf) => {
+                    let n = match read {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => n
+                    };
+                    if ws_tx.send(Message::Binary(buf[..n].to_vec())).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+        let _ = ws_tx.close().await;
+    });
+    ours
+}
@@ -18,7 +18,7 @@ use flate2::{
 use super::codes;
 
 // TelnetEvents are the bread and butter of this Codec.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum TelnetEvent {
     // WILL|WONT|DO|DONT <OPTION>
     Negotiate(u8, u8),
@@ -33,10 +33,42 @@ pub enum TelnetEvent {
     Command(u8)
 }
 
+// Doubles every IAC byte so it's read as data rather than the start of a command.
+fn escape_iac(data: &[u8], out: &mut BytesMut) {
+    for byte in data.iter() {
+        if *byte == codes::IAC {
+            out.put_u8(codes::IAC);
+        }
+        out.put_u8(*byte);
+    }
+}
+
+// Collapses each escaped IAC IAC pair back into a single IAC byte.
+fn unescape_iac(data: &[u8]) -> Bytes {
+    let mut out = BytesMut::with_capacity(data.len());
+    let mut prev_iac = false;
+    for byte in data.iter() {
+        if *byte == codes::IAC && prev_iac {
+            prev_iac = false;
+            continue;
+        }
+        prev_iac = *byte == codes::IAC;
+        out.put_u8(*byte);
+    }
+    out.freeze()
+}
+
 impl From<TelnetEvent> for Bytes {
     fn from(src: TelnetEvent) -> Self {
         match src {
-            TelnetEvent::Data(data) => data,
+            TelnetEvent::Data(data) => {
+                if !data.contains(&codes::IAC) {
+                    return data;
+                }
+                let mut out = BytesMut::with_capacity(data.len() + 8);
+                escape_iac(&data, &mut out);
+                out.freeze()
+            },
             TelnetEvent::Negotiate(comm, op) => {
                 let mut out = BytesMut::with_capacity(3);
                 out.extend(&[codes::IAC, comm, op]);
@@ -45,7 +77,7 @@ impl From<TelnetEvent> for Bytes {
             TelnetEvent::SubNegotiate(op, data) => {
                 let mut out = BytesMut::with_capacity(5 + data.len());
                 out.extend(&[codes::IAC, codes::SB, op]);
-                out.extend(data);
+                escape_iac(&data, &mut out);
                 out.extend(&[codes::IAC, codes::SE]);
                 out.freeze()
             },
@@ -92,27 +124,29 @@ impl TelnetEvent {
                         // Not enough bytes for sub-negotiation.
                         return None;
                     }
-                    let mut escape_next = false;
-                    if let Some(ipos) = src.as_ref().windows(2).enumerate().find_map(|(i, b)| {
-                        if escape_next {
-                            escape_next = false;
-                            return None;
-                        }
-
-                        if b[0] == codes::IAC {
-                            if b[1] == codes::SE {
-                                return Some(i);
-                            } else if b[1] == codes::IAC {
-                                escape_next = true;
+                    // Look for the closing IAC SE after the option byte, stepping over any
+                    // escaped IAC IAC pairs so they can't be mistaken for the terminator.
+                    let mut end = None;
+                    let mut i = 3;
+                    while i + 1 < src.len() {
+                        if src[i] == codes::IAC {
+                            if src[i + 1] == codes::SE {
+                                end = Some(i);
+                                break;
+                            } else if src[i + 1] == codes::IAC {
+                                i += 2;
+                                continue;
                             }
                         }
-                        None
-                    }) {
-                        // Split off any available up to an IAC and stuff it in the sub data buffer.
+                        i += 1;
+                    }
+
+                    if let Some(ipos) = end {
+                        // Split off everything up to the IAC SE and stuff it in the sub data buffer.
                         let mut data = src.split_to(ipos);
                         src.advance(2);
                         let discard = data.split_to(3);
-                        let answer = TelnetEvent::SubNegotiate(discard[2], data.freeze());
+                        let answer = TelnetEvent::SubNegotiate(discard[2], unescape_iac(&data));
                         Some(answer)
                     } else {
                         None
@@ -152,6 +186,15 @@ impl TelnetCodec {
             encoder: None
         }
     }
+
+    // Ends the MCCP2 stream, if one is running, and returns the final compressed bytes
+    // that must be written before the connection closes.
+    pub fn finish_compression(&mut self) -> io::Result<Bytes> {
+        match self.encoder.take() {
+            Some(encoder) => Ok(encoder.finish()?.into_inner().freeze()),
+            None => Ok(Bytes::new())
+        }
+    }
 }
 
 impl Encoder<TelnetEvent> for TelnetCodec {
@@ -2,6 +2,7 @@ pub const NULL: u8 = 0;
 pub const BEL: u8 = 7;
 pub const CR: u8 = 13;
 pub const LF: u8 = 10;
+pub const ECHO: u8 = 1;
 pub const SGA: u8 = 3;
 pub const TELOPT_EOR: u8 = 25;
 pub const NAWS: u8 = 31;
@@ -22,6 +23,17 @@ pub const IAC: u8 = 255;
 // MNES: Mud New-Environ standard
 pub const MNES: u8 = 39;
 
+// NEW-ENVIRON sub-negotiation commands (RFC 1572).
+pub const MNES_IS: u8 = 0;
+pub const MNES_SEND: u8 = 1;
+pub const MNES_INFO: u8 = 2;
+
+// NEW-ENVIRON variable type codes (RFC 1572).
+pub const MNES_VAR: u8 = 0;
+pub const MNES_VALUE: u8 = 1;
+pub const MNES_ESC: u8 = 2;
+pub const MNES_USERVAR: u8 = 3;
+
 // MUD eXtension Protocol
 // NOTE: Disabled due to too many issues with it.
 pub const MXP: u8 = 91;
@@ -41,5 +53,13 @@ pub const GMCP: u8 = 201;
 // MSDP - Mud Server Data Protocol
 pub const MSDP: u8 = 69;
 
+// MSDP sub-negotiation markers.
+pub const MSDP_VAR: u8 = 1;
+pub const MSDP_VAL: u8 = 2;
+pub const MSDP_TABLE_OPEN: u8 = 3;
+pub const MSDP_TABLE_CLOSE: u8 = 4;
+pub const MSDP_ARRAY_OPEN: u8 = 5;
+pub const MSDP_ARRAY_CLOSE: u8 = 6;
+
 // MTTS - Terminal Type
 pub const MTTS: u8 = 24;
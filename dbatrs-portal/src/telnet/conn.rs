@@ -1,5 +1,5 @@
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{HashMap, HashSet, VecDeque},
     net::SocketAddr,
     time::{Duration, Instant},
     vec::Vec,
@@ -7,12 +7,13 @@ use std::{
 };
 
 use tokio::{
-    io::{AsyncRead, AsyncWrite},
+    io::{AsyncRead, AsyncWrite, AsyncWriteExt, ReadHalf, WriteHalf},
     sync::mpsc,
+    task::JoinHandle,
     time
 };
 
-use tokio_util::codec::Framed;
+use tokio_util::codec::{FramedRead, FramedWrite};
 
 use tokio_stream::wrappers::IntervalStream;
 
@@ -27,25 +28,33 @@ use bitflags::bitflags;
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
 
+use tracing::warn;
+
+use chrono::Utc;
+
 use lazy_regex::regex;
 
-use surrealdb::{Notification, RecordId};
+use surrealdb::RecordId;
 use surrealdb::opt::auth::{Jwt, Record};
-use surrealdb::engine::remote::ws::{Ws, Wss, Client};
+use surrealdb::engine::any::Any;
 use surrealdb::Surreal;
 use dbatrs_shared::{
     TotalConf,
     ProtocolCapabilities,
     Color,
     Conn,
-    ConnOutput,
-    Credentials
+    Credentials,
+    protocol::{PagerControl, ToGame}
 };
 
 use crate::{
     telnet::{
         codes as tc,
         codec::{TelnetCodec, TelnetEvent},
+        editor::LineEditor,
+        output::{OutputQueue, OutputError},
+        listen::Msg2Listener,
+        registry::LoginState,
     }
 };
 
@@ -76,6 +85,8 @@ static TELNET_OPTIONS: LazyLock<HashMap<u8, TelnetOption>> = LazyLock::new( || {
     let mut map: HashMap<u8, TelnetOption> = HashMap::new();
 
     map.insert(tc::SGA, TelnetOption::ALLOW_LOCAL | TelnetOption::START_LOCAL);
+    // Only offered once LINEMODE is refused or unanswered, see offer_echo.
+    map.insert(tc::ECHO, TelnetOption::ALLOW_LOCAL);
     map.insert(tc::NAWS, TelnetOption::ALLOW_REMOTE | TelnetOption::START_REMOTE);
     map.insert(tc::MTTS, TelnetOption::ALLOW_REMOTE | TelnetOption::START_REMOTE);
     map.insert(tc::MSSP, TelnetOption::ALLOW_LOCAL | TelnetOption::START_LOCAL);
@@ -85,19 +96,31 @@ static TELNET_OPTIONS: LazyLock<HashMap<u8, TelnetOption>> = LazyLock::new( || {
     map.insert(tc::MSDP, TelnetOption::ALLOW_LOCAL | TelnetOption::START_LOCAL);
     map.insert(tc::LINEMODE, TelnetOption::ALLOW_REMOTE | TelnetOption::START_REMOTE);
     map.insert(tc::TELOPT_EOR, TelnetOption::ALLOW_LOCAL | TelnetOption::START_LOCAL);
+    map.insert(tc::MNES, TelnetOption::ALLOW_REMOTE | TelnetOption::START_REMOTE);
     map
 });
 
+// The MNES variables we request from the client once NEW-ENVIRON is enabled.
+const MNES_VARIABLES: [&str; 6] = [
+    "CLIENT_NAME",
+    "CLIENT_VERSION",
+    "CHARSET",
+    "MTTS",
+    "TERMINAL_TYPE",
+    "IPADDRESS"
+];
+
 #[derive(Default, Debug, Clone)]
 pub struct TelnetHandshakes {
     pub local: HashSet<u8>,
     pub remote: HashSet<u8>,
-    pub ttype: HashSet<u8>
+    pub ttype: HashSet<u8>,
+    pub mnes: HashSet<u8>
 }
 
 impl TelnetHandshakes {
     pub fn len(&self) -> usize {
-        self.local.len() + self.remote.len() + self.ttype.len()
+        self.local.len() + self.remote.len() + self.ttype.len() + self.mnes.len()
     }
 
     pub fn is_empty(&self) -> bool {
@@ -120,10 +143,82 @@ impl Default for TelnetTimers {
     }
 }
 
+// Appends one MSDP value, nesting arrays and tables as the protocol describes.
+fn encode_msdp_value(out: &mut BytesMut, value: &JsonValue) {
+    match value {
+        JsonValue::Null => {},
+        JsonValue::String(s) => out.put_slice(s.as_bytes()),
+        JsonValue::Array(items) => {
+            out.put_u8(tc::MSDP_ARRAY_OPEN);
+            for item in items {
+                out.put_u8(tc::MSDP_VAL);
+                encode_msdp_value(out, item);
+            }
+            out.put_u8(tc::MSDP_ARRAY_CLOSE);
+        },
+        JsonValue::Object(map) => {
+            out.put_u8(tc::MSDP_TABLE_OPEN);
+            for (k, v) in map {
+                out.put_u8(tc::MSDP_VAR);
+                out.put_slice(k.as_bytes());
+                out.put_u8(tc::MSDP_VAL);
+                encode_msdp_value(out, v);
+            }
+            out.put_u8(tc::MSDP_TABLE_CLOSE);
+        },
+        other => out.put_slice(other.to_string().as_bytes())
+    }
+}
+
+// Splits an MNES IS or INFO payload, minus its command byte, into name/value pairs. The
+// payload is a sequence of VAR|USERVAR <name> [VALUE <value>] entries, and ESC makes the
+// next byte literal, even if it's one of the type codes.
+pub fn parse_mnes_variables(data: &[u8]) -> Vec<(String, String)> {
+    let mut out: Vec<(Vec<u8>, Vec<u8>)> = Vec::new();
+    let mut in_value = false;
+    let mut escaped = false;
+
+    for byte in data.iter() {
+        if escaped {
+            escaped = false;
+        } else {
+            match *byte {
+                tc::MNES_ESC => {
+                    escaped = true;
+                    continue;
+                },
+                tc::MNES_VAR | tc::MNES_USERVAR => {
+                    out.push((Vec::new(), Vec::new()));
+                    in_value = false;
+                    continue;
+                },
+                tc::MNES_VALUE => {
+                    in_value = true;
+                    continue;
+                },
+                _ => {}
+            }
+        }
+
+        if let Some((name, value)) = out.last_mut() {
+            if in_value {
+                value.push(*byte);
+            } else {
+                name.push(*byte);
+            }
+        }
+    }
+
+    out.into_iter()
+        .map(|(name, value)| (String::from_utf8_lossy(&name).trim().to_uppercase(),
+                              String::from_utf8_lossy(&value).trim().to_string()))
+        .filter(|(name, _)| !name.is_empty())
+        .collect()
+}
+
 fn ensure_crlf(input: &str) -> String {
     let mut result = String::with_capacity(input.len());
     let mut prev_char_is_cr = false;
-    let iac = char::from(255);
 
     for c in input.chars() {
         match c {
@@ -140,12 +235,6 @@ fn ensure_crlf(input: &str) -> String {
                 result.push(c);
                 prev_char_is_cr = false;
             },
-            x if x == iac => {
-                // This is a telnet IAC character, so we need to escape it.
-                result.push(x);
-                result.push(x);
-                prev_char_is_cr = false;
-            },
             _ => {
                 result.push(c);
                 prev_char_is_cr = false;
@@ -159,7 +248,11 @@ fn ensure_crlf(input: &str) -> String {
 pub enum Msg2TelnetProtocol {
     GameClose,
     GMCP(String, JsonValue),
+    MSDP(Vec<(String, JsonValue)>),
     Text(String),
+    Prompt(String),
+    Echo(bool),
+    Pager(PagerControl),
     MSSP(Vec<(String, String)>),
 }
 
@@ -167,33 +260,68 @@ pub struct TelnetProtocol<T> {
     // This serves as a higher-level actor that abstracts a bunch of the lower-level
     // nitty-gritty so the Session doesn't need to deal with it.
     conf: Arc<TotalConf>,
+    id: usize,
+    tx_listener: mpsc::Sender<Msg2Listener>,
+    rx_protocol: mpsc::UnboundedReceiver<Msg2TelnetProtocol>,
+    tx_game: mpsc::UnboundedSender<ToGame>,
     op_state: HashMap<u8, TelnetOptionState>,
     config: ProtocolCapabilities,
     handshakes_left: TelnetHandshakes,
     ttype_count: u8,
     ttype_last: Option<String>,
-    conn: Framed<T, TelnetCodec>,
+    reader: FramedRead<ReadHalf<T>, TelnetCodec>,
+    writer: Option<FramedWrite<WriteHalf<T>, TelnetCodec>>,
+    output: OutputQueue,
     active: bool,
     running: bool,
     app_buffer: BytesMut,
+    editor: LineEditor,
     time_created: Instant,
     time_activity: Instant,
     timers: TelnetTimers,
-    game: Surreal<Client>,
+    game: Surreal<Any>,
+    // Set when running against an embedded database. It has a single session shared by
+    // every connection, so logins are checked by query instead of record access.
+    shared_db: Option<Surreal<Any>>,
     authenticated: bool,
     jwt: Option<Jwt>,
-    conn_sess: Option<RecordId>
+    // The logged-in user.
+    user: Option<RecordId>,
+    conn_sess: Option<RecordId>,
+    // The listener is behind a proxy, so an IPADDRESS sent in MNES is the client's real
+    // address. What the client itself claims in MTTS has no say in this.
+    proxied: bool,
+    // Sent once, before negotiation starts.
+    banner: Option<String>,
+    // Set while the game has asked for input not to be echoed, e.g. for a password.
+    hide_input: bool,
+    // True if we offered ECHO only to hide input, so it should be withdrawn afterwards.
+    echo_hiding: bool,
+    // Lines of paged text not yet shown.
+    pager: VecDeque<String>
 }
 
 
 impl<T> TelnetProtocol<T> where T: AsyncRead + AsyncWrite + Send + 'static + Unpin + Sync {
-    pub fn new(conf: Arc<TotalConf>, conn: T, addr: SocketAddr, hostnames: Vec<String>, tls: bool) -> Self {
+    pub fn new(conf: Arc<TotalConf>, id: usize, conn: T, addr: SocketAddr, hostnames: Vec<String>, tls: bool, proxied: bool,
+               banner: Option<String>, tx_listener: mpsc::Sender<Msg2Listener>, rx_protocol: mpsc::UnboundedReceiver<Msg2TelnetProtocol>,
+               shared_db: Option<Surreal<Any>>, tx_game: mpsc::UnboundedSender<ToGame>) -> Self {
+
+        let (read_half, write_half) = tokio::io::split(conn);
+        let output = OutputQueue::new(conf.portal.max_output_buffer);
 
         let mut out = Self {
             conf,
-            conn: Framed::new(conn, TelnetCodec::new(8192)),
+            id,
+            tx_listener,
+            rx_protocol,
+            tx_game,
+            reader: FramedRead::new(read_half, TelnetCodec::new(8192)),
+            writer: Some(FramedWrite::new(write_half, TelnetCodec::new(8192))),
+            output,
             running: true,
             app_buffer: BytesMut::with_capacity(1024),
+            editor: LineEditor::new(),
             time_created: Instant::now(),
             time_activity: Instant::now(),
             timers: TelnetTimers::default(),
@@ -204,9 +332,16 @@ impl<T> TelnetProtocol<T> where T: AsyncRead + AsyncWrite + Send + 'static + Unp
             config: ProtocolCapabilities::with_custom_defaults(),
             handshakes_left: Default::default(),
             game: Surreal::init(),
+            shared_db,
             authenticated: false,
             jwt: None,
-            conn_sess: None
+            user: None,
+            conn_sess: None,
+            proxied,
+            banner,
+            hide_input: false,
+            echo_hiding: false,
+            pager: VecDeque::new()
         };
         // Stack overflow before reaching this point.
         out.config.tls = tls;
@@ -216,6 +351,10 @@ impl<T> TelnetProtocol<T> where T: AsyncRead + AsyncWrite + Send + 'static + Unp
         out
     }
 
+    pub fn capabilities(&self) -> &ProtocolCapabilities {
+        &self.config
+    }
+
     async fn handle_conn(&mut self, t_msg: Option<Result<TelnetEvent, std::io::Error>>) {
         if let Some(msg) = t_msg {
             self.time_activity = Instant::now();
@@ -233,9 +372,27 @@ impl<T> TelnetProtocol<T> where T: AsyncRead + AsyncWrite + Send + 'static + Unp
     }
 
     async fn send(&mut self, te: TelnetEvent) -> bool {
-        match self.conn.send(te).await {
+        let result = self.output.push(te);
+        self.check_output(result)
+    }
+
+    async fn send_low_priority(&mut self, te: TelnetEvent, key: String) -> bool {
+        let result = self.output.push_low_priority(te, key);
+        self.check_output(result)
+    }
+
+    fn check_output(&mut self, result: Result<(), OutputError>) -> bool {
+        match result {
             Ok(_) => true,
-            Err(e) => {
+            Err(OutputError::Overflow(backlog)) => {
+                warn!("Dropping client {}:{} ({}): output backlog of {} bytes exceeds the limit of {}",
+                    self.config.host_address, self.config.host_port, self.config.client_name,
+                    backlog, self.conf.portal.max_output_buffer);
+                self.output.close();
+                self.running = false;
+                false
+            },
+            Err(OutputError::Closed) => {
                 self.running = false;
                 //let _ = self.tx_portal.send(Msg2Portal::ClientDisconnected(self.conn_id, String::from(e.to_string()))).await;
                 false
@@ -244,6 +401,19 @@ impl<T> TelnetProtocol<T> where T: AsyncRead + AsyncWrite + Send + 'static + Unp
     }
 
     pub async fn run(&mut self) {
+        // The writer gets its own task so a client that stops reading only grows its
+        // backlog instead of blocking this loop.
+        let mut writer_task: JoinHandle<_> = match self.writer.take() {
+            Some(writer) => tokio::spawn(self.output.clone().run_writer(writer)),
+            None => return
+        };
+
+        if let Some(mut banner) = self.banner.take() {
+            if !banner.ends_with('\n') {
+                banner.push('\n');
+            }
+            self.send(TelnetEvent::Data(Bytes::from(ensure_crlf(&banner)))).await;
+        }
 
         // Initialize Telnet Op handlers.
         for (code, tel_op) in TELNET_OPTIONS.iter() {
@@ -265,19 +435,31 @@ impl<T> TelnetProtocol<T> where T: AsyncRead + AsyncWrite + Send + 'static + Unp
 
         let mut interval_timer = IntervalStream::new(time::interval(Duration::from_millis(100)));
 
+        let mut writer_result = None;
         let mut in_negotiation_phase = true;
         let negotiation_deadline = time::Instant::now() + Duration::from_millis(500);
 
         // The main loop which operates the protocol during and after negotiation.
         while self.running {
             tokio::select! {
-                t_msg = self.conn.next() => self.handle_conn(t_msg).await,
+                t_msg = self.reader.next() => self.handle_conn(t_msg).await,
+
+                Some(p_msg) = self.rx_protocol.recv() => self.process_protocol_message(p_msg).await,
+
+                result = &mut writer_task => {
+                    // The writer only stops on its own if the socket failed.
+                    writer_result = Some(result);
+                    self.running = false;
+                }
 
                 Some(i_msg) = interval_timer.next() => {
                     let _ = self.handle_interval_timer(i_msg.into_std()).await;
                 }
                 _ = time::sleep_until(negotiation_deadline), if in_negotiation_phase => {
                     in_negotiation_phase = false;
+                    if self.handshakes_left.remote.contains(&tc::LINEMODE) {
+                        self.offer_echo().await;
+                    }
                 }
         }
 
@@ -292,6 +474,8 @@ impl<T> TelnetProtocol<T> where T: AsyncRead + AsyncWrite + Send + 'static + Unp
                     Ok(_) => {
                         self.active = true;
                         self.authenticated = false;
+                        let _ = self.tx_listener.send(Msg2Listener::ClientLogin(self.id, LoginState::Connected)).await;
+                        let _ = self.update_capabilities().await;
                         let _ = self.send(TelnetEvent::Data(Bytes::from("Connected to game server.\r\n"))).await;
                     },
                     Err(e) => {
@@ -300,30 +484,85 @@ impl<T> TelnetProtocol<T> where T: AsyncRead + AsyncWrite + Send + 'static + Unp
                 }
             }
         }
+
+        // Let the writer drain whatever is still queued, then close out the stream. A client
+        // that has stopped reading would keep it waiting forever, so it only gets so long.
+        self.output.close();
+        let writer_result = match writer_result {
+            Some(result) => Some(result),
+            None => {
+                let drain = Duration::from_secs(self.conf.portal.drain_timeout);
+                match time::timeout(drain, &mut writer_task).await {
+                    Ok(result) => Some(result),
+                    Err(_) => {
+                        warn!("Connection {} did not take its last {} bytes of output; cutting it off.",
+                            self.id, self.output.backlog());
+                        writer_task.abort();
+                        None
+                    }
+                }
+            }
+        };
+        if let Some(Ok(mut sink)) = writer_result {
+            if let Ok(tail) = sink.encoder_mut().finish_compression() {
+                if !tail.is_empty() {
+                    let _ = sink.get_mut().write_all(&tail).await;
+                }
+            }
+            let _ = sink.get_mut().shutdown().await;
+        }
+
+        let _ = self.remove_conn().await;
+        let _ = self.tx_listener.send(Msg2Listener::ClientDisconnected(self.id)).await;
+    }
+
+    // Deletes our conn record, and anything queued for it, so it isn't left behind.
+    async fn remove_conn(&mut self) {
+        if let Some(conn) = self.conn_sess.take() {
+            let _ = self.tx_game.send(ToGame::Disconnected { conn: conn.clone() });
+            let result = self.game.query("DELETE conn_input WHERE conn = $conn; \
+                                          DELETE conn_output WHERE conn = $conn; \
+                                          DELETE $conn;")
+                .bind(("conn", conn))
+                .await;
+            if let Err(e) = result {
+                warn!("Connection {} could not remove its conn record: {}", self.id, e);
+            }
+        }
     }
 
     async fn setup_surreal(&mut self) -> Result<(), surrealdb::Error> {
-        if self.conf.surreal.tls {
-            self.game.connect::<Wss>(&self.conf.surreal.address).await?;
-        } else {
-            self.game.connect::<Ws>(&self.conf.surreal.address).await?;
-        };
+        if let Some(db) = &self.shared_db {
+            self.game = db.clone();
+            return Ok(());
+        }
+
+        self.game.connect(dbatrs_shared::db::endpoint(&self.conf)).await?;
 
         self.game.use_ns(&self.conf.surreal.namespace).use_db(&self.conf.surreal.database).await?;
 
         Ok(())
     }
 
-    async fn handle_conn_output(&mut self, msg: Result<Notification<ConnOutput>, surrealdb::Error>) {
-        println!("Received notification: {:?}", msg);
-    }
-
     async fn handle_interval_timer(&mut self, ins: Instant) {
         // Check if the connection has been utterly idle at a network level for too long.
         if self.time_activity.elapsed().as_secs() > (60 * 30) {
             // handle disconnect here.
         }
 
+        // Keep our conn record fresh so the stale connection sweep leaves it alone.
+        if self.timers.last_keepalive.elapsed().as_secs() >= self.conf.portal.heartbeat_interval {
+            self.timers.last_keepalive = ins;
+            if let Some(conn) = self.conn_sess.clone() {
+                let result = self.game.query("UPDATE $conn SET time_system_activity = time::now()")
+                    .bind(("conn", conn))
+                    .await;
+                if let Err(e) = result {
+                    warn!("Connection {} heartbeat failed: {}", self.id, e);
+                }
+            }
+        }
+
         self.timers.last_interval = ins;
     }
 
@@ -350,7 +589,36 @@ impl<T> TelnetProtocol<T> where T: AsyncRead + AsyncWrite + Send + 'static + Unp
         }
     }
 
+    fn local_enabled(&self, op: u8) -> bool {
+        self.op_state.get(&op).map(|state| state.local.enabled).unwrap_or(false)
+    }
+
+    // A client that let us take over both echo and go-ahead, and isn't editing locally
+    // with LINEMODE, is sending us each keystroke as it's typed. ECHO claimed only to hide
+    // input doesn't count.
+    fn character_mode(&self) -> bool {
+        self.local_enabled(tc::ECHO) && self.local_enabled(tc::SGA) && !self.config.linemode && !self.echo_hiding
+    }
+
     async fn process_app_buffer(&mut self) {
+        if self.character_mode() {
+            // Run the keystrokes through the server-side editor, which does the echoing.
+            let data = self.app_buffer.split();
+            let output = self.editor.feed(&data);
+            if !output.echo.is_empty() && !self.hide_input {
+                let _ = self.send(TelnetEvent::Data(output.echo)).await;
+            }
+            for line in output.lines {
+                match line {
+                    Ok(line) => self.handle_user_command(line).await,
+                    Err(e) => {
+                        let _ = self.send(TelnetEvent::Data(Bytes::from(format!("{}\r\n", e)))).await;
+                    }
+                }
+            }
+            return;
+        }
+
         loop {
             // Find the position of the LF character
             if let Some(ipos) = self.app_buffer.as_ref().iter().position(|b| b == &b'\n') {
@@ -376,26 +644,84 @@ impl<T> TelnetProtocol<T> where T: AsyncRead + AsyncWrite + Send + 'static + Unp
     }
 
     async fn handle_init_conn(&mut self) -> Result<(), surrealdb::Error> {
-        let res: Option<Conn> = self.game.run("fn::create_conn()").await?;
+        let res: Option<Conn> = match (&self.shared_db, self.user.clone()) {
+            // There's no record session to take the user and address from, so pass them in.
+            (Some(_), Some(user)) => {
+                self.game.query("CREATE ONLY conn SET user = $user, ip = $ip, portal = $portal")
+                    .bind(("user", user))
+                    .bind(("ip", self.config.host_address.clone()))
+                    .bind(("portal", self.conf.portal.id.clone()))
+                    .await?
+                    .take(0)?
+            },
+            _ => self.game.run("fn::create_conn").args((self.conf.portal.id.clone(),)).await?
+        };
 
-        if res.is_some() {
-            self.conn_sess = Some(res.unwrap().id);
+        if let Some(conn) = res {
+            self.user = Some(conn.user.clone());
+            self.conn_sess = Some(conn.id.clone());
+            let _ = self.tx_listener.send(Msg2Listener::ClientSession(self.id, conn.id.clone(), conn.user.clone())).await;
+            let _ = self.tx_game.send(ToGame::Connected { conn: conn.id, user: conn.user });
         }
         Ok(())
     }
 
-    async fn handle_authenticate(&mut self, jwt: Jwt) {
+    async fn handle_authenticate(&mut self, jwt: Jwt, email: &str) {
         self.jwt = Some(jwt.clone());
         match self.game.authenticate(jwt).await {
-            Ok(_) => {
-                self.authenticated = true;
-            },
+            Ok(_) => self.handle_authenticated(email).await,
             Err(e) => {
                 let _ = self.send(TelnetEvent::Data(Bytes::from(format!("Failed to authenticate: {}\r\n", e)))).await;
             }
         }
     }
 
+    async fn handle_authenticated(&mut self, email: &str) {
+        self.authenticated = true;
+        let login = LoginState::Authenticated(email.to_string());
+        let _ = self.tx_listener.send(Msg2Listener::ClientLogin(self.id, login)).await;
+        if let Err(e) = self.handle_init_conn().await {
+            warn!("Connection {} could not create its conn record: {}", self.id, e);
+        }
+        let _ = self.update_capabilities().await;
+    }
+
+    // The shared_db equivalent of signup and signin, following the account access definition.
+    async fn handle_login_shared(&mut self, command: &str, email: &str, password: &str) {
+        let (query, success, failure) = match command {
+            "register" => ("CREATE ONLY user SET email = $email, password = $password RETURN VALUE id",
+                           "You have successfully registered.", "Failed to register"),
+            "login" => ("SELECT VALUE id FROM ONLY user WHERE email = string::lowercase(string::trim($email)) \
+                         AND !password_reset AND crypto::argon2::compare(password, string::trim($password)) LIMIT 1",
+                        "You have successfully logged in.", "Failed to login"),
+            _ => {
+                let _ = self.send(TelnetEvent::Data(Bytes::from("Invalid command.\r\n\
+                Choices are \"register <email>=<password>\" or \"login <email>=<password>\"\r\n"))).await;
+                return;
+            }
+        };
+
+        let result = self.game.query(query)
+            .bind(("email", email.to_string()))
+            .bind(("password", password.to_string()))
+            .await
+            .and_then(|mut response| response.take::<Option<RecordId>>(0));
+
+        match result {
+            Ok(Some(user)) => {
+                let _ = self.send(TelnetEvent::Data(Bytes::from(format!("{}\r\n", success)))).await;
+                self.user = Some(user);
+                self.handle_authenticated(email).await;
+            },
+            Ok(None) => {
+                let _ = self.send(TelnetEvent::Data(Bytes::from(format!("{}: There was a problem with authentication\r\n", failure)))).await;
+            },
+            Err(e) => {
+                let _ = self.send(TelnetEvent::Data(Bytes::from(format!("{}: {}\r\n", failure, e)))).await;
+            }
+        }
+    }
+
     async fn handle_login(&mut self, cmd: String) {
         // Adjust the regex if needed—here we assume passwords have no spaces.
         let re = regex!("^(\\w+)\\s+(\\S+)=(.+)$");
@@ -408,6 +734,11 @@ impl<T> TelnetProtocol<T> where T: AsyncRead + AsyncWrite + Send + 'static + Unp
             // Group 3: password
             let password = caps.get(3).unwrap().as_str();
 
+            if self.shared_db.is_some() {
+                self.handle_login_shared(&command, email, password).await;
+                return;
+            }
+
             let rec = Record {
                 namespace: &self.conf.surreal.namespace,
                 database: &self.conf.surreal.database,
@@ -424,7 +755,7 @@ impl<T> TelnetProtocol<T> where T: AsyncRead + AsyncWrite + Send + 'static + Unp
                     match self.game.signup(rec).await {
                         Ok(jwt) => {
                             let _ = self.send(TelnetEvent::Data(Bytes::from("You have successfully registered.\r\n"))).await;
-                            let _ = self.handle_authenticate(jwt).await;
+                            let _ = self.handle_authenticate(jwt, email).await;
                         },
                         Err(e) => {
                             let _ = self.send(TelnetEvent::Data(Bytes::from(format!("Failed to register: {}\r\n", e)))).await;
@@ -435,7 +766,7 @@ impl<T> TelnetProtocol<T> where T: AsyncRead + AsyncWrite + Send + 'static + Unp
                     match self.game.signin(rec).await {
                         Ok(jwt) => {
                             let _ = self.send(TelnetEvent::Data(Bytes::from("You have successfully logged in.\r\n"))).await;
-                            let _ = self.handle_authenticate(jwt).await;
+                            let _ = self.handle_authenticate(jwt, email).await;
                         },
                         Err(e) => {
                             let _ = self.send(TelnetEvent::Data(Bytes::from(format!("Failed to login: {}\r\n", e)))).await;
@@ -456,11 +787,22 @@ impl<T> TelnetProtocol<T> where T: AsyncRead + AsyncWrite + Send + 'static + Unp
     }
 
     async fn handle_user_command(&mut self, cmd: String) {
+        // Only a courtesy for the registry, so it's not worth waiting on a busy listener for.
+        let _ = self.tx_listener.try_send(Msg2Listener::ClientActivity(self.id, Utc::now()));
         if cmd.starts_with("//") {
             let _ = self.handle_protocol_command(cmd);
+        } else if !self.pager.is_empty() {
+            // While paging, input only moves through the text.
+            if cmd.trim().eq_ignore_ascii_case("q") {
+                self.pager.clear();
+            } else {
+                self.show_page().await;
+            }
         } else if self.active {
             if self.authenticated {
-                // we are authenticated
+                if let Some(conn) = self.conn_sess.clone() {
+                    let _ = self.tx_game.send(ToGame::Input { conn, command: cmd });
+                }
             } else {
                 // We are not authenticated, so we need to handle the signup/signin process.
                 let _ = self.handle_login(cmd).await;
@@ -479,15 +821,47 @@ impl<T> TelnetProtocol<T> where T: AsyncRead + AsyncWrite + Send + 'static + Unp
                 self.running = false;
             },
             Msg2TelnetProtocol::GMCP(v, j) => {
+                let low_priority = self.conf.portal.low_priority_gmcp.contains(&v);
                 let mut gmcp_data = Vec::new();
-                gmcp_data.push(v);
+                gmcp_data.push(v.clone());
                 gmcp_data.push(j.to_string());
                 let gmcp_out = gmcp_data.join(" ");
-                let _ = self.send(TelnetEvent::SubNegotiate(tc::GMCP, Bytes::from(gmcp_out))).await;
+                let event = TelnetEvent::SubNegotiate(tc::GMCP, Bytes::from(gmcp_out));
+                if low_priority {
+                    let _ = self.send_low_priority(event, v).await;
+                } else {
+                    let _ = self.send(event).await;
+                }
+            },
+            Msg2TelnetProtocol::MSDP(variables) => {
+                if !self.local_enabled(tc::MSDP) {
+                    return;
+                }
+                let mut out = BytesMut::new();
+                for (name, value) in variables {
+                    out.put_u8(tc::MSDP_VAR);
+                    out.put_slice(name.as_bytes());
+                    out.put_u8(tc::MSDP_VAL);
+                    encode_msdp_value(&mut out, &value);
+                }
+                let _ = self.send(TelnetEvent::SubNegotiate(tc::MSDP, out.freeze())).await;
             },
             Msg2TelnetProtocol::Text(t) => {
                 let _ = self.send(TelnetEvent::Data(Bytes::from(ensure_crlf(&t)))).await;
             },
+            Msg2TelnetProtocol::Prompt(t) => {
+                let _ = self.send_prompt(&t).await;
+            },
+            Msg2TelnetProtocol::Echo(enabled) => {
+                let _ = self.set_echo(enabled).await;
+            },
+            Msg2TelnetProtocol::Pager(PagerControl::Show { text }) => {
+                self.pager = text.lines().map(String::from).collect();
+                self.show_page().await;
+            },
+            Msg2TelnetProtocol::Pager(PagerControl::Clear) => {
+                self.pager.clear();
+            },
             Msg2TelnetProtocol::MSSP(v) => {
                 // this message should come from the DbManager, and it needs to be forwarded to the client.
                 let mut mssp_data = Vec::new();
@@ -500,6 +874,67 @@ impl<T> TelnetProtocol<T> where T: AsyncRead + AsyncWrite + Send + 'static + Unp
         }
     }
 
+    // Sends text that the client should show without waiting for a newline, marked with
+    // EOR if the client agreed to it, or GA unless we've suppressed go-aheads.
+    async fn send_prompt(&mut self, text: &str) {
+        let _ = self.send(TelnetEvent::Data(Bytes::from(ensure_crlf(text)))).await;
+        if self.local_enabled(tc::TELOPT_EOR) {
+            let _ = self.send(TelnetEvent::Command(tc::EOR)).await;
+        } else if !self.config.sga {
+            let _ = self.send(TelnetEvent::Command(tc::GA)).await;
+        }
+    }
+
+    // A client that won't edit lines itself may let the server echo for it instead.
+    async fn offer_echo(&mut self) {
+        let Some(state) = self.op_state.get_mut(&tc::ECHO) else {
+            return;
+        };
+        if state.local.enabled || state.local.negotiating {
+            return;
+        }
+        state.local.negotiating = true;
+        self.handshakes_left.local.insert(tc::ECHO);
+        let _ = self.send(TelnetEvent::Negotiate(tc::WILL, tc::ECHO)).await;
+    }
+
+    // Clients stop echoing locally while the server claims WILL ECHO, so hiding input means
+    // offering ECHO and then not echoing anything.
+    async fn set_echo(&mut self, enabled: bool) {
+        self.hide_input = !enabled;
+        let Some(state) = self.op_state.get_mut(&tc::ECHO) else {
+            return;
+        };
+        if !enabled && !state.local.enabled && !state.local.negotiating {
+            state.local.negotiating = true;
+            self.echo_hiding = true;
+            let _ = self.send(TelnetEvent::Negotiate(tc::WILL, tc::ECHO)).await;
+        } else if enabled && self.echo_hiding {
+            // The client may have refused, in which case there's nothing to withdraw.
+            let was_enabled = state.local.enabled;
+            state.local.enabled = false;
+            state.local.negotiating = false;
+            self.echo_hiding = false;
+            if was_enabled {
+                let _ = self.send(TelnetEvent::Negotiate(tc::WONT, tc::ECHO)).await;
+            }
+        }
+    }
+
+    // Shows the next screenful of paged text, leaving room for the prompt.
+    async fn show_page(&mut self) {
+        let lines = (self.config.height as usize).saturating_sub(2).max(1);
+        let count = lines.min(self.pager.len());
+        let page: Vec<String> = self.pager.drain(..count).collect();
+        let mut text = page.join("\n");
+        text.push('\n');
+        let _ = self.send(TelnetEvent::Data(Bytes::from(ensure_crlf(&text)))).await;
+        if !self.pager.is_empty() {
+            let remaining = self.pager.len();
+            let _ = self.send_prompt(&format!("[{} more lines: Enter to continue, q to stop] ", remaining)).await;
+        }
+    }
+
     async fn receive_negotiate(&mut self, command: u8, op: u8) {
         // This means we received an IAC will/wont/do/dont...
         let mut handshake: u8 = 0;
@@ -607,16 +1042,26 @@ impl<T> TelnetProtocol<T> where T: AsyncRead + AsyncWrite + Send + 'static + Unp
         if disable_remote {
             let _ = self.disable_remote(op).await;
         }
+        if op == tc::LINEMODE && command == tc::WONT && handshake_remote == op {
+            self.offer_echo().await;
+        }
     }
 
     async fn enable_remote(&mut self, op: u8) {
         match op {
             tc::NAWS => self.config.naws = true,
             tc::MTTS => {
-                self.handshakes_left.ttype.insert(0);
+                self.config.ttype = true;
+                // Client name, terminal type and MTTS bitvector, in that order.
+                self.handshakes_left.ttype.extend([0, 1, 2]);
                 self.request_ttype().await;
             },
             tc::LINEMODE => self.config.linemode = true,
+            tc::MNES => {
+                self.config.mnes = true;
+                self.handshakes_left.mnes.insert(tc::MNES_IS);
+                self.request_mnes().await;
+            },
             _ => {
                 // Whatever this option is.. well, whatever.
             }
@@ -635,6 +1080,10 @@ impl<T> TelnetProtocol<T> where T: AsyncRead + AsyncWrite + Send + 'static + Unp
                 self.handshakes_left.ttype.clear();
             },
             tc::LINEMODE => self.config.linemode = false,
+            tc::MNES => {
+                self.config.mnes = false;
+                self.handshakes_left.mnes.clear();
+            },
             _ => {
                 // Whatever this option is.. well, whatever.
             }
@@ -684,18 +1133,22 @@ impl<T> TelnetProtocol<T> where T: AsyncRead + AsyncWrite + Send + 'static + Unp
             tc::MTTS => {
                 let _ = self.receive_ttype(data).await;
             },
+            tc::MNES => {
+                let _ = self.receive_mnes(data).await;
+            },
             tc::GMCP => {
                 if let Ok(s) = String::from_utf8(data.to_vec()) {
-                    if s.contains(" ") {
-                        let mut parts = s.splitn(2, " ");
-                        if let Some(cmd) = parts.next() {
-                            if let Some(data) = parts.next() {
-
-                            }
-                        }
+                    let (package, data) = match s.split_once(' ') {
+                        Some((package, data)) => (package, serde_json::from_str(data).unwrap_or(JsonValue::Null)),
+                        None => (s.as_str(), JsonValue::Null)
+                    };
+                    let before = self.config.clone();
+                    self.receive_gmcp_core(package, &data);
+                    if self.config != before {
+                        let _ = self.update_capabilities().await;
                     }
-                    else {
-
+                    if let Some(conn) = self.conn_sess.clone() {
+                        let _ = self.tx_game.send(ToGame::Gmcp { conn, package: package.to_string(), data });
                     }
                 }
             },
@@ -703,6 +1156,40 @@ impl<T> TelnetProtocol<T> where T: AsyncRead + AsyncWrite + Send + 'static + Unp
         }
     }
 
+    // Core.Hello and Core.Supports.* tell us about the client; everything else is the game's.
+    fn receive_gmcp_core(&mut self, package: &str, data: &JsonValue) {
+        let names = || data.as_array().into_iter().flatten().filter_map(|v| v.as_str()).map(|s| s.trim().to_string());
+        // Packages are matched by name alone, so a new version replaces the old one.
+        let name_of = |entry: &str| entry.split_whitespace().next().unwrap_or_default().to_lowercase();
+
+        match package.to_lowercase().as_str() {
+            "core.hello" => {
+                if let Some(client) = data.get("client").and_then(|v| v.as_str()) {
+                    self.config.client_name = client.trim().to_uppercase();
+                    self.deduce_client_capabilities();
+                }
+                if let Some(version) = data.get("version").and_then(|v| v.as_str()) {
+                    self.config.client_version = version.trim().to_string();
+                }
+            },
+            "core.supports.set" => {
+                self.config.gmcp_packages = names().collect();
+            },
+            "core.supports.add" => {
+                for entry in names() {
+                    self.config.gmcp_packages.retain(|p| name_of(p) != name_of(&entry));
+                    self.config.gmcp_packages.push(entry);
+                }
+            },
+            "core.supports.remove" => {
+                for entry in names() {
+                    self.config.gmcp_packages.retain(|p| name_of(p) != name_of(&entry));
+                }
+            },
+            _ => {}
+        }
+    }
+
     async fn request_ttype(&mut self) {
         let mut data = BytesMut::with_capacity(1);
         data.put_u8(1);
@@ -749,6 +1236,8 @@ impl<T> TelnetProtocol<T> where T: AsyncRead + AsyncWrite + Send + 'static + Unp
                                 1 => {
                                     let _ = self.receive_ttype_1(upper.clone()).await;
                                     self.ttype_last = Some(upper.clone());
+                                    self.ttype_count += 1;
+                                    let _ = self.request_ttype().await;
                                 },
                                 2 => {
                                     let _ = self.receive_ttype_2(upper.clone()).await;
@@ -785,6 +1274,11 @@ impl<T> TelnetProtocol<T> where T: AsyncRead + AsyncWrite + Send + 'static + Unp
             self.config.client_name = data;
         }
 
+        self.deduce_client_capabilities();
+        self.update_capabilities().await;
+    }
+
+    fn deduce_client_capabilities(&mut self) {
         // Now that the name and version (may be UNKNOWN) are set... we can deduce capabilities.
         let mut extra_check = false;
         match self.config.client_name.as_str() {
@@ -803,10 +1297,15 @@ impl<T> TelnetProtocol<T> where T: AsyncRead + AsyncWrite + Send + 'static + Unp
     }
 
     async fn receive_ttype_1(&mut self, data: String) {
+        self.apply_terminal_type(&data);
+        self.handshakes_left.ttype.remove(&1);
+        self.update_capabilities().await;
+    }
+
+    fn apply_terminal_type(&mut self, data: &str) {
         if (data.starts_with("XTERM") || data.ends_with("-256COLOR")) && self.config.ansi_color != Color::TrueColor  {
             self.config.ansi_color = Color::Xterm256;
         }
-        self.handshakes_left.ttype.remove(&1);
     }
 
     async fn receive_ttype_2(&mut self, data: String) {
@@ -819,6 +1318,12 @@ impl<T> TelnetProtocol<T> where T: AsyncRead + AsyncWrite + Send + 'static + Unp
         if mtts == 0 {
             return;
         }
+        self.apply_mtts(mtts);
+        self.handshakes_left.ttype.remove(&2);
+        self.update_capabilities().await;
+    }
+
+    fn apply_mtts(&mut self, mtts: usize) {
         if (1 & mtts) == 1 && (self.config.ansi_color.clone() as i32) < Color::Standard as i32 {
             self.config.ansi_color = Color::Standard;
         }
@@ -850,7 +1355,76 @@ impl<T> TelnetProtocol<T> where T: AsyncRead + AsyncWrite + Send + 'static + Unp
         if (512 & mtts) == 512 {
             self.config.mnes = true;
         }
-        self.handshakes_left.ttype.remove(&2);
+    }
+
+    async fn request_mnes(&mut self) {
+        let mut data = BytesMut::with_capacity(64);
+        data.put_u8(tc::MNES_SEND);
+        for var in MNES_VARIABLES.iter() {
+            data.put_u8(tc::MNES_VAR);
+            data.put(var.as_bytes());
+        }
+        self.send(TelnetEvent::SubNegotiate(tc::MNES, data.freeze())).await;
+    }
+
+    async fn receive_mnes(&mut self, data: Bytes) {
+        if data.is_empty() {
+            return;
+        }
+
+        let command = data[0];
+        if command != tc::MNES_IS && command != tc::MNES_INFO {
+            // We never answer SEND; the server has no environment to share.
+            return;
+        }
+
+        let before = self.config.clone();
+
+        for (name, value) in parse_mnes_variables(&data[1..]) {
+            if value.is_empty() {
+                continue;
+            }
+            match name.as_str() {
+                "CLIENT_NAME" => {
+                    self.config.client_name = value.to_uppercase();
+                    self.deduce_client_capabilities();
+                },
+                "CLIENT_VERSION" => {
+                    self.config.client_version = value;
+                },
+                "CHARSET" => {
+                    self.config.utf8 = value.eq_ignore_ascii_case("UTF-8");
+                    self.config.encoding = value;
+                },
+                "MTTS" => {
+                    if let Ok(mtts) = value.parse::<usize>() {
+                        self.apply_mtts(mtts);
+                    }
+                },
+                "TERMINAL_TYPE" => {
+                    self.apply_terminal_type(&value.to_uppercase());
+                },
+                "IPADDRESS" => {
+                    // Only a proxy is in a position to tell us the client's real address.
+                    if self.proxied {
+                        self.config.host_address = value;
+                    }
+                },
+                _ => {}
+            }
+        }
+
+        if command == tc::MNES_IS {
+            // MNES answers everything the TTYPE cycle would have told us, so there's
+            // no reason to keep waiting on it.
+            self.handshakes_left.mnes.clear();
+            self.handshakes_left.ttype.clear();
+            self.ttype_last = None;
+        }
+
+        if self.config != before {
+            let _ = self.update_capabilities().await;
+        }
     }
 
     async fn receive_naws(&mut self, mut data: Bytes) {
@@ -867,8 +1441,19 @@ impl<T> TelnetProtocol<T> where T: AsyncRead + AsyncWrite + Send + 'static + Unp
     }
 
     async fn update_capabilities(&mut self) {
+        let _ = self.tx_listener.send(Msg2Listener::ClientCapabilities(self.id, self.config.clone())).await;
         if self.active {
-
+            // The game watches the conn record to decide how to format output for us.
+            if let Some(conn) = self.conn_sess.clone() {
+                let result = self.game.query("UPDATE $conn SET capabilities = $capabilities")
+                    .bind(("conn", conn.clone()))
+                    .bind(("capabilities", self.config.clone()))
+                    .await;
+                if let Err(e) = result {
+                    warn!("Connection {} could not update its capabilities: {}", self.id, e);
+                }
+                let _ = self.tx_game.send(ToGame::Capabilities { conn, capabilities: self.config.clone() });
+            }
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,243 @@
+use std::{collections::VecDeque, fmt};
+
+use bytes::{BufMut, Bytes, BytesMut};
+
+// How many previous lines a connection can recall with the up arrow.
+const HISTORY_SIZE: usize = 20;
+
+// A line can't grow past this; a longer one is thrown away and reported as LineTooLong.
+pub const MAX_LINE_LENGTH: usize = 4096;
+
+const BS: u8 = 8;
+const TAB: u8 = 9;
+const DEL: u8 = 127;
+const ESC: u8 = 27;
+const CTRL_U: u8 = 21;
+const CTRL_W: u8 = 23;
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+enum EscapeState {
+    #[default]
+    None,
+    // We have seen ESC and are waiting to see what kind of sequence it starts.
+    Escape,
+    // We are inside an ESC [ (CSI) or ESC O (SS3) sequence, waiting for its final byte.
+    Sequence,
+}
+
+// A line that ran past MAX_LINE_LENGTH. Displays as what the user is told.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineTooLong;
+
+impl fmt::Display for LineTooLong {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Line too long (over {} characters); it was discarded.", MAX_LINE_LENGTH)
+    }
+}
+
+impl std::error::Error for LineTooLong {}
+
+// The result of feeding bytes through the LineEditor.
+#[derive(Default, Debug)]
+pub struct EditorOutput {
+    // Completed lines, in the order they were entered.
+    pub lines: Vec<Result<String, LineTooLong>>,
+    // What should be echoed back to the client if the server is responsible for echo.
+    pub echo: Bytes,
+}
+
+// A small server-side line editor for clients in character mode, which send us
+// every keystroke instead of completed lines. It understands backspace/DEL, Ctrl-W
+// (delete word), Ctrl-U (kill line) and up/down arrow history recall. Tabs are kept;
+// other control characters are dropped.
+#[derive(Default, Debug)]
+pub struct LineEditor {
+    line: Vec<u8>,
+    history: VecDeque<String>,
+    // Index into history while recalling, 0 being the most recent entry.
+    history_pos: Option<usize>,
+    // Whatever the user had typed before they started scrolling through history.
+    draft: Vec<u8>,
+    escape: EscapeState,
+    last_cr: bool,
+    // Set once the line has run past MAX_LINE_LENGTH; the rest of it is ignored.
+    overflowed: bool,
+}
+
+impl LineEditor {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn feed(&mut self, data: &[u8]) -> EditorOutput {
+        let mut lines = Vec::new();
+        let mut echo = BytesMut::new();
+
+        for byte in data.iter().copied() {
+            // A CR may be followed by LF or NUL; either way it's part of the same newline.
+            if self.last_cr {
+                self.last_cr = false;
+                if byte == b'\n' || byte == 0 {
+                    continue;
+                }
+            }
+
+            match self.escape {
+                EscapeState::Escape => {
+                    self.escape = match byte {
+                        b'[' | b'O' => EscapeState::Sequence,
+                        _ => EscapeState::None,
+                    };
+                    continue;
+                },
+                EscapeState::Sequence => {
+                    // Parameter and intermediate bytes run until a final byte in 0x40..=0x7E.
+                    if (0x40..=0x7E).contains(&byte) {
+                        self.escape = EscapeState::None;
+                        self.handle_sequence(byte, &mut echo);
+                    }
+                    continue;
+                },
+                EscapeState::None => {}
+            }
+
+            match byte {
+                b'\r' | b'\n' => {
+                    self.last_cr = byte == b'\r';
+                    echo.put_slice(b"\r\n");
+                    lines.push(self.finish_line());
+                },
+                BS | DEL => {
+                    self.erase_chars(1, &mut echo);
+                },
+                CTRL_W => {
+                    let count = self.word_length();
+                    self.erase_chars(count, &mut echo);
+                },
+                CTRL_U => {
+                    let count = self.char_count();
+                    self.erase_chars(count, &mut echo);
+                    self.overflowed = false;
+                },
+                ESC => {
+                    self.escape = EscapeState::Escape;
+                },
+                x if x < 32 && x != TAB => {
+                    // Any other control character is ignored.
+                },
+                x => {
+                    if self.line.len() < MAX_LINE_LENGTH {
+                        self.line.push(x);
+                        echo.put_u8(x);
+                    } else {
+                        self.overflowed = true;
+                    }
+                }
+            }
+        }
+
+        EditorOutput {
+            lines,
+            echo: echo.freeze()
+        }
+    }
+
+    fn handle_sequence(&mut self, final_byte: u8, echo: &mut BytesMut) {
+        match final_byte {
+            b'A' => self.history_prev(echo),
+            b'B' => self.history_next(echo),
+            _ => {
+                // Left/right and everything else would need cursor movement; the cursor
+                // always stays at the end of the line, so these are ignored.
+            }
+        }
+    }
+
+    fn finish_line(&mut self) -> Result<String, LineTooLong> {
+        let line = String::from_utf8_lossy(&self.line).into_owned();
+        self.line.clear();
+        self.draft.clear();
+        self.history_pos = None;
+        if std::mem::take(&mut self.overflowed) {
+            return Err(LineTooLong);
+        }
+
+        if !line.trim().is_empty() && self.history.front() != Some(&line) {
+            self.history.push_front(line.clone());
+            self.history.truncate(HISTORY_SIZE);
+        }
+        Ok(line)
+    }
+
+    fn history_prev(&mut self, echo: &mut BytesMut) {
+        let next = match self.history_pos {
+            None => 0,
+            Some(pos) => pos + 1
+        };
+        if next >= self.history.len() {
+            return;
+        }
+        if self.history_pos.is_none() {
+            self.draft = self.line.clone();
+        }
+        self.history_pos = Some(next);
+        let recalled = self.history[next].clone().into_bytes();
+        self.replace_line(recalled, echo);
+    }
+
+    fn history_next(&mut self, echo: &mut BytesMut) {
+        match self.history_pos {
+            None => {},
+            Some(0) => {
+                self.history_pos = None;
+                let draft = std::mem::take(&mut self.draft);
+                self.replace_line(draft, echo);
+            },
+            Some(pos) => {
+                self.history_pos = Some(pos - 1);
+                let recalled = self.history[pos - 1].clone().into_bytes();
+                self.replace_line(recalled, echo);
+            }
+        }
+    }
+
+    fn replace_line(&mut self, line: Vec<u8>, echo: &mut BytesMut) {
+        let count = self.char_count();
+        self.erase_chars(count, echo);
+        echo.put_slice(&line);
+        self.line = line;
+    }
+
+    // The number of characters (not bytes) in the current line.
+    fn char_count(&self) -> usize {
+        self.line.iter().filter(|b| !is_continuation(**b)).count()
+    }
+
+    // The number of characters Ctrl-W removes: trailing whitespace, then the word before it.
+    fn word_length(&self) -> usize {
+        let text = String::from_utf8_lossy(&self.line);
+        let trimmed = text.trim_end();
+        let spaces = text.chars().count() - trimmed.chars().count();
+        let word = trimmed.chars().rev().take_while(|c| !c.is_whitespace()).count();
+        spaces + word
+    }
+
+    fn erase_chars(&mut self, count: usize, echo: &mut BytesMut) {
+        for _ in 0..count {
+            if self.line.is_empty() {
+                break;
+            }
+            // Pop a whole UTF-8 character, not just its last byte.
+            while let Some(b) = self.line.pop() {
+                if !is_continuation(b) {
+                    break;
+                }
+            }
+            echo.put_slice(&[BS, b' ', BS]);
+        }
+    }
+}
+
+fn is_continuation(byte: u8) -> bool {
+    (byte & 0b1100_0000) == 0b1000_0000
+}
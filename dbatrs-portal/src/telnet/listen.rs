@@ -1,80 +1,259 @@
-use std::{
-    net::SocketAddr,
-    sync::Arc,
-    str::FromStr
-};
-use std::net::ToSocketAddrs;
+use std::sync::Arc;
+use chrono::{DateTime, Utc};
 use tokio::{
-    net::{TcpListener, TcpStream},
     sync::{mpsc, oneshot},
+    task::JoinHandle
+};
+use tracing::info;
+use dbatrs_shared::{
+    TotalConf, ProtocolCapabilities,
+    protocol::{ToGame, ToPortal}
 };
-use tracing::{info, error};
-use dbatrs_shared::TotalConf;
 use trust_dns_resolver::TokioAsyncResolver;
+use surrealdb::{RecordId, Surreal, engine::any::Any};
 
 use crate::{
-    telnet::conn::TelnetProtocol
+    telnet::{
+        acceptor::{Accepted, Acceptor},
+        conn::{TelnetProtocol, Msg2TelnetProtocol},
+        registry::{ConnectionRegistry, ConnectionInfo, KickTarget, LoginState}
+    }
 };
 
 pub enum Msg2Listener {
+    // A new client from one of the acceptors.
+    Accepted(Accepted),
+    // The same client, once its hostnames have been looked up.
+    Resolved(Accepted, Vec<String>),
+
+    // Sent by TelnetProtocol tasks to keep the registry current.
+    ClientCapabilities(usize, ProtocolCapabilities),
+    ClientLogin(usize, LoginState),
+    ClientActivity(usize, DateTime<Utc>),
+    // The connection's conn record and user, once logged in.
+    ClientSession(usize, RecordId, RecordId),
+    ClientDisconnected(usize),
 
+    // From the game link.
+    Game(ToPortal),
+    // The link to the game has just been (re)established, so the game needs to hear
+    // about every logged-in connection.
+    GameLinked,
+
+    // Control commands, for the portal itself and admin tooling.
+    ListConnections(oneshot::Sender<Vec<ConnectionInfo>>),
+    // Replies with how many connections were kicked.
+    Kick(KickTarget, String, oneshot::Sender<usize>),
+    Broadcast(String),
+    // Stop accepting connections, send everyone the shutdown message and close them.
+    // The listener's run() returns once they're all gone.
+    Shutdown
 }
 
 pub struct TelnetListener {
     conf: Arc<TotalConf>,
-    listener: TcpListener,
+    // One per portal.listeners entry; they're moved into their own tasks by run().
+    acceptors: Vec<Acceptor>,
+    acceptor_tasks: Vec<JoinHandle<()>>,
     resolver: TokioAsyncResolver,
+    registry: ConnectionRegistry,
+    accepting: bool,
+    // Handed to every connection when running against an embedded database.
+    shared_db: Option<Surreal<Any>>,
+    tx_game: mpsc::UnboundedSender<ToGame>,
     pub tx_telnet: mpsc::Sender<Msg2Listener>,
     rx_telnet: mpsc::Receiver<Msg2Listener>
 }
 
 impl TelnetListener {
-    pub async fn new(conf: Arc<TotalConf>) -> Result<Self, Box<dyn std::error::Error>> {
-        let addr = SocketAddr::from_str(&conf.portal.telnet)?;
-
-        let listener = TcpListener::bind(addr).await?;
+    pub async fn new(conf: Arc<TotalConf>, shared_db: Option<Surreal<Any>>, tx_game: mpsc::UnboundedSender<ToGame>) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut acceptors = Vec::new();
+        for listener in conf.portal.listeners.iter() {
+            acceptors.push(Acceptor::bind(&conf, listener).await?);
+        }
         let resolver = TokioAsyncResolver::tokio_from_system_conf()?;
 
-        let (tx_telnet, rx_telnet) = mpsc::channel(10);
+        let (tx_telnet, rx_telnet) = mpsc::channel(100);
 
         Ok(TelnetListener {
             conf,
-            listener,
+            acceptors,
+            acceptor_tasks: Vec::new(),
             resolver,
+            registry: Default::default(),
+            accepting: true,
+            shared_db,
+            tx_game,
             tx_telnet,
             rx_telnet
         })
     }
 
-    async fn check_site(&self, hostnames: &Vec<String>, ip: std::net::IpAddr) -> bool {
+    fn check_site(&self, hostnames: &Vec<String>, ip: std::net::IpAddr) -> bool {
         true
     }
 
     pub async fn run(&mut self) {
+        for acceptor in self.acceptors.drain(..) {
+            self.acceptor_tasks.push(tokio::spawn(acceptor.run(self.tx_telnet.clone())));
+        }
 
-        loop {
-            match self.listener.accept().await {
-                Ok((stream, addr)) => {
+        while let Some(msg) = self.rx_telnet.recv().await {
+            let _ = self.handle_msg(msg).await;
 
-                    let mut hostnames: Vec<String> = vec!();
-                    if let Ok(response) = self.resolver.reverse_lookup(addr.ip()).await {
-                        hostnames = response.iter().map(|x| x.to_string()).collect();
-                    }
+            if !self.accepting && self.registry.is_empty() {
+                break;
+            }
+        }
+    }
 
-                    if !self.check_site(&hostnames, addr.ip()).await {
-                        info!("(BLOCKED) Connection from: {:?} ({:?})", addr, hostnames);
-                        continue;
-                    }
+    // Looks up the client's hostnames off to the side, so a slow nameserver can't hold up
+    // everyone else, and hands it back as Resolved.
+    fn handle_accept(&mut self, accepted: Accepted) {
+        if !self.conf.portal.reverse_dns {
+            self.handle_resolved(accepted, Vec::new());
+            return;
+        }
+        let resolver = self.resolver.clone();
+        let tx_telnet = self.tx_telnet.clone();
+        tokio::spawn(async move {
+            let hostnames = match resolver.reverse_lookup(accepted.addr.ip()).await {
+                Ok(response) => response.iter().map(|x| x.to_string()).collect(),
+                Err(_) => Vec::new()
+            };
+            let _ = tx_telnet.send(Msg2Listener::Resolved(accepted, hostnames)).await;
+        });
+    }
+
+    fn handle_resolved(&mut self, accepted: Accepted, hostnames: Vec<String>) {
+        let Accepted { stream, addr, tls, proxied, banner } = accepted;
+        if !self.check_site(&hostnames, addr.ip()) {
+            info!("(BLOCKED) Connection from: {:?} ({:?})", addr, hostnames);
+            return;
+        }
 
-                    info!("Connection from: {:?} ({:?})", addr, hostnames);
+        let id = self.registry.next_id();
+        info!("Connection {} from: {:?} ({:?})", id, addr, hostnames);
 
-                    let mut handler = TelnetProtocol::new(self.conf.clone(), stream, addr, hostnames, false);
-                    tokio::spawn(async move { handler.run().await;});
+        // Unbounded, so the listener never waits on a connection that is itself waiting to
+        // send to the listener. A client that stops reading is cut off by its output buffer.
+        let (tx_protocol, rx_protocol) = mpsc::unbounded_channel();
+        let mut handler = TelnetProtocol::new(self.conf.clone(), id, stream, addr, hostnames.clone(), tls, proxied, banner,
+                                              self.tx_telnet.clone(), rx_protocol, self.shared_db.clone(),
+                                              self.tx_game.clone());
+
+        let now = Utc::now();
+        self.registry.insert(ConnectionInfo {
+            id,
+            address: addr,
+            hostnames,
+            capabilities: handler.capabilities().clone(),
+            login: LoginState::Negotiating,
+            conn: None,
+            user: None,
+            time_connected: now,
+            time_activity: now
+        }, tx_protocol);
+
+        tokio::spawn(async move { handler.run().await;});
+    }
+
+    async fn handle_msg(&mut self, msg: Msg2Listener) {
+        match msg {
+            Msg2Listener::Accepted(accepted) => {
+                // Anyone still mid-handshake at shutdown is turned away.
+                if self.accepting {
+                    self.handle_accept(accepted);
                 }
-                Err(e) => {
-                    error!("Error accepting connection: {}", e);
+            },
+            Msg2Listener::Resolved(accepted, hostnames) => {
+                if self.accepting {
+                    self.handle_resolved(accepted, hostnames);
+                }
+            },
+            Msg2Listener::ClientCapabilities(id, capabilities) => {
+                if let Some(handle) = self.registry.get_mut(id) {
+                    handle.info.capabilities = capabilities;
+                }
+            },
+            Msg2Listener::ClientLogin(id, login) => {
+                if let Some(handle) = self.registry.get_mut(id) {
+                    handle.info.login = login;
+                }
+            },
+            Msg2Listener::ClientActivity(id, time) => {
+                if let Some(handle) = self.registry.get_mut(id) {
+                    handle.info.time_activity = time;
+                }
+            },
+            Msg2Listener::ClientSession(id, conn, user) => self.registry.set_session(id, conn, user),
+            Msg2Listener::Game(msg) => {
+                // The handshake is the link's business; everything else is for a connection.
+                let Some(handle) = msg.conn().and_then(|conn| self.registry.find_conn(conn)) else {
+                    return;
+                };
+                let out = match msg {
+                    ToPortal::Text { text, .. } => Msg2TelnetProtocol::Text(text),
+                    ToPortal::Prompt { text, .. } => Msg2TelnetProtocol::Prompt(text),
+                    ToPortal::Gmcp { package, data, .. } => Msg2TelnetProtocol::GMCP(package, data),
+                    ToPortal::Msdp { variables, .. } => Msg2TelnetProtocol::MSDP(variables),
+                    ToPortal::Echo { enabled, .. } => Msg2TelnetProtocol::Echo(enabled),
+                    ToPortal::Pager { control, .. } => Msg2TelnetProtocol::Pager(control),
+                    ToPortal::Disconnect { reason, .. } => {
+                        if let Some(reason) = reason {
+                            let _ = handle.tx_protocol.send(Msg2TelnetProtocol::Text(reason + "\n"));
+                        }
+                        Msg2TelnetProtocol::GameClose
+                    },
+                    ToPortal::Hello { .. } | ToPortal::Reject { .. } => return
+                };
+                let _ = handle.tx_protocol.send(out);
+            },
+            Msg2Listener::GameLinked => {
+                for handle in self.registry.handles() {
+                    let (Some(conn), Some(user)) = (handle.info.conn.clone(), handle.info.user.clone()) else {
+                        continue;
+                    };
+                    let _ = self.tx_game.send(ToGame::Connected { conn: conn.clone(), user });
+                    let capabilities = handle.info.capabilities.clone();
+                    let _ = self.tx_game.send(ToGame::Capabilities { conn, capabilities });
+                }
+            },
+            Msg2Listener::ClientDisconnected(id) => {
+                if let Some(handle) = self.registry.remove(id) {
+                    info!("Connection {} from {:?} closed.", id, handle.info.address);
+                }
+            },
+            Msg2Listener::ListConnections(reply) => {
+                let _ = reply.send(self.registry.list());
+            },
+            Msg2Listener::Kick(target, reason, reply) => {
+                let mut count = 0;
+                for handle in self.registry.matching(target) {
+                    info!("Kicking connection {} from {:?}: {}", handle.info.id, handle.info.address, reason);
+                    let _ = handle.tx_protocol.send(Msg2TelnetProtocol::Text(format!("{}\n", reason)));
+                    let _ = handle.tx_protocol.send(Msg2TelnetProtocol::GameClose);
+                    count += 1;
+                }
+                let _ = reply.send(count);
+            },
+            Msg2Listener::Broadcast(text) => {
+                for handle in self.registry.handles() {
+                    let _ = handle.tx_protocol.send(Msg2TelnetProtocol::Text(text.clone()));
+                }
+            },
+            Msg2Listener::Shutdown => {
+                info!("Telnet listener shutting down; closing {} connections.", self.registry.len());
+                self.accepting = false;
+                for task in self.acceptor_tasks.drain(..) {
+                    task.abort();
+                }
+                let message = format!("{}\n", self.conf.portal.shutdown_message);
+                for handle in self.registry.handles() {
+                    let _ = handle.tx_protocol.send(Msg2TelnetProtocol::Text(message.clone()));
+                    let _ = handle.tx_protocol.send(Msg2TelnetProtocol::GameClose);
                 }
             }
         }
     }
-}
\ No newline at end of file
+}
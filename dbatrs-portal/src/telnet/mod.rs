@@ -1,5 +1,10 @@
+pub mod acceptor;
 pub mod codec;
 pub mod codes;
 pub mod listen;
 pub mod conn;
-pub mod msg;
\ No newline at end of file
+pub mod editor;
+pub mod msg;
+pub mod output;
+pub mod proxy;
+pub mod registry;
\ No newline at end of file
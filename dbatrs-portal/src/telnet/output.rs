@@ -0,0 +1,165 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex}
+};
+
+use tokio::{
+    io::AsyncWrite,
+    sync::Notify
+};
+
+use tokio_util::codec::FramedWrite;
+
+use futures::sink::SinkExt;
+
+use crate::telnet::codec::{TelnetCodec, TelnetEvent};
+
+#[derive(Debug)]
+pub enum OutputError {
+    // The backlog would grow past the configured limit. Holds the backlog size in bytes.
+    Overflow(usize),
+    // The writer has stopped, either due to a socket error or because the queue was closed.
+    Closed
+}
+
+struct PendingOutput {
+    event: TelnetEvent,
+    size: usize,
+    // Low-priority messages carry a key; a newer message with the same key replaces
+    // an older one that hasn't been written yet.
+    collapse_key: Option<String>
+}
+
+#[derive(Default)]
+struct OutputState {
+    pending: VecDeque<PendingOutput>,
+    // Bytes queued plus bytes handed to the writer that haven't been flushed yet.
+    backlog: usize,
+    closed: bool,
+}
+
+// A bounded outbound queue shared between a TelnetProtocol and its writer task.
+// Pushing never blocks, so a client that stops reading can't stall the connection's
+// select loop; instead its backlog grows until it hits max_backlog.
+#[derive(Clone)]
+pub struct OutputQueue {
+    state: Arc<Mutex<OutputState>>,
+    notify: Arc<Notify>,
+    max_backlog: usize,
+}
+
+fn encoded_size(event: &TelnetEvent) -> usize {
+    match event {
+        TelnetEvent::Data(data) => data.len(),
+        TelnetEvent::SubNegotiate(_, data) => data.len() + 5,
+        TelnetEvent::Negotiate(_, _) => 3,
+        TelnetEvent::Command(_) => 2
+    }
+}
+
+impl OutputQueue {
+    pub fn new(max_backlog: usize) -> Self {
+        Self {
+            state: Default::default(),
+            notify: Arc::new(Notify::new()),
+            max_backlog
+        }
+    }
+
+    pub fn backlog(&self) -> usize {
+        self.state.lock().unwrap().backlog
+    }
+
+    pub fn push(&self, event: TelnetEvent) -> Result<(), OutputError> {
+        self.push_inner(event, None)
+    }
+
+    // Queues a message that may be collapsed into a newer one with the same key, or
+    // dropped outright once the client has fallen behind.
+    pub fn push_low_priority(&self, event: TelnetEvent, key: String) -> Result<(), OutputError> {
+        self.push_inner(event, Some(key))
+    }
+
+    fn push_inner(&self, event: TelnetEvent, collapse_key: Option<String>) -> Result<(), OutputError> {
+        let size = encoded_size(&event);
+        let mut state = self.state.lock().unwrap();
+
+        if state.closed {
+            return Err(OutputError::Closed);
+        }
+
+        if let Some(key) = &collapse_key {
+            if let Some(existing) = state.pending.iter_mut().find(|p| p.collapse_key.as_ref() == Some(key)) {
+                let old_size = existing.size;
+                existing.event = event;
+                existing.size = size;
+                state.backlog = state.backlog - old_size + size;
+                return Ok(());
+            }
+            // Past half the limit the client is falling behind; skip low-priority updates.
+            if state.backlog > self.max_backlog / 2 {
+                return Ok(());
+            }
+        }
+
+        if state.backlog + size > self.max_backlog {
+            return Err(OutputError::Overflow(state.backlog + size));
+        }
+
+        state.backlog += size;
+        state.pending.push_back(PendingOutput {
+            event,
+            size,
+            collapse_key
+        });
+        drop(state);
+        self.notify.notify_one();
+        Ok(())
+    }
+
+    // Stops accepting new output. The writer finishes what's already queued and then exits.
+    pub fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.notify.notify_one();
+    }
+
+    pub async fn run_writer<W>(self, mut sink: FramedWrite<W, TelnetCodec>) -> FramedWrite<W, TelnetCodec>
+    where W: AsyncWrite + Unpin {
+        loop {
+            let (batch, closed) = {
+                let mut state = self.state.lock().unwrap();
+                (state.pending.drain(..).collect::<Vec<_>>(), state.closed)
+            };
+
+            if batch.is_empty() {
+                if closed {
+                    break;
+                }
+                self.notify.notified().await;
+                continue;
+            }
+
+            let mut written = 0;
+            let mut failed = false;
+            for item in batch {
+                written += item.size;
+                if sink.feed(item.event).await.is_err() {
+                    failed = true;
+                    break;
+                }
+            }
+            if !failed && sink.flush().await.is_err() {
+                failed = true;
+            }
+
+            let mut state = self.state.lock().unwrap();
+            state.backlog = state.backlog.saturating_sub(written);
+            if failed {
+                state.closed = true;
+                state.pending.clear();
+                break;
+            }
+        }
+        sink
+    }
+}
@@ -0,0 +1,98 @@
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr}
+};
+
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+// The PROXY protocol lets a reverse proxy tell us who it's relaying for. It sends one header
+// ahead of the client's data: a line of text in version 1, or a binary block in version 2.
+// See https://www.haproxy.org/download/2.9/doc/proxy-protocol.txt
+
+const V2_SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+
+// The longest a version 1 header can be, CRLF included.
+const V1_MAX_LEN: usize = 107;
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("PROXY header: {}", message))
+}
+
+// Reads exactly one header from the stream, leaving whatever follows it unread. Returns the
+// client's address, or None if the proxy says the connection isn't relayed for anyone, e.g.
+// its own health checks.
+pub async fn read_header<S: AsyncRead + Unpin>(stream: &mut S) -> io::Result<Option<SocketAddr>> {
+    let mut start = [0u8; 12];
+    stream.read_exact(&mut start).await?;
+    if start == V2_SIGNATURE {
+        read_v2(stream).await
+    } else if start.starts_with(b"PROXY ") {
+        read_v1(stream, &start).await
+    } else {
+        Err(invalid("missing"))
+    }
+}
+
+async fn read_v1<S: AsyncRead + Unpin>(stream: &mut S, start: &[u8]) -> io::Result<Option<SocketAddr>> {
+    // The header is short and we mustn't read past it, so go a byte at a time.
+    let mut line = start.to_vec();
+    while !line.ends_with(b"\r\n") {
+        if line.len() >= V1_MAX_LEN {
+            return Err(invalid("too long"));
+        }
+        line.push(stream.read_u8().await?);
+    }
+    let line = std::str::from_utf8(&line[..line.len() - 2]).map_err(|_| invalid("not text"))?;
+
+    let parts: Vec<&str> = line.split(' ').collect();
+    match parts.as_slice() {
+        ["PROXY", "UNKNOWN", ..] => Ok(None),
+        ["PROXY", family @ ("TCP4" | "TCP6"), source, _, port, _] => {
+            let ip: IpAddr = source.parse().map_err(|_| invalid("bad source address"))?;
+            if ip.is_ipv4() != (*family == "TCP4") {
+                return Err(invalid("address doesn't match family"));
+            }
+            let port: u16 = port.parse().map_err(|_| invalid("bad source port"))?;
+            Ok(Some(SocketAddr::new(ip, port)))
+        },
+        _ => Err(invalid("malformed"))
+    }
+}
+
+async fn read_v2<S: AsyncRead + Unpin>(stream: &mut S) -> io::Result<Option<SocketAddr>> {
+    let version_command = stream.read_u8().await?;
+    let family = stream.read_u8().await?;
+    let len = stream.read_u16().await? as usize;
+    let mut data = vec![0u8; len];
+    stream.read_exact(&mut data).await?;
+
+    if version_command >> 4 != 2 {
+        return Err(invalid("unsupported version"));
+    }
+    match version_command & 0x0F {
+        // LOCAL: the proxy is talking to us on its own behalf.
+        0 => return Ok(None),
+        1 => {},
+        _ => return Err(invalid("unsupported command"))
+    }
+
+    // Addresses are followed by TLVs, which we have no use for.
+    match family {
+        // TCP over IPv4.
+        0x11 if data.len() >= 12 => {
+            let ip = Ipv4Addr::new(data[0], data[1], data[2], data[3]);
+            let port = u16::from_be_bytes([data[8], data[9]]);
+            Ok(Some(SocketAddr::new(IpAddr::V4(ip), port)))
+        },
+        // TCP over IPv6.
+        0x21 if data.len() >= 36 => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(&data[..16]);
+            let port = u16::from_be_bytes([data[32], data[33]]);
+            Ok(Some(SocketAddr::new(IpAddr::V6(Ipv6Addr::from(octets)), port)))
+        },
+        0x11 | 0x21 => Err(invalid("truncated addresses")),
+        // UDP, Unix sockets or unspecified: nothing we can use as a client address.
+        _ => Ok(None)
+    }
+}
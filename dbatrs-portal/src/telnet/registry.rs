@@ -0,0 +1,126 @@
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr}
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use surrealdb::RecordId;
+use tokio::sync::mpsc;
+
+use dbatrs_shared::ProtocolCapabilities;
+
+use crate::telnet::conn::Msg2TelnetProtocol;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub enum LoginState {
+    // Telnet negotiation hasn't finished yet.
+    Negotiating,
+    // Negotiation is done, but nobody has logged in.
+    Connected,
+    // Logged into the account with this email.
+    Authenticated(String)
+}
+
+// A snapshot of everything the portal knows about one live connection.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectionInfo {
+    pub id: usize,
+    pub address: SocketAddr,
+    pub hostnames: Vec<String>,
+    pub capabilities: ProtocolCapabilities,
+    pub login: LoginState,
+    // Set once the user has logged in and the conn record exists.
+    pub conn: Option<RecordId>,
+    pub user: Option<RecordId>,
+    pub time_connected: DateTime<Utc>,
+    pub time_activity: DateTime<Utc>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KickTarget {
+    Id(usize),
+    Ip(IpAddr)
+}
+
+pub struct ConnectionHandle {
+    pub info: ConnectionInfo,
+    pub tx_protocol: mpsc::UnboundedSender<Msg2TelnetProtocol>
+}
+
+// Tracks every TelnetProtocol task the listener has spawned.
+#[derive(Default)]
+pub struct ConnectionRegistry {
+    next_id: usize,
+    connections: HashMap<usize, ConnectionHandle>,
+    // Which connection each conn record belongs to, kept in step by insert, set_session and
+    // remove.
+    conns: HashMap<RecordId, usize>
+}
+
+impl ConnectionRegistry {
+    pub fn next_id(&mut self) -> usize {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    pub fn insert(&mut self, info: ConnectionInfo, tx_protocol: mpsc::UnboundedSender<Msg2TelnetProtocol>) {
+        if let Some(conn) = &info.conn {
+            self.conns.insert(conn.clone(), info.id);
+        }
+        self.connections.insert(info.id, ConnectionHandle { info, tx_protocol });
+    }
+
+    pub fn remove(&mut self, id: usize) -> Option<ConnectionHandle> {
+        let handle = self.connections.remove(&id)?;
+        if let Some(conn) = &handle.info.conn {
+            self.conns.remove(conn);
+        }
+        Some(handle)
+    }
+
+    // Links a connection to the conn and user records made when it logged in.
+    pub fn set_session(&mut self, id: usize, conn: RecordId, user: RecordId) {
+        let Some(handle) = self.connections.get_mut(&id) else {
+            return;
+        };
+        if let Some(old) = handle.info.conn.replace(conn.clone()) {
+            self.conns.remove(&old);
+        }
+        handle.info.user = Some(user);
+        self.conns.insert(conn, id);
+    }
+
+    pub fn get_mut(&mut self, id: usize) -> Option<&mut ConnectionHandle> {
+        self.connections.get_mut(&id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.connections.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.connections.is_empty()
+    }
+
+    pub fn list(&self) -> Vec<ConnectionInfo> {
+        let mut out: Vec<ConnectionInfo> = self.connections.values().map(|h| h.info.clone()).collect();
+        out.sort_by_key(|info| info.id);
+        out
+    }
+
+    pub fn matching(&self, target: KickTarget) -> Vec<&ConnectionHandle> {
+        self.connections.values().filter(|h| match target {
+            KickTarget::Id(id) => h.info.id == id,
+            KickTarget::Ip(ip) => h.info.address.ip() == ip
+        }).collect()
+    }
+
+    pub fn find_conn(&self, conn: &RecordId) -> Option<&ConnectionHandle> {
+        self.conns.get(conn).and_then(|id| self.connections.get(id))
+    }
+
+    pub fn handles(&self) -> impl Iterator<Item = &ConnectionHandle> {
+        self.connections.values()
+    }
+}
@@ -0,0 +1,70 @@
+use surrealdb::{Surreal, engine::any::Any};
+
+use dbatrs_shared::{
+    TotalConf, SurrealConf, SurrealEngine,
+    db::connect_root,
+    migrate
+};
+use dbatrs_portal::cleanup::{remove_orphans, remove_stale};
+
+// Users a to d, each with a character in play. c is connected through both portals, and
+// d has no connection at all, as if the game were holding their character for them.
+async fn world() -> Surreal<Any> {
+    let conf = TotalConf {
+        surreal: SurrealConf {
+            engine: SurrealEngine::Memory,
+            namespace: "test".to_string(),
+            database: "test".to_string(),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let db = connect_root(&conf).await.unwrap();
+    migrate::migrate(&db).await.unwrap();
+    db.query("
+        FOR $name IN ['a', 'b', 'c', 'd'] {
+            CREATE type::thing('user', $name) SET email = $name + '@example.com', password = 'secret';
+            CREATE type::thing('pc', $name) SET name = $name, lower_name = $name;
+            CREATE game_session SET user = type::thing('user', $name), pc = type::thing('pc', $name);
+        };
+        CREATE conn:a1 SET user = user:a, ip = '192.0.2.1', portal = 'one';
+        CREATE conn:b1 SET user = user:b, ip = '192.0.2.2', portal = 'two';
+        CREATE conn:c1 SET user = user:c, ip = '192.0.2.3', portal = 'one';
+        CREATE conn:c2 SET user = user:c, ip = '192.0.2.3', portal = 'two';
+        CREATE conn_input SET user = user:a, conn = conn:a1, version = 1, message = '{}';
+        CREATE conn_output SET user = user:a, conn = conn:a1, version = 1, message = '{}';
+    ").await.unwrap().check().unwrap();
+    db
+}
+
+async fn ids(db: &Surreal<Any>, query: &str) -> Vec<String> {
+    let mut ids: Vec<String> = db.query(query).await.unwrap().take(0).unwrap();
+    ids.sort();
+    ids
+}
+
+async fn sessions(db: &Surreal<Any>) -> Vec<String> {
+    ids(db, "SELECT VALUE record::id(user) FROM game_session").await
+}
+
+#[tokio::test]
+async fn orphans_take_only_their_own_sessions() {
+    let db = world().await;
+    assert_eq!(remove_orphans(&db, "one").await.unwrap(), 2);
+    assert_eq!(ids(&db, "SELECT VALUE record::id(id) FROM conn").await, ["b1", "c2"]);
+    assert!(ids(&db, "SELECT VALUE record::id(id) FROM conn_input, conn_output").await.is_empty());
+    // c is still connected through the other portal, and d was never ours.
+    assert_eq!(sessions(&db).await, ["b", "c", "d"]);
+
+    assert_eq!(remove_orphans(&db, "one").await.unwrap(), 0);
+    assert_eq!(sessions(&db).await, ["b", "c", "d"]);
+}
+
+#[tokio::test]
+async fn stale_conns_take_only_their_own_sessions() {
+    let db = world().await;
+    db.query("UPDATE conn:b1, conn:c1 SET time_system_activity = time::now() - 1h").await.unwrap().check().unwrap();
+    assert_eq!(remove_stale(&db, 300).await.unwrap(), 2);
+    assert_eq!(ids(&db, "SELECT VALUE record::id(id) FROM conn").await, ["a1", "c2"]);
+    assert_eq!(sessions(&db).await, ["a", "c", "d"]);
+}
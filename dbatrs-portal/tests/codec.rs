@@ -0,0 +1,110 @@
+use bytes::{Bytes, BytesMut};
+use proptest::prelude::*;
+use tokio_util::codec::{Decoder, Encoder};
+
+use dbatrs_portal::telnet::{
+    codes,
+    codec::{TelnetCodec, TelnetEvent},
+};
+
+// Any option byte except the compression ones, which would switch the codec into zlib mode.
+fn option_byte() -> impl Strategy<Value = u8> {
+    any::<u8>().prop_filter("MCCP changes the stream encoding", |b| *b != codes::MCCP2 && *b != codes::MCCP3)
+}
+
+fn telnet_event() -> impl Strategy<Value = TelnetEvent> {
+    prop_oneof![
+        prop::collection::vec(any::<u8>(), 1..64)
+            .prop_map(|data| TelnetEvent::Data(Bytes::from(data))),
+        (prop::sample::select(vec![codes::WILL, codes::WONT, codes::DO, codes::DONT]), any::<u8>())
+            .prop_map(|(comm, op)| TelnetEvent::Negotiate(comm, op)),
+        (option_byte(), prop::collection::vec(any::<u8>(), 0..64))
+            .prop_map(|(op, data)| TelnetEvent::SubNegotiate(op, Bytes::from(data))),
+        any::<u8>()
+            .prop_filter("must be a plain command", |b| {
+                ![codes::IAC, codes::SB, codes::WILL, codes::WONT, codes::DO, codes::DONT].contains(b)
+            })
+            .prop_map(TelnetEvent::Command),
+    ]
+}
+
+// Data may be split across several events by reads or escapes, so adjacent Data events are
+// joined before comparing.
+fn coalesce(events: Vec<TelnetEvent>) -> Vec<TelnetEvent> {
+    let mut out: Vec<TelnetEvent> = Vec::new();
+    for event in events {
+        if let (Some(TelnetEvent::Data(prev)), TelnetEvent::Data(data)) = (out.last_mut(), &event) {
+            let mut joined = BytesMut::from(prev.as_ref());
+            joined.extend_from_slice(data);
+            *prev = joined.freeze();
+            continue;
+        }
+        out.push(event);
+    }
+    out
+}
+
+fn encode_all(events: &[TelnetEvent]) -> BytesMut {
+    let mut codec = TelnetCodec::new(usize::MAX);
+    let mut wire = BytesMut::new();
+    for event in events {
+        codec.encode(event.clone(), &mut wire).unwrap();
+    }
+    wire
+}
+
+fn decode_in_chunks(wire: &[u8], chunk_sizes: &[usize]) -> Vec<TelnetEvent> {
+    let mut codec = TelnetCodec::new(usize::MAX);
+    let mut buf = BytesMut::new();
+    let mut out = Vec::new();
+    let mut pos = 0;
+    let mut sizes = chunk_sizes.iter().cycle();
+
+    while pos < wire.len() {
+        let size = (*sizes.next().unwrap()).min(wire.len() - pos);
+        buf.extend_from_slice(&wire[pos..pos + size]);
+        pos += size;
+        while let Some(event) = codec.decode(&mut buf).unwrap() {
+            out.push(event);
+        }
+    }
+    assert!(buf.is_empty(), "undecoded bytes left over: {:?}", buf);
+    out
+}
+
+proptest! {
+    #[test]
+    fn round_trip_whole(events in prop::collection::vec(telnet_event(), 0..32)) {
+        let wire = encode_all(&events);
+        let decoded = decode_in_chunks(&wire, &[wire.len().max(1)]);
+        prop_assert_eq!(coalesce(decoded), coalesce(events));
+    }
+
+    #[test]
+    fn round_trip_split_reads(events in prop::collection::vec(telnet_event(), 0..32),
+                              chunk_sizes in prop::collection::vec(1usize..8, 1..16)) {
+        let wire = encode_all(&events);
+        let decoded = decode_in_chunks(&wire, &chunk_sizes);
+        prop_assert_eq!(coalesce(decoded), coalesce(events));
+    }
+
+    #[test]
+    fn encoded_data_never_contains_bare_iac(data in prop::collection::vec(any::<u8>(), 1..128)) {
+        let wire = Bytes::from(TelnetEvent::Data(Bytes::from(data)));
+        let mut iter = wire.iter();
+        while let Some(byte) = iter.next() {
+            if *byte == codes::IAC {
+                prop_assert_eq!(iter.next(), Some(&codes::IAC));
+            }
+        }
+    }
+}
+
+#[test]
+fn gmcp_payload_with_iac_survives() {
+    let payload = Bytes::from_static(b"Core.Hello {\"x\": \"\xff\xff\"}");
+    let wire = encode_all(&[TelnetEvent::SubNegotiate(codes::GMCP, payload.clone())]);
+    assert_eq!(&wire[..], b"\xff\xfa\xc9Core.Hello {\"x\": \"\xff\xff\xff\xff\"}\xff\xf0");
+    let decoded = decode_in_chunks(&wire, &[1]);
+    assert_eq!(decoded, vec![TelnetEvent::SubNegotiate(codes::GMCP, payload)]);
+}
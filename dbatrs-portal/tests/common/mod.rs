@@ -0,0 +1,142 @@
+#![allow(dead_code)]
+
+use std::{sync::Arc, time::Duration};
+
+use futures::{sink::SinkExt, stream::StreamExt};
+use tokio::{
+    io::DuplexStream,
+    sync::mpsc,
+    task::JoinHandle,
+    time
+};
+use tokio_util::codec::Framed;
+
+use dbatrs_shared::{ProtocolCapabilities, TotalConf, protocol::ToGame};
+use dbatrs_portal::telnet::{
+    codec::{TelnetCodec, TelnetEvent},
+    conn::{Msg2TelnetProtocol, TelnetProtocol},
+    listen::Msg2Listener,
+    registry::LoginState
+};
+
+// Long enough for anything the protocol does on its own, short enough to fail fast.
+pub const WAIT: Duration = Duration::from_secs(2);
+
+pub fn conf() -> TotalConf {
+    let mut conf = TotalConf::default();
+    conf.portal.id = "test".to_string();
+    conf.portal.max_output_buffer = 64 * 1024;
+    conf.portal.heartbeat_interval = 3600;
+    conf.portal.drain_timeout = 1;
+    conf
+}
+
+// The far end of a TelnetProtocol: a client socket, plus the channels the listener and
+// game would otherwise hold.
+pub struct Client {
+    pub socket: Framed<DuplexStream, TelnetCodec>,
+    pub rx_listener: mpsc::Receiver<Msg2Listener>,
+    pub tx_protocol: mpsc::UnboundedSender<Msg2TelnetProtocol>,
+    pub rx_game: mpsc::UnboundedReceiver<ToGame>,
+    pub task: JoinHandle<()>
+}
+
+impl Client {
+    pub async fn connect() -> Self {
+        Self::connect_with(conf(), 64 * 1024).await
+    }
+
+    // Runs a connection against an in-memory database, over a pipe that holds at most
+    // buffer bytes in each direction.
+    pub async fn connect_with(conf: TotalConf, buffer: usize) -> Self {
+        Self::start(conf, buffer, false).await
+    }
+
+    // As if accepted on a listener behind a proxy.
+    pub async fn connect_proxied() -> Self {
+        Self::start(conf(), 64 * 1024, true).await
+    }
+
+    async fn start(conf: TotalConf, buffer: usize, proxied: bool) -> Self {
+        let (client, server) = tokio::io::duplex(buffer);
+        let (tx_listener, rx_listener) = mpsc::channel(100);
+        let (tx_protocol, rx_protocol) = mpsc::unbounded_channel();
+        let (tx_game, rx_game) = mpsc::unbounded_channel();
+        let db = surrealdb::engine::any::connect("mem://").await.unwrap();
+        db.use_ns("test").use_db("test").await.unwrap();
+
+        let mut protocol = TelnetProtocol::new(Arc::new(conf), 1, server, "127.0.0.1:4000".parse().unwrap(), Vec::new(),
+                                               false, proxied, None, tx_listener, rx_protocol, Some(db), tx_game);
+        let task = tokio::spawn(async move { protocol.run().await; });
+        Client {
+            socket: Framed::new(client, TelnetCodec::new(8192)),
+            rx_listener,
+            tx_protocol,
+            rx_game,
+            task
+        }
+    }
+
+    pub async fn send(&mut self, event: TelnetEvent) {
+        self.socket.send(event).await.unwrap();
+    }
+
+    pub async fn send_text(&mut self, text: &str) {
+        self.send(TelnetEvent::Data(bytes::Bytes::copy_from_slice(text.as_bytes()))).await;
+    }
+
+    // The next thing the server sends, or None if it has gone quiet or hung up.
+    pub async fn next_event(&mut self) -> Option<TelnetEvent> {
+        match time::timeout(WAIT, self.socket.next()).await {
+            Ok(Some(Ok(event))) => Some(event),
+            _ => None
+        }
+    }
+
+    // Reads until the server sends this event, returning everything before it.
+    pub async fn expect(&mut self, wanted: TelnetEvent) -> Vec<TelnetEvent> {
+        let mut seen = Vec::new();
+        loop {
+            match self.next_event().await {
+                Some(event) if event == wanted => return seen,
+                Some(event) => seen.push(event),
+                None => panic!("never received {:?}; got {:?}", wanted, seen)
+            }
+        }
+    }
+
+    // Reads until the text the server sent contains wanted, returning all of it.
+    pub async fn expect_text(&mut self, wanted: &str) -> String {
+        let mut text = String::new();
+        while !text.contains(wanted) {
+            match self.next_event().await {
+                Some(TelnetEvent::Data(data)) => text.push_str(&String::from_utf8_lossy(&data)),
+                Some(_) => {},
+                None => panic!("never received {:?}; got {:?}", wanted, text)
+            }
+        }
+        text
+    }
+
+    // Waits for the negotiation phase to end.
+    pub async fn until_connected(&mut self) {
+        loop {
+            match time::timeout(WAIT, self.rx_listener.recv()).await {
+                Ok(Some(Msg2Listener::ClientLogin(_, LoginState::Connected))) => return,
+                Ok(Some(_)) => {},
+                _ => panic!("the connection never finished negotiating")
+            }
+        }
+    }
+
+    // The next capabilities the connection reports that pass check.
+    pub async fn capabilities(&mut self, check: impl Fn(&ProtocolCapabilities) -> bool) -> ProtocolCapabilities {
+        loop {
+            match time::timeout(WAIT, self.rx_listener.recv()).await {
+                Ok(Some(Msg2Listener::ClientCapabilities(_, capabilities))) if check(&capabilities) => return capabilities,
+                Ok(Some(_)) => {},
+                _ => panic!("the connection never reported matching capabilities")
+            }
+        }
+    }
+}
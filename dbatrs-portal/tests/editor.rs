@@ -0,0 +1,71 @@
+use dbatrs_portal::telnet::editor::{LineEditor, LineTooLong, MAX_LINE_LENGTH};
+
+fn lines(editor: &mut LineEditor, data: &[u8]) -> Vec<Result<String, LineTooLong>> {
+    editor.feed(data).lines
+}
+
+fn ok(list: &[&str]) -> Vec<Result<String, LineTooLong>> {
+    list.iter().map(|line| Ok(line.to_string())).collect()
+}
+
+#[test]
+fn backspace_and_delete_erase_whole_characters() {
+    let mut editor = LineEditor::new();
+    let output = editor.feed(b"lookk\x08");
+    assert!(output.lines.is_empty());
+    assert_eq!(&output.echo[..], b"lookk\x08 \x08");
+    assert_eq!(lines(&mut editor, b" caf\xc3\xa9\x7fe\r"), ok(&["look cafe"]));
+    // Nothing to erase, so nothing is echoed.
+    assert_eq!(&editor.feed(b"\x08\x7f").echo[..], b"");
+}
+
+#[test]
+fn ctrl_w_and_ctrl_u() {
+    let mut editor = LineEditor::new();
+    assert_eq!(lines(&mut editor, b"get all corpse  \x17bag\n"), ok(&["get all bag"]));
+    assert_eq!(lines(&mut editor, b"say oops\x15say hi\n"), ok(&["say hi"]));
+}
+
+#[test]
+fn crlf_and_cr_nul_end_one_line() {
+    let mut editor = LineEditor::new();
+    assert_eq!(lines(&mut editor, b"north\r\nsouth\r\0east\nwest\r"), ok(&["north", "south", "east", "west"]));
+    // The LF of a CRLF split across two reads is still part of the same newline.
+    assert_eq!(lines(&mut editor, b"\nup\r\n\r\n"), ok(&["up", ""]));
+}
+
+#[test]
+fn tabs_are_kept_and_other_control_bytes_dropped() {
+    let mut editor = LineEditor::new();
+    let output = editor.feed(b"say a\tb\x07\x01c\r\n");
+    assert_eq!(output.lines, ok(&["say a\tbc"]));
+    assert_eq!(&output.echo[..], b"say a\tbc\r\n");
+}
+
+#[test]
+fn arrow_keys_recall_history() {
+    let mut editor = LineEditor::new();
+    editor.feed(b"look\r\nscore\r\nscore\r\n");
+    // Up twice skips the repeated line; down comes back to the draft.
+    let output = editor.feed(b"wh\x1b[A\x1b[A");
+    assert_eq!(&output.echo[..], b"wh\x08 \x08\x08 \x08score\x08 \x08\x08 \x08\x08 \x08\x08 \x08\x08 \x08look");
+    editor.feed(b"\x1b[B\x1bOB");
+    assert_eq!(lines(&mut editor, b"o\r\n"), ok(&["who"]));
+    // Parameters before the final byte are skipped, and unknown sequences ignored.
+    assert_eq!(lines(&mut editor, b"\x1b[1;5A\x1b[C\r\n"), ok(&["who"]));
+}
+
+#[test]
+fn over_long_lines_are_reported() {
+    let mut editor = LineEditor::new();
+    let mut data = vec![b'x'; MAX_LINE_LENGTH + 10];
+    data.extend_from_slice(b"\r\nlook\r\n");
+    let output = editor.feed(&data);
+    assert_eq!(output.lines, vec![Err(LineTooLong), Ok("look".to_string())]);
+    assert_eq!(output.echo.len(), MAX_LINE_LENGTH + 8);
+
+    // Killing the line starts it over.
+    let mut data = vec![b'x'; MAX_LINE_LENGTH + 1];
+    data.extend_from_slice(b"\x15look\r\n");
+    assert_eq!(lines(&mut editor, &data), ok(&["look"]));
+}
@@ -0,0 +1,121 @@
+mod common;
+
+use std::{sync::Arc, time::Duration};
+
+use futures::stream::StreamExt;
+use tokio::{
+    io::DuplexStream,
+    sync::{mpsc, oneshot},
+    task::JoinHandle,
+    time
+};
+use tokio_util::codec::Framed;
+
+use dbatrs_portal::telnet::{
+    acceptor::Accepted,
+    codec::{TelnetCodec, TelnetEvent},
+    listen::{Msg2Listener, TelnetListener},
+    registry::{ConnectionInfo, KickTarget}
+};
+
+type Socket = Framed<DuplexStream, TelnetCodec>;
+
+struct Portal {
+    tx: mpsc::Sender<Msg2Listener>,
+    task: JoinHandle<()>
+}
+
+impl Portal {
+    async fn start() -> Self {
+        let mut conf = common::conf();
+        conf.portal.shutdown_message = "The game is shutting down.".to_string();
+        let db = surrealdb::engine::any::connect("mem://").await.unwrap();
+        let mut listener = TelnetListener::new(Arc::new(conf), Some(db), mpsc::unbounded_channel().0).await.unwrap();
+        let tx = listener.tx_telnet.clone();
+        let task = tokio::spawn(async move { listener.run().await; });
+        Portal { tx, task }
+    }
+
+    async fn connect(&self, addr: &str) -> Socket {
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let accepted = Accepted { stream: Box::new(server), addr: addr.parse().unwrap(), tls: false, proxied: false, banner: None };
+        self.tx.send(Msg2Listener::Accepted(accepted)).await.unwrap();
+        Framed::new(client, TelnetCodec::new(8192))
+    }
+
+    async fn list(&self) -> Vec<ConnectionInfo> {
+        let (reply, rx) = oneshot::channel();
+        self.tx.send(Msg2Listener::ListConnections(reply)).await.unwrap();
+        rx.await.unwrap()
+    }
+
+    // Waits for the registry to settle on these connection ids.
+    async fn until_listed(&self, ids: &[usize]) {
+        for _ in 0..100 {
+            if self.list().await.iter().map(|info| info.id).eq(ids.iter().copied()) {
+                return;
+            }
+            time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("the listener never listed {:?}", ids);
+    }
+}
+
+// Reads text until it contains wanted, or, without wanted, until the server hangs up.
+async fn read_text(socket: &mut Socket, wanted: Option<&str>) -> String {
+    let mut text = String::new();
+    while !wanted.is_some_and(|wanted| text.contains(wanted)) {
+        match time::timeout(common::WAIT, socket.next()).await {
+            Ok(Some(Ok(TelnetEvent::Data(data)))) => text.push_str(&String::from_utf8_lossy(&data)),
+            Ok(Some(Ok(_))) => {},
+            Ok(_) if wanted.is_none() => break,
+            _ => panic!("never received {:?}; got {:?}", wanted, text)
+        }
+    }
+    text
+}
+
+#[tokio::test]
+async fn lists_and_kicks_connections() {
+    let portal = Portal::start().await;
+    let mut first = portal.connect("192.0.2.7:4000").await;
+    let _second = portal.connect("192.0.2.8:4000").await;
+    portal.until_listed(&[1, 2]).await;
+    let list = portal.list().await;
+    assert_eq!(list[0].address, "192.0.2.7:4000".parse().unwrap());
+    assert_eq!(list[1].address, "192.0.2.8:4000".parse().unwrap());
+
+    let (reply, rx) = oneshot::channel();
+    portal.tx.send(Msg2Listener::Kick(KickTarget::Ip("192.0.2.7".parse().unwrap()), "Go away.".to_string(), reply)).await.unwrap();
+    assert_eq!(rx.await.unwrap(), 1);
+    assert!(read_text(&mut first, None).await.contains("Go away.\r\n"));
+    portal.until_listed(&[2]).await;
+
+    let (reply, rx) = oneshot::channel();
+    portal.tx.send(Msg2Listener::Kick(KickTarget::Id(1), "Again.".to_string(), reply)).await.unwrap();
+    assert_eq!(rx.await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn broadcasts_reach_everyone() {
+    let portal = Portal::start().await;
+    let mut first = portal.connect("192.0.2.7:4000").await;
+    let mut second = portal.connect("192.0.2.8:4000").await;
+    portal.until_listed(&[1, 2]).await;
+    portal.tx.send(Msg2Listener::Broadcast("Reboot in 5 minutes.\n".to_string())).await.unwrap();
+    read_text(&mut first, Some("Reboot in 5 minutes.\r\n")).await;
+    read_text(&mut second, Some("Reboot in 5 minutes.\r\n")).await;
+}
+
+#[tokio::test]
+async fn shutdown_closes_everyone_and_stops() {
+    let mut portal = Portal::start().await;
+    let mut client = portal.connect("192.0.2.7:4000").await;
+    portal.until_listed(&[1]).await;
+    portal.tx.send(Msg2Listener::Shutdown).await.unwrap();
+    assert!(read_text(&mut client, None).await.contains("The game is shutting down.\r\n"));
+    time::timeout(common::WAIT, &mut portal.task).await.expect("the listener kept running").unwrap();
+
+    // Nothing is listening any more.
+    assert!(portal.tx.send(Msg2Listener::Broadcast("Anyone?".to_string())).await.is_err());
+}
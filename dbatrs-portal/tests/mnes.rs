@@ -0,0 +1,104 @@
+mod common;
+
+use bytes::Bytes;
+
+use dbatrs_shared::Color;
+use dbatrs_portal::telnet::{
+    codec::TelnetEvent,
+    codes::{self, MNES_ESC as ESC, MNES_INFO, MNES_IS, MNES_USERVAR as USERVAR, MNES_VALUE as VALUE, MNES_VAR as VAR},
+    conn::parse_mnes_variables
+};
+
+use common::Client;
+
+fn pairs(list: &[(&str, &str)]) -> Vec<(String, String)> {
+    list.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+}
+
+// Builds a payload from pieces, each either a type code or some text.
+fn payload(command: u8, pieces: &[&[u8]]) -> Bytes {
+    let mut out = vec![command];
+    for piece in pieces {
+        out.extend_from_slice(piece);
+    }
+    Bytes::from(out)
+}
+
+#[test]
+fn var_and_uservar_entries() {
+    let data = [&[VAR][..], b"client_name", &[VALUE], b"Mudlet", &[USERVAR], b"colour", &[VALUE], b" blue ", &[VAR], b"CHARSET"].concat();
+    assert_eq!(parse_mnes_variables(&data), pairs(&[("CLIENT_NAME", "Mudlet"), ("COLOUR", "blue"), ("CHARSET", "")]));
+}
+
+#[test]
+fn esc_makes_type_codes_literal() {
+    let data = [&[VAR][..], b"A", &[ESC, VAR], b"B", &[VALUE], b"x", &[ESC, VALUE], b"y", &[ESC, ESC]].concat();
+    assert_eq!(parse_mnes_variables(&data), pairs(&[("A\0B", "x\u{1}y\u{2}")]));
+}
+
+#[test]
+fn stray_bytes_and_empty_names_are_dropped() {
+    let data = [&b"junk"[..], &[VAR, VALUE], b"orphan", &[VAR], b"MTTS", &[VALUE], b"137"].concat();
+    assert_eq!(parse_mnes_variables(&data), pairs(&[("MTTS", "137")]));
+    assert!(parse_mnes_variables(&[]).is_empty());
+}
+
+#[tokio::test]
+async fn is_reply_updates_capabilities() {
+    let mut client = Client::connect().await;
+    client.send(TelnetEvent::Negotiate(codes::WILL, codes::MNES)).await;
+    // Once we agree, the server asks for the variables it cares about.
+    client.expect(TelnetEvent::Negotiate(codes::DO, codes::MNES)).await;
+    let request = loop {
+        if let Some(TelnetEvent::SubNegotiate(codes::MNES, data)) = client.next_event().await {
+            break data;
+        }
+    };
+    assert_eq!(request[0], codes::MNES_SEND);
+    assert!(request.windows(11).any(|w| w == b"CLIENT_NAME"));
+
+    // An IAC inside a value is escaped on the wire and mustn't end the subnegotiation.
+    client.send(TelnetEvent::SubNegotiate(codes::MNES, payload(MNES_IS, &[
+        &[VAR], b"CLIENT_VERSION", &[VALUE], b"4.17\xff",
+        &[VAR], b"CLIENT_NAME", &[VALUE], b"Mudlet",
+        &[VAR], b"CHARSET", &[VALUE], b"UTF-8",
+        &[VAR], b"MTTS", &[VALUE], b"137",
+        &[VAR], b"IPADDRESS", &[VALUE], b"192.0.2.7"
+    ]))).await;
+    let capabilities = client.capabilities(|c| c.client_name == "MUDLET").await;
+    assert_eq!(capabilities.client_version, "4.17\u{fffd}");
+    assert!(capabilities.utf8);
+    assert_eq!(capabilities.encoding, "UTF-8");
+    assert_eq!(capabilities.ansi_color, Color::Xterm256);
+    // MTTS 128 claims it's a proxy, but only the listener's configuration is trusted.
+    assert!(capabilities.proxy);
+    assert_eq!(capabilities.host_address, "127.0.0.1");
+}
+
+#[tokio::test]
+async fn a_proxied_listener_trusts_the_address() {
+    let mut client = Client::connect_proxied().await;
+    client.send(TelnetEvent::Negotiate(codes::WILL, codes::MNES)).await;
+    client.send(TelnetEvent::SubNegotiate(codes::MNES, payload(MNES_IS, &[
+        &[VAR], b"IPADDRESS", &[VALUE], b"192.0.2.7"
+    ]))).await;
+    let capabilities = client.capabilities(|c| c.host_address == "192.0.2.7").await;
+    assert_eq!(capabilities.host_port, 4000);
+}
+
+#[tokio::test]
+async fn info_updates_and_untrusted_addresses() {
+    let mut client = Client::connect().await;
+    client.send(TelnetEvent::Negotiate(codes::WILL, codes::MNES)).await;
+    client.send(TelnetEvent::SubNegotiate(codes::MNES, payload(MNES_IS, &[
+        &[VAR], b"IPADDRESS", &[VALUE], b"192.0.2.7",
+        &[VAR], b"CHARSET", &[VALUE], b"ASCII"
+    ]))).await;
+    let capabilities = client.capabilities(|c| c.encoding == "ASCII").await;
+    assert_eq!(capabilities.host_address, "127.0.0.1");
+    assert!(!capabilities.utf8);
+
+    client.send(TelnetEvent::SubNegotiate(codes::MNES, payload(MNES_INFO, &[&[VAR], b"CHARSET", &[VALUE], b"utf-8"]))).await;
+    let capabilities = client.capabilities(|c| c.encoding == "utf-8").await;
+    assert!(capabilities.utf8);
+}
@@ -0,0 +1,308 @@
+mod common;
+
+use std::time::Duration;
+
+use bytes::Bytes;
+use tokio::{io::AsyncWriteExt, time};
+
+use dbatrs_shared::{Color, protocol::PagerControl};
+use dbatrs_portal::telnet::{codec::TelnetEvent, codes, conn::Msg2TelnetProtocol, listen::Msg2Listener};
+
+use common::Client;
+
+// Refuses LINEMODE and answers the server's offers of ECHO and SGA, which together put a
+// client in character mode.
+async fn connect(echo: u8, sga: u8) -> Client {
+    let mut client = Client::connect().await;
+    client.send(TelnetEvent::Negotiate(codes::WONT, codes::LINEMODE)).await;
+    client.send(TelnetEvent::Negotiate(echo, codes::ECHO)).await;
+    client.send(TelnetEvent::Negotiate(sga, codes::SGA)).await;
+    client.until_connected().await;
+    client
+}
+
+// Offers collected until the connection finishes negotiating.
+async fn offers(client: &mut Client) -> Vec<u8> {
+    let mut offered = Vec::new();
+    loop {
+        match client.next_event().await {
+            Some(TelnetEvent::Negotiate(codes::WILL, op)) => offered.push(op),
+            Some(TelnetEvent::Data(data)) if data.ends_with(b"Connected to game server.\r\n") => return offered,
+            Some(_) => {},
+            None => panic!("the connection never finished negotiating")
+        }
+    }
+}
+
+#[tokio::test]
+async fn server_offers_echo_once_linemode_is_refused() {
+    let mut client = Client::connect().await;
+    client.send(TelnetEvent::Negotiate(codes::WONT, codes::LINEMODE)).await;
+    let mut offered = Vec::new();
+    while offered.len() < 2 {
+        match client.next_event().await {
+            Some(TelnetEvent::Negotiate(codes::WILL, op)) if op == codes::ECHO || op == codes::SGA => offered.push(op),
+            Some(_) => {},
+            None => panic!("the server only offered {:?}", offered)
+        }
+    }
+}
+
+#[tokio::test]
+async fn server_offers_echo_when_linemode_goes_unanswered() {
+    let mut client = Client::connect().await;
+    assert!(offers(&mut client).await.contains(&codes::ECHO));
+}
+
+#[tokio::test]
+async fn line_editing_clients_are_not_offered_echo() {
+    let mut client = Client::connect().await;
+    client.send(TelnetEvent::Negotiate(codes::WILL, codes::LINEMODE)).await;
+    let offered = offers(&mut client).await;
+    assert!(offered.contains(&codes::SGA) && !offered.contains(&codes::ECHO), "{:?}", offered);
+}
+
+#[tokio::test]
+async fn character_mode_clients_are_echoed() {
+    let mut client = connect(codes::DO, codes::DO).await;
+    client.expect_text("Connected to game server.\r\n").await;
+    client.send_text("lo").await;
+    client.send_text("oc\x08k\r\n").await;
+    let text = client.expect_text("Invalid command.").await;
+    assert!(text.starts_with("looc\x08 \x08k\r\nInvalid command."), "{:?}", text);
+}
+
+#[tokio::test]
+async fn line_mode_clients_are_not_echoed() {
+    for (echo, sga) in [(codes::DONT, codes::DO), (codes::DO, codes::DONT)] {
+        let mut client = connect(echo, sga).await;
+        client.expect_text("Connected to game server.\r\n").await;
+        client.send_text("look\r\n").await;
+        let text = client.expect_text("Invalid command.").await;
+        assert!(text.starts_with("Invalid command."), "{:?}", text);
+    }
+}
+
+#[tokio::test]
+async fn over_long_lines_are_reported() {
+    let mut client = connect(codes::DO, codes::DO).await;
+    client.expect_text("Connected to game server.\r\n").await;
+    client.send_text(&"x".repeat(5000)).await;
+    client.send_text("\r\n").await;
+    client.expect_text("Line too long").await;
+}
+
+// A client that never reads, over a pipe too small for the negotiation to fit in.
+async fn stuck_client() -> Client {
+    let mut conf = common::conf();
+    conf.portal.max_output_buffer = 4096;
+    Client::connect_with(conf, 16).await
+}
+
+// The connection must finish, and tell the listener, even though its output is stuck.
+async fn assert_cut_off(mut client: Client) {
+    time::timeout(Duration::from_secs(5), &mut client.task).await
+        .expect("the connection waited forever on a client that doesn't read").unwrap();
+    let mut disconnected = false;
+    while let Ok(msg) = client.rx_listener.try_recv() {
+        disconnected |= matches!(msg, Msg2Listener::ClientDisconnected(1));
+    }
+    assert!(disconnected);
+}
+
+#[tokio::test]
+async fn overflowing_clients_are_cut_off() {
+    let client = stuck_client().await;
+    for _ in 0..100 {
+        if client.tx_protocol.send(Msg2TelnetProtocol::Text("x".repeat(100))).is_err() {
+            break;
+        }
+    }
+    assert_cut_off(client).await;
+}
+
+#[tokio::test]
+async fn disconnecting_clients_are_cut_off() {
+    let mut client = stuck_client().await;
+    client.socket.get_mut().shutdown().await.unwrap();
+    assert_cut_off(client).await;
+}
+
+fn ttype(name: &str) -> TelnetEvent {
+    let mut data = vec![0];
+    data.extend_from_slice(name.as_bytes());
+    TelnetEvent::SubNegotiate(codes::MTTS, Bytes::from(data))
+}
+
+#[tokio::test]
+async fn each_ttype_reply_updates_capabilities() {
+    let mut client = Client::connect().await;
+    client.send(TelnetEvent::Negotiate(codes::WILL, codes::MTTS)).await;
+    let send = TelnetEvent::SubNegotiate(codes::MTTS, Bytes::from_static(&[1]));
+
+    client.expect(send.clone()).await;
+    client.send(ttype("Mudlet 4.17")).await;
+    let capabilities = client.capabilities(|c| c.client_name == "MUDLET").await;
+    assert_eq!(capabilities.client_version, "4.17");
+    assert!(capabilities.ttype);
+
+    client.expect(send.clone()).await;
+    client.send(ttype("vt100")).await;
+    client.capabilities(|c| c.client_name == "MUDLET").await;
+
+    client.expect(send).await;
+    client.send(ttype("MTTS 261")).await;
+    let capabilities = client.capabilities(|c| c.utf8).await;
+    assert_eq!(capabilities.ansi_color, Color::TrueColor);
+}
+
+fn gmcp(message: &str) -> TelnetEvent {
+    TelnetEvent::SubNegotiate(codes::GMCP, Bytes::copy_from_slice(message.as_bytes()))
+}
+
+#[tokio::test]
+async fn gmcp_core_messages_update_capabilities() {
+    let mut client = Client::connect().await;
+    client.send(TelnetEvent::Negotiate(codes::DO, codes::GMCP)).await;
+    client.send(gmcp(r#"Core.Hello {"client": "Mudlet", "version": "4.17.2"}"#)).await;
+    let capabilities = client.capabilities(|c| c.client_name == "MUDLET").await;
+    assert_eq!(capabilities.client_version, "4.17.2");
+
+    client.send(gmcp(r#"Core.Supports.Set ["Char 1", "Room 1"]"#)).await;
+    client.capabilities(|c| c.gmcp_packages == ["Char 1", "Room 1"]).await;
+    client.send(gmcp(r#"Core.Supports.Add ["Char 2", "Comm.Channel 1"]"#)).await;
+    client.capabilities(|c| c.gmcp_packages == ["Room 1", "Char 2", "Comm.Channel 1"]).await;
+    client.send(gmcp(r#"core.supports.remove ["Room"]"#)).await;
+    client.capabilities(|c| c.gmcp_packages == ["Char 2", "Comm.Channel 1"]).await;
+}
+
+// Messages to the connection are handled in order, so once this text arrives, everything
+// sent before it has been too.
+async fn handled(client: &mut Client) {
+    client.tx_protocol.send(Msg2TelnetProtocol::Text("handled\n".to_string())).unwrap();
+    client.expect_text("handled\r\n").await;
+}
+
+// Sets the client's screen height, so a page of the pager is height - 2 lines.
+async fn set_height(client: &mut Client, height: u8) {
+    client.send(TelnetEvent::Negotiate(codes::WILL, codes::NAWS)).await;
+    client.send(TelnetEvent::SubNegotiate(codes::NAWS, Bytes::from(vec![0, 80, 0, height]))).await;
+    client.capabilities(|c| c.height == height as u16).await;
+}
+
+#[tokio::test]
+async fn the_pager_shows_a_page_at_a_time() {
+    let mut client = connect(codes::DONT, codes::DO).await;
+    client.expect_text("Connected to game server.\r\n").await;
+    set_height(&mut client, 5).await;
+
+    let text = (1..=7).map(|n| format!("line {}", n)).collect::<Vec<_>>().join("\n");
+    client.tx_protocol.send(Msg2TelnetProtocol::Pager(PagerControl::Show { text })).unwrap();
+    let page = client.expect_text("[4 more lines: Enter to continue, q to stop] ").await;
+    assert!(page.starts_with("line 1\r\nline 2\r\nline 3\r\n["), "{:?}", page);
+
+    // Any input moves on, without reaching the login.
+    client.send_text("look\r\n").await;
+    let page = client.expect_text("[1 more lines").await;
+    assert!(page.starts_with("line 4\r\nline 5\r\nline 6\r\n["), "{:?}", page);
+    client.send_text("\r\n").await;
+    let page = client.expect_text("line 7\r\n").await;
+    assert!(!page.contains("more lines"), "{:?}", page);
+
+    // With the text used up, input goes where it normally would.
+    client.send_text("look\r\n").await;
+    client.expect_text("Invalid command.").await;
+}
+
+#[tokio::test]
+async fn the_pager_can_be_stopped() {
+    let mut client = connect(codes::DONT, codes::DO).await;
+    client.expect_text("Connected to game server.\r\n").await;
+    set_height(&mut client, 5).await;
+    let text = (1..=20).map(|n| format!("line {}", n)).collect::<Vec<_>>().join("\n");
+
+    client.tx_protocol.send(Msg2TelnetProtocol::Pager(PagerControl::Show { text: text.clone() })).unwrap();
+    client.expect_text("[17 more lines").await;
+    client.send_text(" Q \r\n").await;
+    client.send_text("look\r\n").await;
+    let rest = client.expect_text("Invalid command.").await;
+    assert!(!rest.contains("line 4"), "{:?}", rest);
+
+    // The game can also clear it.
+    client.tx_protocol.send(Msg2TelnetProtocol::Pager(PagerControl::Show { text })).unwrap();
+    client.expect_text("[17 more lines").await;
+    client.tx_protocol.send(Msg2TelnetProtocol::Pager(PagerControl::Clear)).unwrap();
+    handled(&mut client).await;
+    client.send_text("look\r\n").await;
+    let rest = client.expect_text("Invalid command.").await;
+    assert!(!rest.contains("line 4"), "{:?}", rest);
+}
+
+#[tokio::test]
+async fn prompts_are_marked_the_way_the_client_asked() {
+    let prompted = |events: Vec<TelnetEvent>| matches!(events.last(), Some(TelnetEvent::Data(data)) if data.ends_with(b"> "));
+
+    // Without go-aheads suppressed, prompts end with GA.
+    let mut client = connect(codes::DONT, codes::DONT).await;
+    client.expect_text("Connected to game server.\r\n").await;
+    client.tx_protocol.send(Msg2TelnetProtocol::Prompt("> ".to_string())).unwrap();
+    assert!(prompted(client.expect(TelnetEvent::Command(codes::GA)).await));
+
+    // EOR takes precedence once the client agrees to it.
+    let mut client = Client::connect().await;
+    client.send(TelnetEvent::Negotiate(codes::DO, codes::TELOPT_EOR)).await;
+    client.until_connected().await;
+    client.expect_text("Connected to game server.\r\n").await;
+    client.tx_protocol.send(Msg2TelnetProtocol::Prompt("> ".to_string())).unwrap();
+    assert!(prompted(client.expect(TelnetEvent::Command(codes::EOR)).await));
+
+    // With go-aheads suppressed and no EOR, there's nothing after the prompt.
+    let mut client = connect(codes::DONT, codes::DO).await;
+    client.expect_text("Connected to game server.\r\n").await;
+    client.tx_protocol.send(Msg2TelnetProtocol::Prompt("> ".to_string())).unwrap();
+    client.tx_protocol.send(Msg2TelnetProtocol::Text("after".to_string())).unwrap();
+    let mut events = Vec::new();
+    while !events.iter().any(|e| matches!(e, TelnetEvent::Data(data) if data.ends_with(b"after"))) {
+        events.push(client.next_event().await.expect("never received the text after the prompt"));
+    }
+    assert!(!events.iter().any(|e| matches!(e, TelnetEvent::Command(_))), "{:?}", events);
+}
+
+#[tokio::test]
+async fn hiding_input_from_a_line_mode_client() {
+    let mut client = connect(codes::DONT, codes::DO).await;
+    client.expect_text("Connected to game server.\r\n").await;
+
+    // The client echoes for itself, so the server claims ECHO to stop it...
+    client.tx_protocol.send(Msg2TelnetProtocol::Echo(false)).unwrap();
+    client.expect(TelnetEvent::Negotiate(codes::WILL, codes::ECHO)).await;
+    client.send(TelnetEvent::Negotiate(codes::DO, codes::ECHO)).await;
+    // ...without echoing anything itself.
+    handled(&mut client).await;
+    client.send_text("hunter22\r\n").await;
+    let text = client.expect_text("Invalid command.").await;
+    assert!(!text.contains("hunter22"), "{:?}", text);
+
+    // And gives it back afterwards.
+    client.tx_protocol.send(Msg2TelnetProtocol::Echo(true)).unwrap();
+    client.expect(TelnetEvent::Negotiate(codes::WONT, codes::ECHO)).await;
+}
+
+#[tokio::test]
+async fn hiding_input_from_a_character_mode_client() {
+    let mut client = connect(codes::DO, codes::DO).await;
+    client.expect_text("Connected to game server.\r\n").await;
+
+    // The server already echoes, so it only has to stop.
+    client.tx_protocol.send(Msg2TelnetProtocol::Echo(false)).unwrap();
+    handled(&mut client).await;
+    client.send_text("hunter22\r\n").await;
+    let events = client.expect(TelnetEvent::Data(Bytes::from_static(b"Invalid command.\r\nChoices are \"register <email>=<password>\" or \"login <email>=<password>\"\r\n"))).await;
+    assert!(!events.iter().any(|e| matches!(e, TelnetEvent::Negotiate(_, codes::ECHO) | TelnetEvent::Data(_))), "{:?}", events);
+
+    client.tx_protocol.send(Msg2TelnetProtocol::Echo(true)).unwrap();
+    handled(&mut client).await;
+    client.send_text("ab\r\n").await;
+    let text = client.expect_text("Invalid command.").await;
+    assert!(text.starts_with("ab\r\n"), "{:?}", text);
+}
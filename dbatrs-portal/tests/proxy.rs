@@ -0,0 +1,63 @@
+use std::net::SocketAddr;
+
+use tokio::io::AsyncReadExt;
+
+use dbatrs_portal::telnet::proxy::read_header;
+
+// Parses a header from the front of input and returns the address plus whatever was left.
+async fn parse(input: &[u8]) -> (std::io::Result<Option<SocketAddr>>, Vec<u8>) {
+    let mut stream = input;
+    let result = read_header(&mut stream).await;
+    let mut rest = Vec::new();
+    stream.read_to_end(&mut rest).await.unwrap();
+    (result, rest)
+}
+
+#[tokio::test]
+async fn v1_tcp4_and_tcp6() {
+    let (addr, rest) = parse(b"PROXY TCP4 192.0.2.7 10.0.0.1 51234 7000\r\nlook\r\n").await;
+    assert_eq!(addr.unwrap(), Some("192.0.2.7:51234".parse().unwrap()));
+    assert_eq!(rest, b"look\r\n");
+
+    let (addr, _) = parse(b"PROXY TCP6 2001:db8::7 2001:db8::1 51234 7000\r\n").await;
+    assert_eq!(addr.unwrap(), Some("[2001:db8::7]:51234".parse().unwrap()));
+
+    let (addr, rest) = parse(b"PROXY UNKNOWN\r\n\xff\xfb\x01").await;
+    assert_eq!(addr.unwrap(), None);
+    assert_eq!(rest, b"\xff\xfb\x01");
+}
+
+#[tokio::test]
+async fn v2_tcp4_with_tlvs() {
+    let mut header = b"\r\n\r\n\0\r\nQUIT\n".to_vec();
+    header.extend([0x21, 0x11, 0x00, 0x10]);
+    header.extend([192, 0, 2, 7, 10, 0, 0, 1]);
+    header.extend(51234u16.to_be_bytes());
+    header.extend(7000u16.to_be_bytes());
+    // A NOOP TLV, which should be skipped.
+    header.extend([0x04, 0x00, 0x01, 0x00]);
+    header.extend(b"look");
+
+    let (addr, rest) = parse(&header).await;
+    assert_eq!(addr.unwrap(), Some("192.0.2.7:51234".parse().unwrap()));
+    assert_eq!(rest, b"look");
+}
+
+#[tokio::test]
+async fn v2_local() {
+    let mut header = b"\r\n\r\n\0\r\nQUIT\n".to_vec();
+    header.extend([0x20, 0x00, 0x00, 0x00]);
+    let (addr, _) = parse(&header).await;
+    assert_eq!(addr.unwrap(), None);
+}
+
+#[tokio::test]
+async fn rejects_missing_or_malformed_headers() {
+    assert!(parse(b"look around the room\r\n").await.0.is_err());
+    assert!(parse(b"PROXY TCP4 192.0.2.7\r\n").await.0.is_err());
+    assert!(parse(b"PROXY TCP4 2001:db8::7 2001:db8::1 51234 7000\r\n").await.0.is_err());
+
+    let mut endless = b"PROXY TCP4 ".to_vec();
+    endless.extend([b'1'; 200]);
+    assert!(parse(&endless).await.0.is_err());
+}
@@ -0,0 +1,73 @@
+use chrono::Utc;
+use surrealdb::RecordId;
+use tokio::sync::mpsc;
+
+use dbatrs_shared::ProtocolCapabilities;
+use dbatrs_portal::telnet::registry::{ConnectionInfo, ConnectionRegistry, KickTarget, LoginState};
+
+fn info(id: usize, address: &str) -> ConnectionInfo {
+    ConnectionInfo {
+        id,
+        address: address.parse().unwrap(),
+        hostnames: Vec::new(),
+        capabilities: ProtocolCapabilities::with_custom_defaults(),
+        login: LoginState::Negotiating,
+        conn: None,
+        user: None,
+        time_connected: Utc::now(),
+        time_activity: Utc::now()
+    }
+}
+
+fn registry() -> ConnectionRegistry {
+    let mut registry = ConnectionRegistry::default();
+    for address in ["192.0.2.7:4000", "192.0.2.8:4000", "192.0.2.7:4001"] {
+        let id = registry.next_id();
+        registry.insert(info(id, address), mpsc::unbounded_channel().0);
+    }
+    registry
+}
+
+#[test]
+fn ids_are_never_reused() {
+    let mut registry = registry();
+    assert_eq!(registry.list().iter().map(|info| info.id).collect::<Vec<_>>(), vec![1, 2, 3]);
+    assert_eq!(registry.remove(3).map(|handle| handle.info.id), Some(3));
+    assert!(registry.remove(3).is_none());
+    assert_eq!(registry.next_id(), 4);
+    assert_eq!(registry.len(), 2);
+}
+
+#[test]
+fn matching_by_id_or_address() {
+    let registry = registry();
+    let ids = |target| {
+        let mut ids: Vec<usize> = registry.matching(target).iter().map(|handle| handle.info.id).collect();
+        ids.sort();
+        ids
+    };
+    assert_eq!(ids(KickTarget::Id(2)), vec![2]);
+    assert_eq!(ids(KickTarget::Id(9)), Vec::<usize>::new());
+    assert_eq!(ids(KickTarget::Ip("192.0.2.7".parse().unwrap())), vec![1, 3]);
+}
+
+#[test]
+fn finding_a_conn() {
+    let mut registry = registry();
+    let conn = RecordId::from(("conn", "abc"));
+    assert!(registry.find_conn(&conn).is_none());
+    registry.set_session(2, conn.clone(), RecordId::from(("user", "bob")));
+    assert_eq!(registry.find_conn(&conn).map(|handle| handle.info.id), Some(2));
+
+    // A new session replaces the old conn.
+    let other = RecordId::from(("conn", "def"));
+    registry.set_session(2, other.clone(), RecordId::from(("user", "bob")));
+    assert!(registry.find_conn(&conn).is_none());
+    assert_eq!(registry.find_conn(&other).map(|handle| handle.info.id), Some(2));
+    registry.set_session(2, conn.clone(), RecordId::from(("user", "bob")));
+
+    registry.remove(2);
+    assert!(registry.find_conn(&conn).is_none());
+    assert!(registry.get_mut(2).is_none());
+    assert!(!registry.is_empty());
+}
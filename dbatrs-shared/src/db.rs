@@ -0,0 +1,41 @@
+use surrealdb::{
+    Surreal,
+    engine::any::{self, Any},
+    opt::{
+        auth::Root,
+        capabilities::{Capabilities, ExperimentalFeature},
+        Config
+    }
+};
+
+use crate::{SurrealEngine, TotalConf};
+
+// The endpoint for surrealdb::engine::any, e.g. ws://127.0.0.1:8000 or mem://.
+pub fn endpoint(conf: &TotalConf) -> String {
+    let surreal = &conf.surreal;
+    match surreal.engine {
+        SurrealEngine::Remote if surreal.tls => format!("wss://{}", surreal.address),
+        SurrealEngine::Remote => format!("ws://{}", surreal.address),
+        SurrealEngine::Memory => "mem://".to_string(),
+        SurrealEngine::SurrealKv => format!("surrealkv://{}", conf.resolve_path(&surreal.path).display())
+    }
+}
+
+// Connects to SurrealDB as root, for maintenance work that isn't tied to a user.
+// Embedded engines have no users and already run with full access.
+pub async fn connect_root(conf: &TotalConf) -> Result<Surreal<Any>, surrealdb::Error> {
+    // The account access uses WITH REFRESH, which needs bearer access. Servers enable it
+    // on the command line; embedded engines take it from here.
+    let capabilities = Capabilities::default().with_experimental_feature_allowed(ExperimentalFeature::BearerAccess);
+    let db = any::connect((endpoint(conf), Config::new().capabilities(capabilities))).await?;
+
+    if !conf.surreal.engine.is_embedded() {
+        db.signin(Root {
+            username: &conf.surreal.username,
+            password: &conf.surreal.password
+        }).await?;
+    }
+
+    db.use_ns(&conf.surreal.namespace).use_db(&conf.surreal.database).await?;
+    Ok(db)
+}
@@ -1,42 +1,348 @@
+use std::{
+    fmt,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    str::FromStr
+};
+
 use serde::{Deserialize, Serialize};
-use config::{Config, File, FileFormat, ConfigError};
+use config::{Config, Environment, File, FileFormat, ConfigError};
 use chrono::{DateTime, Utc};
 use surrealdb::{Notification, Error, RecordId};
 
-use serde_json::Value as JsonValue;
+pub mod db;
+pub mod logging;
+pub mod migrate;
+pub mod protocol;
+pub mod shutdown;
+pub mod transport;
+
+use logging::LoggingConf;
+use transport::{TransportConf, TransportKind};
+
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SurrealEngine {
+    // A SurrealDB server at surreal.address.
+    #[default]
+    Remote,
+    // An embedded in-memory database. Everything is lost on exit.
+    Memory,
+    // An embedded on-disk database at surreal.path.
+    SurrealKv
+}
+
+impl SurrealEngine {
+    // Embedded databases live inside one process, so only dbatrs-dev can use them.
+    pub fn is_embedded(&self) -> bool {
+        *self != SurrealEngine::Remote
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, Default)]
 pub struct SurrealConf {
+    pub engine: SurrealEngine,
+    // Relative to the configuration directory. Only used by the surrealkv engine.
+    pub path: String,
     pub address: String,
     pub tls: bool,
     pub namespace: String,
     pub database: String,
     pub username: String,
-    pub password: String
+    pub password: String,
+    // Apply pending schema migrations at startup. When off, startup fails until the
+    // migrate command has been run.
+    pub auto_migrate: bool
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ListenerProtocol {
+    // Plain telnet.
+    #[default]
+    Telnet,
+    // Telnet over TLS, using the listener's tls_cert and tls_key.
+    Tls,
+    // Telnet carried in WebSocket messages, for browser clients.
+    WebSocket
+}
+
+// Where a listener accepts connections.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ListenAddr {
+    Tcp(SocketAddr),
+    // A socket path, relative to the configuration directory.
+    Unix(String)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, Default)]
+#[serde(default)]
+pub struct ListenerConf {
+    // ip:port, [ipv6]:port, or unix:<path> for a Unix socket.
+    pub address: String,
+    pub protocol: ListenerProtocol,
+    // Expect a PROXY protocol header (v1 or v2) ahead of anything else and take the client's
+    // address from it. Only for listeners that nothing but the proxy can reach.
+    pub proxy: bool,
+    // Sent to each client as soon as it connects, before option negotiation.
+    pub banner: String,
+    // PEM files for the tls protocol, relative to the configuration directory.
+    pub tls_cert: String,
+    pub tls_key: String
+}
+
+impl ListenerConf {
+    pub fn listen_addr(&self) -> Option<ListenAddr> {
+        if let Some(path) = self.address.strip_prefix("unix:") {
+            return (!path.trim().is_empty()).then(|| ListenAddr::Unix(path.to_string()));
+        }
+        SocketAddr::from_str(&self.address).ok().map(ListenAddr::Tcp)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, Default)]
 pub struct PortalConf {
-    pub telnet: String
+    // Identifies this portal instance. Its conn records are tagged with it, so it can
+    // clean up after itself if it crashes.
+    pub id: String,
+    // Every address clients can connect on. All of them share one connection registry.
+    pub listeners: Vec<ListenerConf>,
+    // A client whose unsent output grows past this many bytes is disconnected.
+    pub max_output_buffer: usize,
+    // GMCP packages that only carry the latest state, so stale updates can be collapsed
+    // or dropped for clients that fall behind.
+    pub low_priority_gmcp: Vec<String>,
+    // Sent to every connected client when the portal shuts down.
+    pub shutdown_message: String,
+    // Seconds to wait for connections to close cleanly before exiting anyway.
+    pub shutdown_timeout: u64,
+    // Look up each client's hostnames. A slow nameserver only delays new connections.
+    pub reverse_dns: bool,
+    // Seconds a closing connection gets to flush its output before it's cut off anyway.
+    pub drain_timeout: u64,
+    // Seconds between each connection refreshing its conn record's time_system_activity.
+    pub heartbeat_interval: u64,
+    // Seconds without a heartbeat before a conn record is considered abandoned.
+    pub stale_timeout: u64,
+    // Seconds between sweeps for abandoned conn records.
+    pub sweep_interval: u64
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, Default)]
+pub struct GameConf {
+    // How many times a second the game loop runs. Everything else here is in pulses.
+    pub pulses_per_second: u32,
+    pub violence_pulses: u64,
+    pub mobile_pulses: u64,
+    pub zone_pulses: u64,
+    pub affect_pulses: u64,
+    pub autosave_pulses: u64,
+    // A directory of legacy CircleMUD-format world files to import at startup if the
+    // database has no rooms yet, relative to the config directory. Empty for none.
+    pub legacy_world: String,
+    // The room vnum characters start in when they aren't anywhere yet.
+    pub start_room: usize
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, Default)]
 pub struct TotalConf {
     pub surreal: SurrealConf,
-    pub portal: PortalConf
+    pub portal: PortalConf,
+    pub game: GameConf,
+    pub transport: TransportConf,
+    pub logging: LoggingConf,
 
+    // The directory the configuration was loaded from. Relative paths elsewhere in the
+    // configuration are resolved against it.
+    #[serde(skip)]
+    pub config_dir: PathBuf
 }
 
-// A global that will be set *once*
+#[derive(Debug)]
+pub enum ConfError {
+    // The files or environment couldn't be read or didn't match the expected structure.
+    Load(ConfigError),
+    // Everything loaded, but these fields have unusable values.
+    Invalid(Vec<String>)
+}
+
+impl fmt::Display for ConfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfError::Load(e) => write!(f, "Could not load configuration: {}", e),
+            ConfError::Invalid(problems) => {
+                writeln!(f, "Configuration has {} problem(s):", problems.len())?;
+                for problem in problems {
+                    writeln!(f, "  {}", problem)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfError {}
+
+impl From<ConfigError> for ConfError {
+    fn from(e: ConfigError) -> Self {
+        ConfError::Load(e)
+    }
+}
+
+// Command-line options shared by every binary for locating and checking configuration.
+#[derive(clap::Args, Debug, Clone)]
+pub struct ConfArgs {
+    /// Which config.<mode>.toml to layer over config.default.toml.
+    #[arg(long, default_value = "devel")]
+    pub mode: String,
+    /// The directory holding the config.*.toml files.
+    #[arg(long, default_value = ".")]
+    pub config_dir: PathBuf,
+    /// Load and validate the configuration, report the result, then exit.
+    #[arg(long)]
+    pub check_config: bool
+}
+
+impl ConfArgs {
+    pub fn load(&self) -> Result<TotalConf, ConfError> {
+        let conf = TotalConf::load(&self.config_dir, &self.mode)?;
+        conf.validate()?;
+        Ok(conf)
+    }
+}
 
 impl TotalConf {
-    pub fn set(mode: &str) -> Result<Self, ConfigError> {
-        Config::builder()
-            .add_source(File::new("config.default.toml", FileFormat::Toml).required(true))
-            .add_source(File::new(&format!("config.{}.toml", mode), FileFormat::Toml).required(true))
-            .add_source(File::new("config.user.toml", FileFormat::Toml).required(false))
-            .add_source(File::new(&format!("config.user.{}.toml", mode), FileFormat::Toml).required(false))
-            .build()?.try_deserialize()
+    // Files are layered in this order, each overriding the last:
+    // config.default.toml, config.<mode>.toml, config.user.toml, config.user.<mode>.toml,
+    // and finally DBATRS__SECTION__KEY environment variables.
+    pub fn load(dir: &Path, mode: &str) -> Result<Self, ConfError> {
+        let file = |name: String| File::from(dir.join(name)).format(FileFormat::Toml);
+
+        let mut conf: Self = Config::builder()
+            .add_source(file("config.default.toml".to_string()).required(true))
+            .add_source(file(format!("config.{}.toml", mode)).required(true))
+            .add_source(file("config.user.toml".to_string()).required(false))
+            .add_source(file(format!("config.user.{}.toml", mode)).required(false))
+            .add_source(Environment::with_prefix("DBATRS")
+                .prefix_separator("__")
+                .separator("__")
+                .try_parsing(true)
+                .list_separator(",")
+                .with_list_parse_key("portal.low_priority_gmcp"))
+            .build()?.try_deserialize()?;
+
+        conf.config_dir = dir.to_path_buf();
+        Ok(conf)
+    }
+
+    // Checks every field up front, so a bad value is reported at startup instead of
+    // causing a panic the first time something uses it.
+    pub fn validate(&self) -> Result<(), ConfError> {
+        let mut problems = Vec::new();
+
+        if self.surreal.engine == SurrealEngine::SurrealKv && self.surreal.path.trim().is_empty() {
+            problems.push("surreal.path: must not be empty for the surrealkv engine".to_string());
+        }
+        if !is_host_port(&self.surreal.address) {
+            problems.push(format!("surreal.address: '{}' is not a valid host:port", self.surreal.address));
+        }
+        for (name, value) in [("surreal.namespace", &self.surreal.namespace),
+                              ("surreal.database", &self.surreal.database),
+                              ("surreal.username", &self.surreal.username)] {
+            if value.trim().is_empty() {
+                problems.push(format!("{}: must not be empty", name));
+            }
+        }
+
+        if self.portal.listeners.is_empty() {
+            problems.push("portal.listeners: at least one listener is required".to_string());
+        }
+        for (i, listener) in self.portal.listeners.iter().enumerate() {
+            match listener.listen_addr() {
+                None => problems.push(format!("portal.listeners[{}].address: '{}' is not ip:port or unix:<path>",
+                                              i, listener.address)),
+                Some(ListenAddr::Unix(_)) if cfg!(not(unix)) => {
+                    problems.push(format!("portal.listeners[{}].address: Unix sockets aren't supported on this platform", i));
+                },
+                Some(_) => {}
+            }
+            if listener.protocol == ListenerProtocol::Tls &&
+                (listener.tls_cert.trim().is_empty() || listener.tls_key.trim().is_empty()) {
+                problems.push(format!("portal.listeners[{}]: tls_cert and tls_key are required for the tls protocol", i));
+            }
+        }
+        if self.portal.max_output_buffer == 0 {
+            problems.push("portal.max_output_buffer: must be greater than zero".to_string());
+        }
+        if self.portal.shutdown_timeout == 0 || self.portal.drain_timeout == 0 {
+            problems.push("portal.shutdown_timeout and portal.drain_timeout: must be greater than zero".to_string());
+        }
+        if self.portal.id.is_empty() || !self.portal.id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            problems.push(format!("portal.id: '{}' must be letters, digits, '-' and '_' only", self.portal.id));
+        }
+        if self.portal.heartbeat_interval == 0 || self.portal.sweep_interval == 0 {
+            problems.push("portal.heartbeat_interval and portal.sweep_interval: must be greater than zero".to_string());
+        }
+        if self.portal.stale_timeout <= self.portal.heartbeat_interval {
+            problems.push("portal.stale_timeout: must be longer than portal.heartbeat_interval".to_string());
+        }
+
+        match self.transport.kind {
+            TransportKind::Surreal => {},
+            TransportKind::Tcp => {
+                if !is_host_port(&self.transport.address) {
+                    problems.push(format!("transport.address: '{}' is not a valid host:port", self.transport.address));
+                }
+            },
+            TransportKind::Unix => {
+                if self.transport.address.trim().is_empty() {
+                    problems.push("transport.address: must be a socket path for the unix transport".to_string());
+                }
+            }
+        }
+        if self.transport.reconnect_interval == 0 {
+            problems.push("transport.reconnect_interval: must be greater than zero".to_string());
+        }
+
+        if self.game.pulses_per_second == 0 || self.game.pulses_per_second > 1000 {
+            problems.push(format!("game.pulses_per_second: {} is not between 1 and 1000", self.game.pulses_per_second));
+        }
+        for (name, value) in [("game.violence_pulses", self.game.violence_pulses),
+                              ("game.mobile_pulses", self.game.mobile_pulses),
+                              ("game.zone_pulses", self.game.zone_pulses),
+                              ("game.affect_pulses", self.game.affect_pulses),
+                              ("game.autosave_pulses", self.game.autosave_pulses)] {
+            if value == 0 {
+                problems.push(format!("{}: must be greater than zero", name));
+            }
+        }
+
+        self.logging.validate(&mut problems);
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfError::Invalid(problems))
+        }
+    }
+
+    // Resolves a path from the configuration against the configuration directory.
+    pub fn resolve_path(&self, path: &str) -> PathBuf {
+        self.config_dir.join(path)
+    }
+}
+
+// Accepts host:port where host is a name or IP address, since SurrealDB may be remote.
+fn is_host_port(address: &str) -> bool {
+    if SocketAddr::from_str(address).is_ok() {
+        return true;
+    }
+    match address.rsplit_once(':') {
+        Some((host, port)) => {
+            !host.is_empty() && !host.contains(char::is_whitespace) && port.parse::<u16>().is_ok()
+        },
+        None => false
     }
 }
 
@@ -85,6 +391,9 @@ pub struct ProtocolCapabilities {
     pub osc_color_palette: bool,
     pub proxy: bool,
     pub mnes: bool,
+    // The GMCP packages the client asked for with Core.Supports, as "<package> <version>".
+    #[serde(default)]
+    pub gmcp_packages: Vec<String>,
 }
 
 impl ProtocolCapabilities {
@@ -108,17 +417,12 @@ pub struct Conn {
     pub time_created: DateTime<Utc>,
     pub time_system_activity: DateTime<Utc>,
     pub time_user_activity: DateTime<Utc>,
-}
-
-#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
-pub struct ConnOutput {
-    pub id: RecordId,
-    pub user: RecordId,
-    pub conn: RecordId,
-    pub time_created: DateTime<Utc>,
-    pub data_type: String,
-    pub command: String,
-    pub gmcp: JsonValue
+    // The ID of the portal instance that owns this connection.
+    #[serde(default)]
+    pub portal: String,
+    // Written by the portal once negotiation finishes, and again whenever it changes.
+    #[serde(default)]
+    pub capabilities: Option<ProtocolCapabilities>,
 }
 
 #[derive(Serialize)]
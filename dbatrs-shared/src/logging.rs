@@ -0,0 +1,267 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::PathBuf,
+    str::FromStr,
+    sync::{Arc, Mutex}
+};
+
+use chrono::{Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::{
+    filter::Targets,
+    fmt::{self, MakeWriter},
+    layer::SubscriberExt,
+    registry::Registry,
+    Layer
+};
+
+use crate::TotalConf;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LogRotation {
+    // One file per day, named <name>.<YYYY-MM-DD>.log.
+    #[default]
+    Daily,
+    // <name>.log, moved aside to <name>.<timestamp>.log once it reaches max_size.
+    Size,
+    // <name>.log, appended to forever.
+    Never
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub struct LoggingConf {
+    // The level for any target not listed in targets.
+    pub level: String,
+    // Per-target overrides, e.g. surrealdb = "warn".
+    pub targets: std::collections::HashMap<String, String>,
+    pub json: bool,
+    // Write to files in directory as well as stdout.
+    pub file: bool,
+    // Relative to the configuration directory.
+    pub directory: String,
+    pub rotation: LogRotation,
+    // Size in bytes at which a size-rotated log is moved aside.
+    pub max_size: u64,
+    // How many rotated files to keep per binary. Zero keeps everything.
+    pub max_files: usize,
+    // Feed spans and events to the Tracy profiler.
+    pub tracy: bool
+}
+
+impl LoggingConf {
+    pub fn validate(&self, problems: &mut Vec<String>) {
+        if LevelFilter::from_str(&self.level).is_err() {
+            problems.push(format!("logging.level: '{}' is not a log level", self.level));
+        }
+        for (target, level) in self.targets.iter() {
+            if LevelFilter::from_str(level).is_err() {
+                problems.push(format!("logging.targets.{}: '{}' is not a log level", target, level));
+            }
+        }
+        if self.file && self.directory.trim().is_empty() {
+            problems.push("logging.directory: must not be empty when logging.file is enabled".to_string());
+        }
+        if self.file && self.rotation == LogRotation::Size && self.max_size == 0 {
+            problems.push("logging.max_size: must be greater than zero for size rotation".to_string());
+        }
+    }
+
+    fn targets(&self) -> Targets {
+        let default = LevelFilter::from_str(&self.level).unwrap_or(LevelFilter::INFO);
+        self.targets.iter().fold(Targets::new().with_default(default), |targets, (target, level)| {
+            targets.with_target(target.clone(), LevelFilter::from_str(level).unwrap_or(default))
+        })
+    }
+}
+
+struct RollingState {
+    file: Option<File>,
+    // The date of the open file, for daily rotation.
+    date: NaiveDate,
+    // Bytes in the open file, for size rotation.
+    size: u64,
+    // The timestamp and number of the last file moved aside, for size rotation.
+    last_rotated: (String, usize)
+}
+
+// A log file writer that rotates by day or by size. Cloned into every event write.
+#[derive(Clone)]
+pub struct RollingWriter {
+    directory: PathBuf,
+    name: String,
+    rotation: LogRotation,
+    max_size: u64,
+    max_files: usize,
+    state: Arc<Mutex<RollingState>>
+}
+
+impl RollingWriter {
+    pub fn new(directory: PathBuf, name: &str, rotation: LogRotation, max_size: u64, max_files: usize) -> io::Result<Self> {
+        fs::create_dir_all(&directory)?;
+        let writer = Self {
+            directory,
+            name: name.to_string(),
+            rotation,
+            max_size,
+            max_files,
+            state: Arc::new(Mutex::new(RollingState {
+                file: None,
+                date: Local::now().date_naive(),
+                size: 0,
+                last_rotated: Default::default()
+            }))
+        };
+        {
+            let mut state = writer.state.lock().unwrap();
+            writer.open(&mut state)?;
+        }
+        Ok(writer)
+    }
+
+    fn current_path(&self, date: NaiveDate) -> PathBuf {
+        match self.rotation {
+            LogRotation::Daily => self.directory.join(format!("{}.{}.log", self.name, date.format("%Y-%m-%d"))),
+            _ => self.directory.join(format!("{}.log", self.name))
+        }
+    }
+
+    fn open(&self, state: &mut RollingState) -> io::Result<()> {
+        let path = self.current_path(state.date);
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        state.size = file.metadata().map(|m| m.len()).unwrap_or(0);
+        state.file = Some(file);
+        Ok(())
+    }
+
+    fn rotate_if_needed(&self, state: &mut RollingState) -> io::Result<()> {
+        match self.rotation {
+            LogRotation::Daily => {
+                let today = Local::now().date_naive();
+                if today != state.date {
+                    state.date = today;
+                    self.open(state)?;
+                    self.prune();
+                }
+            },
+            LogRotation::Size => {
+                if state.size >= self.max_size {
+                    state.file = None;
+                    let stamp = Local::now().format("%Y%m%d-%H%M%S%.3f").to_string();
+                    // Files moved aside within the same millisecond are numbered after the
+                    // first, even if pruning has since removed it. '_' sorts after '.', so the
+                    // names still sort in the order the files were written.
+                    let mut count = if state.last_rotated.0 == stamp { state.last_rotated.1 + 1 } else { 0 };
+                    let rotated = loop {
+                        let rotated = match count {
+                            0 => self.directory.join(format!("{}.{}.log", self.name, stamp)),
+                            _ => self.directory.join(format!("{}.{}_{:03}.log", self.name, stamp, count))
+                        };
+                        if !rotated.exists() {
+                            break rotated;
+                        }
+                        count += 1;
+                    };
+                    fs::rename(self.current_path(state.date), rotated)?;
+                    state.last_rotated = (stamp, count);
+                    self.open(state)?;
+                    self.prune();
+                }
+            },
+            LogRotation::Never => {}
+        }
+        Ok(())
+    }
+
+    // Deletes the oldest rotated files beyond max_files. The timestamps in the names sort
+    // chronologically, so sorting by name is enough.
+    fn prune(&self) {
+        if self.max_files == 0 {
+            return;
+        }
+        let live = self.directory.join(format!("{}.log", self.name));
+        let prefix = format!("{}.", self.name);
+        let Ok(entries) = fs::read_dir(&self.directory) else {
+            return;
+        };
+        let mut rotated: Vec<PathBuf> = entries
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| *p != live)
+            .filter(|p| p.file_name()
+                .and_then(|n| n.to_str())
+                .map(|n| n.starts_with(&prefix) && n.ends_with(".log"))
+                .unwrap_or(false))
+            .collect();
+        rotated.sort();
+        while rotated.len() > self.max_files {
+            let _ = fs::remove_file(rotated.remove(0));
+        }
+    }
+}
+
+impl Write for RollingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut state = self.state.lock().unwrap();
+        self.rotate_if_needed(&mut state)?;
+        let written = match state.file.as_mut() {
+            Some(file) => file.write(buf)?,
+            None => buf.len()
+        };
+        state.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        match state.file.as_mut() {
+            Some(file) => file.flush(),
+            None => Ok(())
+        }
+    }
+}
+
+impl<'a> MakeWriter<'a> for RollingWriter {
+    type Writer = RollingWriter;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+type BoxedLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+// Installs the global tracing subscriber for a binary. name is used for log file names,
+// e.g. "portal" writes logs/portal.2025-01-01.log.
+pub fn init(conf: &TotalConf, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let logging = &conf.logging;
+    let mut layers: Vec<BoxedLayer> = Vec::new();
+
+    let stdout = fmt::layer()
+        .with_target(true)
+        .with_level(true);
+    layers.push(if logging.json { stdout.json().boxed() } else { stdout.boxed() });
+
+    if logging.file {
+        let writer = RollingWriter::new(conf.resolve_path(&logging.directory), name,
+                                        logging.rotation, logging.max_size, logging.max_files)?;
+        let file = fmt::layer()
+            .with_target(true)
+            .with_level(true)
+            .with_ansi(false)
+            .with_writer(writer);
+        layers.push(if logging.json { file.json().boxed() } else { file.boxed() });
+    }
+
+    if logging.tracy {
+        layers.push(tracy_full::tracing::TracyLayer.boxed());
+    }
+
+    let subscriber = tracing_subscriber::registry()
+        .with(layers)
+        .with(logging.targets());
+
+    tracing::subscriber::set_global_default(subscriber)?;
+    Ok(())
+}
@@ -0,0 +1,177 @@
+use std::fmt;
+
+use serde::Deserialize;
+use surrealdb::{Surreal, Connection};
+use tracing::info;
+
+use crate::SurrealConf;
+
+// A numbered schema change. Versions must be consecutive starting from 1. Nothing has been
+// released yet, so a broken migration is fixed where it is rather than patched by a later
+// one; once released, a migration must never be edited again.
+pub struct Migration {
+    pub version: u32,
+    pub name: &'static str,
+    pub script: &'static str
+}
+
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial",
+        script: include_str!("../../data/migrations/0001_initial.surql")
+    },
+    Migration {
+        version: 2,
+        name: "transport",
+        script: include_str!("../../data/migrations/0002_transport.surql")
+    },
+    Migration {
+        version: 3,
+        name: "legacy_accounts",
+        script: include_str!("../../data/migrations/0003_legacy_accounts.surql")
+    },
+    Migration {
+        version: 4,
+        name: "persistence",
+        script: include_str!("../../data/migrations/0004_persistence.surql")
+    },
+];
+
+// The newest schema version this build knows about.
+pub fn latest_version() -> u32 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+#[derive(Debug)]
+pub enum MigrateError {
+    Db(surrealdb::Error),
+    // The database has migrations applied that this build doesn't have, so it was
+    // migrated by newer code. Running against it could corrupt data.
+    TooNew { database: u32, code: u32 },
+    // Pending migrations exist, but surreal.auto_migrate is off.
+    Pending { database: u32, code: u32 }
+}
+
+impl fmt::Display for MigrateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrateError::Db(e) => write!(f, "Database error during migration: {}", e),
+            MigrateError::TooNew { database, code } => {
+                write!(f, "Database schema is at version {}, but this build only knows up to version {}. Refusing to start.", database, code)
+            },
+            MigrateError::Pending { database, code } => {
+                write!(f, "Database schema is at version {} but this build needs version {}. Run the migrate command or enable surreal.auto_migrate.", database, code)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MigrateError {}
+
+impl From<surrealdb::Error> for MigrateError {
+    fn from(e: surrealdb::Error) -> Self {
+        MigrateError::Db(e)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AppliedMigration {
+    pub version: u32,
+    pub name: String,
+    pub time_applied: chrono::DateTime<chrono::Utc>
+}
+
+// The table is created by the first write, so an empty or missing table means version 0.
+pub async fn applied<C: Connection>(db: &Surreal<C>) -> Result<Vec<AppliedMigration>, MigrateError> {
+    let mut response = db.query("SELECT version, name, <string> time_applied AS time_applied FROM schema_migration ORDER BY version").await?;
+    Ok(response.take(0)?)
+}
+
+pub async fn current_version<C: Connection>(db: &Surreal<C>) -> Result<u32, MigrateError> {
+    Ok(applied(db).await?.iter().map(|m| m.version).max().unwrap_or(0))
+}
+
+// Applies every pending migration in order, each in its own transaction along with its
+// schema_migration record. Returns the versions that were applied here, which leaves out
+// any that another process starting at the same time applied first.
+pub async fn migrate<C: Connection>(db: &Surreal<C>) -> Result<Vec<u32>, MigrateError> {
+    let database = current_version(db).await?;
+    let code = latest_version();
+    if database > code {
+        return Err(MigrateError::TooNew { database, code });
+    }
+
+    let mut done = Vec::new();
+    for migration in MIGRATIONS.iter().filter(|m| m.version > database) {
+        info!("Applying schema migration {:04}_{}...", migration.version, migration.name);
+        let query = format!("BEGIN TRANSACTION;
+            {}
+            CREATE type::thing('schema_migration', $version) SET version = $version, name = $name, time_applied = time::now();
+            COMMIT TRANSACTION;", migration.script);
+        let result = match db.query(query).bind(("version", migration.version)).bind(("name", migration.name)).await {
+            Ok(response) => response.check().map(|_| ()),
+            Err(e) => Err(e)
+        };
+        if let Err(e) = result {
+            // If someone else got there first, our transaction failed on their
+            // schema_migration record and was rolled back, and the schema is as it should be.
+            if applied(db).await?.iter().any(|m| m.version == migration.version && m.name == migration.name) {
+                info!("Schema migration {:04}_{} was applied by another process.", migration.version, migration.name);
+                continue;
+            }
+            return Err(e.into());
+        }
+        done.push(migration.version);
+    }
+    Ok(done)
+}
+
+// Run by every binary at startup. Applies pending migrations if auto_migrate is set;
+// either way, refuses to continue unless the schema matches this build.
+pub async fn startup<C: Connection>(db: &Surreal<C>, conf: &SurrealConf) -> Result<(), MigrateError> {
+    if conf.auto_migrate {
+        let done = migrate(db).await?;
+        if !done.is_empty() {
+            info!("Database schema migrated to version {}.", latest_version());
+        }
+        return Ok(());
+    }
+
+    let database = current_version(db).await?;
+    let code = latest_version();
+    if database > code {
+        Err(MigrateError::TooNew { database, code })
+    } else if database < code {
+        Err(MigrateError::Pending { database, code })
+    } else {
+        Ok(())
+    }
+}
+
+// The migrate command, shared by every binary: lists applied and pending migrations with
+// status, otherwise applies whatever is pending.
+pub async fn run_migrate<C: Connection>(db: &Surreal<C>, status: bool) -> Result<(), MigrateError> {
+    if status {
+        let applied = applied(db).await?;
+        for m in applied.iter() {
+            println!("applied  {:04}_{} ({})", m.version, m.name, m.time_applied);
+        }
+        let current = applied.iter().map(|m| m.version).max().unwrap_or(0);
+        for m in MIGRATIONS.iter().filter(|m| m.version > current) {
+            println!("pending  {:04}_{}", m.version, m.name);
+        }
+        if current > latest_version() {
+            println!("Database schema version {} is newer than this build ({}).", current, latest_version());
+        }
+        return Ok(());
+    }
+
+    let done = migrate(db).await?;
+    if done.is_empty() {
+        println!("Database schema is up to date at version {}.", latest_version());
+    } else {
+        println!("Applied {} migration(s); database schema is now at version {}.", done.len(), latest_version());
+    }
+    Ok(())
+}
@@ -0,0 +1,65 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use surrealdb::RecordId;
+
+use crate::ProtocolCapabilities;
+
+// Bump whenever ToGame or ToPortal change in a way an older peer would misread. Peers
+// with different versions refuse to talk to each other.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+// Sent from the portal to the game about one of its connections.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ToGame {
+    // The first message on a direct link. Its shape must never change, so that any
+    // version can read it.
+    Hello { version: u32, portal: String },
+    // A user has logged in and their conn record exists.
+    Connected { conn: RecordId, user: RecordId },
+    Capabilities { conn: RecordId, capabilities: ProtocolCapabilities },
+    Input { conn: RecordId, command: String },
+    Gmcp { conn: RecordId, package: String, data: JsonValue },
+    Disconnected { conn: RecordId }
+}
+
+// Sent from the game to the portal, mostly for one connection.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ToPortal {
+    // The game's reply to a Hello it accepts. Like ToGame::Hello, it must never change.
+    Hello { version: u32 },
+    // The game's reply to a Hello it won't accept.
+    Reject { reason: String },
+    Text { conn: RecordId, text: String },
+    // Text that doesn't end in a newline and should be followed by GA or EOR.
+    Prompt { conn: RecordId, text: String },
+    Gmcp { conn: RecordId, package: String, data: JsonValue },
+    // Values for MSDP variables, sent only if the client enabled MSDP.
+    Msdp { conn: RecordId, variables: Vec<(String, JsonValue)> },
+    // Turn the client's local echo on or off, e.g. around password entry.
+    Echo { conn: RecordId, enabled: bool },
+    Pager { conn: RecordId, control: PagerControl },
+    // Ask the portal to disconnect the client, showing it reason first.
+    Disconnect { conn: RecordId, reason: Option<String> }
+}
+
+impl ToPortal {
+    pub fn conn(&self) -> Option<&RecordId> {
+        match self {
+            ToPortal::Hello { .. } | ToPortal::Reject { .. } => None,
+            ToPortal::Text { conn, .. } | ToPortal::Prompt { conn, .. } | ToPortal::Gmcp { conn, .. } |
+            ToPortal::Msdp { conn, .. } | ToPortal::Echo { conn, .. } | ToPortal::Pager { conn, .. } |
+            ToPortal::Disconnect { conn, .. } => Some(conn)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum PagerControl {
+    // Page this text a screenful at a time, sized to the client's window.
+    Show { text: String },
+    // Drop whatever is left of the current page.
+    Clear
+}
@@ -0,0 +1,43 @@
+use std::marker::PhantomData;
+
+use bytes::Bytes;
+use futures::{SinkExt, StreamExt};
+use serde::{Serialize, de::DeserializeOwned};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_util::codec::{Framed, LengthDelimitedCodec};
+
+use crate::transport::{Transport, TransportError};
+
+// A direct link over any byte stream, usually TCP or a Unix socket. Each message is one
+// JSON document in a length-delimited frame.
+pub struct FramedTransport<S, Tx, Rx> {
+    framed: Framed<S, LengthDelimitedCodec>,
+    _types: PhantomData<fn(Tx) -> Rx>
+}
+
+impl<S, Tx, Rx> FramedTransport<S, Tx, Rx> where S: AsyncRead + AsyncWrite + Unpin {
+    pub fn new(stream: S) -> Self {
+        Self {
+            framed: Framed::new(stream, LengthDelimitedCodec::new()),
+            _types: PhantomData
+        }
+    }
+}
+
+impl<S, Tx, Rx> Transport<Tx, Rx> for FramedTransport<S, Tx, Rx>
+where S: AsyncRead + AsyncWrite + Unpin + Send,
+      Tx: Serialize + Send,
+      Rx: DeserializeOwned + Send {
+    async fn send(&mut self, msg: Tx) -> Result<(), TransportError> {
+        let data = serde_json::to_vec(&msg)?;
+        self.framed.send(Bytes::from(data)).await?;
+        Ok(())
+    }
+
+    async fn recv(&mut self) -> Result<Option<Rx>, TransportError> {
+        match self.framed.next().await {
+            Some(frame) => Ok(Some(serde_json::from_slice(&frame?)?)),
+            None => Ok(None)
+        }
+    }
+}
@@ -0,0 +1,256 @@
+use std::{
+    fmt,
+    future::Future
+};
+
+use serde::{Deserialize, Serialize};
+use surrealdb::{Surreal, engine::any::Any};
+use tokio::net::{TcpListener, TcpStream};
+#[cfg(unix)]
+use tokio::net::{UnixListener, UnixStream};
+
+use tracing::info;
+
+use crate::{
+    TotalConf,
+    protocol::{PROTOCOL_VERSION, ToGame, ToPortal}
+};
+use framed::FramedTransport;
+use surreal::{SurrealGameTransport, SurrealPortalTransport};
+
+pub mod framed;
+pub mod surreal;
+
+#[derive(Debug)]
+pub enum TransportError {
+    Db(surrealdb::Error),
+    Io(std::io::Error),
+    // A message couldn't be encoded, or the peer sent one we can't decode.
+    Codec(serde_json::Error),
+    // The peer speaks a different protocol version.
+    Incompatible { ours: u32, theirs: u32 },
+    // The game turned us away.
+    Rejected(String),
+    // The peer sent something other than what the handshake expects.
+    Handshake(String)
+}
+
+impl fmt::Display for TransportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransportError::Db(e) => write!(f, "database error: {}", e),
+            TransportError::Io(e) => write!(f, "I/O error: {}", e),
+            TransportError::Codec(e) => write!(f, "bad message: {}", e),
+            TransportError::Incompatible { ours, theirs } => {
+                write!(f, "peer speaks protocol version {}, but we speak version {}", theirs, ours)
+            },
+            TransportError::Rejected(reason) => write!(f, "rejected by the game: {}", reason),
+            TransportError::Handshake(e) => write!(f, "handshake failed: {}", e)
+        }
+    }
+}
+
+impl std::error::Error for TransportError {}
+
+impl From<surrealdb::Error> for TransportError {
+    fn from(e: surrealdb::Error) -> Self {
+        TransportError::Db(e)
+    }
+}
+
+impl From<std::io::Error> for TransportError {
+    fn from(e: std::io::Error) -> Self {
+        TransportError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for TransportError {
+    fn from(e: serde_json::Error) -> Self {
+        TransportError::Codec(e)
+    }
+}
+
+// One end of the portal<->game link. The portal implements Transport<ToGame, ToPortal>
+// and the game Transport<ToPortal, ToGame>.
+pub trait Transport<Tx, Rx>: Send {
+    fn send(&mut self, msg: Tx) -> impl Future<Output = Result<(), TransportError>> + Send;
+
+    // Waits for the next message from the peer, or None once the link has closed.
+    // Must be cancel-safe, since callers select on it.
+    fn recv(&mut self) -> impl Future<Output = Result<Option<Rx>, TransportError>> + Send;
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TransportKind {
+    // Rows in conn_input and conn_output, plus the conn records themselves.
+    #[default]
+    Surreal,
+    // Length-delimited JSON over TCP. The game listens on transport.address.
+    Tcp,
+    // As tcp, but over a Unix domain socket at transport.address.
+    Unix
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub struct TransportConf {
+    pub kind: TransportKind,
+    // host:port for tcp, or a socket path relative to the configuration directory for unix.
+    pub address: String,
+    // Seconds the portal waits between attempts to reach the game.
+    pub reconnect_interval: u64
+}
+
+// Whichever transport the configuration picked. S is the SurrealDB implementation for
+// this end of the link.
+pub enum Link<S, Tx, Rx> {
+    Surreal(S),
+    Tcp(FramedTransport<TcpStream, Tx, Rx>),
+    #[cfg(unix)]
+    Unix(FramedTransport<UnixStream, Tx, Rx>)
+}
+
+impl<S, Tx, Rx> Transport<Tx, Rx> for Link<S, Tx, Rx>
+where S: Transport<Tx, Rx>,
+      Tx: Serialize + Send,
+      Rx: serde::de::DeserializeOwned + Send {
+    async fn send(&mut self, msg: Tx) -> Result<(), TransportError> {
+        match self {
+            Link::Surreal(t) => t.send(msg).await,
+            Link::Tcp(t) => t.send(msg).await,
+            #[cfg(unix)]
+            Link::Unix(t) => t.send(msg).await
+        }
+    }
+
+    async fn recv(&mut self) -> Result<Option<Rx>, TransportError> {
+        match self {
+            Link::Surreal(t) => t.recv().await,
+            Link::Tcp(t) => t.recv().await,
+            #[cfg(unix)]
+            Link::Unix(t) => t.recv().await
+        }
+    }
+}
+
+pub type PortalLink = Link<SurrealPortalTransport, ToGame, ToPortal>;
+pub type GameLink = Link<SurrealGameTransport, ToPortal, ToGame>;
+
+// Clears the way to bind a unix socket at path. A socket file left behind by an earlier run
+// would make bind fail, but one that still accepts connections belongs to a live process, and
+// anything that isn't a socket isn't ours to remove.
+#[cfg(unix)]
+pub fn remove_stale_socket(path: &std::path::Path) -> std::io::Result<()> {
+    use std::{io::{Error, ErrorKind}, os::unix::fs::FileTypeExt};
+
+    let metadata = match std::fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e)
+    };
+    if !metadata.file_type().is_socket() {
+        return Err(Error::new(ErrorKind::AlreadyExists, format!("{} exists and is not a socket", path.display())));
+    }
+    if std::os::unix::net::UnixStream::connect(path).is_ok() {
+        return Err(Error::new(ErrorKind::AddrInUse, format!("something is already listening on {}", path.display())));
+    }
+    std::fs::remove_file(path)
+}
+
+#[cfg(not(unix))]
+fn unsupported() -> TransportError {
+    TransportError::Io(std::io::Error::new(std::io::ErrorKind::Unsupported, "unix sockets are not available on this platform"))
+}
+
+// Opens the portal's end of the link. For tcp and unix, the game must already be listening
+// and accept our protocol version.
+pub async fn connect_portal(conf: &TotalConf, db: Surreal<Any>) -> Result<PortalLink, TransportError> {
+    // The surreal transport checks the version on every row instead.
+    let mut link = match conf.transport.kind {
+        TransportKind::Surreal => return Ok(Link::Surreal(SurrealPortalTransport::new(db, &conf.portal.id).await?)),
+        TransportKind::Tcp => {
+            let stream = TcpStream::connect(&conf.transport.address).await?;
+            stream.set_nodelay(true)?;
+            Link::Tcp(FramedTransport::new(stream))
+        },
+        #[cfg(unix)]
+        TransportKind::Unix => {
+            let stream = UnixStream::connect(conf.resolve_path(&conf.transport.address)).await?;
+            Link::Unix(FramedTransport::new(stream))
+        },
+        #[cfg(not(unix))]
+        TransportKind::Unix => return Err(unsupported())
+    };
+
+    link.send(ToGame::Hello { version: PROTOCOL_VERSION, portal: conf.portal.id.clone() }).await?;
+    match link.recv().await? {
+        Some(ToPortal::Hello { version }) if version == PROTOCOL_VERSION => Ok(link),
+        Some(ToPortal::Hello { version }) => Err(TransportError::Incompatible { ours: PROTOCOL_VERSION, theirs: version }),
+        Some(ToPortal::Reject { reason }) => Err(TransportError::Rejected(reason)),
+        Some(other) => Err(TransportError::Handshake(format!("expected hello, got {:?}", other))),
+        None => Err(TransportError::Handshake("the game closed the link".to_string()))
+    }
+}
+
+// The game's half of the handshake on a direct link.
+async fn accept_hello(link: &mut GameLink) -> Result<(), TransportError> {
+    match link.recv().await? {
+        Some(ToGame::Hello { version, portal }) => {
+            if version != PROTOCOL_VERSION {
+                let reason = format!("this game speaks protocol version {}, not {}", PROTOCOL_VERSION, version);
+                let _ = link.send(ToPortal::Reject { reason }).await;
+                return Err(TransportError::Incompatible { ours: PROTOCOL_VERSION, theirs: version });
+            }
+            link.send(ToPortal::Hello { version: PROTOCOL_VERSION }).await?;
+            info!("Portal '{}' linked with protocol version {}.", portal, version);
+            Ok(())
+        },
+        Some(other) => Err(TransportError::Handshake(format!("expected hello, got {:?}", other))),
+        None => Err(TransportError::Handshake("the portal closed the link".to_string()))
+    }
+}
+
+// The game's side, which waits for a portal to connect.
+pub enum GameAcceptor {
+    Surreal(Surreal<Any>),
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener)
+}
+
+impl GameAcceptor {
+    pub async fn bind(conf: &TotalConf, db: Surreal<Any>) -> Result<Self, TransportError> {
+        match conf.transport.kind {
+            TransportKind::Surreal => Ok(GameAcceptor::Surreal(db)),
+            TransportKind::Tcp => Ok(GameAcceptor::Tcp(TcpListener::bind(&conf.transport.address).await?)),
+            #[cfg(unix)]
+            TransportKind::Unix => {
+                let path = conf.resolve_path(&conf.transport.address);
+                remove_stale_socket(&path)?;
+                Ok(GameAcceptor::Unix(UnixListener::bind(path)?))
+            },
+            #[cfg(not(unix))]
+            TransportKind::Unix => Err(unsupported())
+        }
+    }
+
+    // Returns the next portal link. With the surreal transport this is immediate, since
+    // the tables are always there.
+    pub async fn accept(&mut self) -> Result<GameLink, TransportError> {
+        let mut link = match self {
+            GameAcceptor::Surreal(db) => return Ok(Link::Surreal(SurrealGameTransport::new(db.clone()).await?)),
+            GameAcceptor::Tcp(listener) => {
+                let (stream, _) = listener.accept().await?;
+                stream.set_nodelay(true)?;
+                Link::Tcp(FramedTransport::new(stream))
+            },
+            #[cfg(unix)]
+            GameAcceptor::Unix(listener) => {
+                let (stream, _) = listener.accept().await?;
+                Link::Unix(FramedTransport::new(stream))
+            }
+        };
+        accept_hello(&mut link).await?;
+        Ok(link)
+    }
+}
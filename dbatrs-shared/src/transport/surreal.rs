@@ -0,0 +1,197 @@
+use std::{
+    collections::HashMap,
+    pin::Pin
+};
+
+use futures::{Stream, StreamExt, stream};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use surrealdb::{
+    Action, Notification, RecordId, Surreal,
+    engine::any::Any
+};
+
+use crate::{
+    ProtocolCapabilities,
+    protocol::{PROTOCOL_VERSION, ToGame, ToPortal},
+    transport::{Transport, TransportError}
+};
+
+// A conn_input or conn_output row: one message, as JSON, tagged with the protocol version
+// of whoever wrote it.
+#[derive(Debug, Clone, Deserialize)]
+struct MessageRow {
+    id: RecordId,
+    version: u32,
+    message: String
+}
+
+// Only the fields the game needs; a deleted record may not carry the rest.
+#[derive(Debug, Clone, Deserialize)]
+struct ConnRow {
+    id: RecordId,
+    #[serde(default)]
+    user: Option<RecordId>,
+    #[serde(default)]
+    capabilities: Option<ProtocolCapabilities>
+}
+
+enum GameRow {
+    Conn(Notification<ConnRow>),
+    Input(Notification<MessageRow>)
+}
+
+type NotificationStream<T> = Pin<Box<dyn Stream<Item = Result<T, surrealdb::Error>> + Send>>;
+
+// Rows are removed once read. This runs in the background so recv stays cancel-safe.
+fn consume(db: &Surreal<Any>, id: RecordId) {
+    let db = db.clone();
+    tokio::spawn(async move {
+        let _ = db.query("DELETE $id").bind(("id", id)).await;
+    });
+}
+
+// Decodes a row, refusing rows from a peer with a different protocol version rather than
+// guessing at their meaning.
+fn decode<T: DeserializeOwned>(row: MessageRow) -> Result<T, TransportError> {
+    if row.version != PROTOCOL_VERSION {
+        return Err(TransportError::Incompatible { ours: PROTOCOL_VERSION, theirs: row.version });
+    }
+    Ok(serde_json::from_str(&row.message)?)
+}
+
+async fn write<T: Serialize>(db: &Surreal<Any>, table: &'static str, conn: RecordId, msg: &T) -> Result<(), TransportError> {
+    let message = serde_json::to_string(msg)?;
+    db.query("CREATE type::table($table) SET user = $conn.user, conn = $conn, version = $version, message = $message")
+        .bind(("table", table))
+        .bind(("conn", conn))
+        .bind(("version", PROTOCOL_VERSION))
+        .bind(("message", message))
+        .await?
+        .check()?;
+    Ok(())
+}
+
+// The portal's end of the table link. Input goes into conn_input; output for this portal's
+// connections arrives as conn_output rows. Connection lifecycle and capabilities need no
+// messages, since the portal already writes them to the conn records.
+pub struct SurrealPortalTransport {
+    db: Surreal<Any>,
+    output: NotificationStream<Notification<MessageRow>>
+}
+
+impl SurrealPortalTransport {
+    // portal must be a valid portal.id: parameters aren't available to a live query's
+    // condition, so it's written into the query.
+    pub async fn new(db: Surreal<Any>, portal: &str) -> Result<Self, TransportError> {
+        let query = format!("LIVE SELECT id, version, message FROM conn_output WHERE conn.portal = '{}'", portal);
+        let mut response = db.query(query).await?;
+        let output = response.stream::<Notification<MessageRow>>(0)?;
+        Ok(Self {
+            db,
+            output: Box::pin(output)
+        })
+    }
+}
+
+impl Transport<ToGame, ToPortal> for SurrealPortalTransport {
+    async fn send(&mut self, msg: ToGame) -> Result<(), TransportError> {
+        let conn = match &msg {
+            ToGame::Input { conn, .. } | ToGame::Gmcp { conn, .. } => conn.clone(),
+            ToGame::Hello { .. } | ToGame::Connected { .. } | ToGame::Capabilities { .. } |
+            ToGame::Disconnected { .. } => return Ok(())
+        };
+        write(&self.db, "conn_input", conn, &msg).await
+    }
+
+    async fn recv(&mut self) -> Result<Option<ToPortal>, TransportError> {
+        loop {
+            let notification = match self.output.next().await {
+                Some(notification) => notification?,
+                None => return Ok(None)
+            };
+            if notification.action != Action::Create {
+                continue;
+            }
+            let row = notification.data;
+            consume(&self.db, row.id.clone());
+            return decode(row).map(Some);
+        }
+    }
+}
+
+// The game's end of the table link. Watches conn records for logins, capability changes
+// and disconnects, and conn_input for input.
+pub struct SurrealGameTransport {
+    db: Surreal<Any>,
+    rows: Pin<Box<dyn Stream<Item = Result<GameRow, surrealdb::Error>> + Send>>,
+    // The last capabilities seen for each conn, since heartbeats update the record too.
+    capabilities: HashMap<RecordId, ProtocolCapabilities>
+}
+
+impl SurrealGameTransport {
+    pub async fn new(db: Surreal<Any>) -> Result<Self, TransportError> {
+        let mut response = db.query("LIVE SELECT * FROM conn; LIVE SELECT id, version, message FROM conn_input;").await?;
+        let conns = response.stream::<Notification<ConnRow>>(0)?
+            .map(|n| n.map(GameRow::Conn));
+        let input = response.stream::<Notification<MessageRow>>(1)?
+            .map(|n| n.map(GameRow::Input));
+        Ok(Self {
+            db,
+            rows: Box::pin(stream::select(conns, input)),
+            capabilities: HashMap::new()
+        })
+    }
+}
+
+impl Transport<ToPortal, ToGame> for SurrealGameTransport {
+    async fn send(&mut self, msg: ToPortal) -> Result<(), TransportError> {
+        let Some(conn) = msg.conn().cloned() else {
+            return Ok(());
+        };
+        write(&self.db, "conn_output", conn, &msg).await
+    }
+
+    async fn recv(&mut self) -> Result<Option<ToGame>, TransportError> {
+        loop {
+            let row = match self.rows.next().await {
+                Some(row) => row?,
+                None => return Ok(None)
+            };
+            match row {
+                GameRow::Conn(n) => {
+                    let conn = n.data;
+                    match n.action {
+                        Action::Create => {
+                            if let Some(user) = conn.user {
+                                return Ok(Some(ToGame::Connected { conn: conn.id, user }));
+                            }
+                        },
+                        Action::Update => {
+                            let Some(capabilities) = conn.capabilities else {
+                                continue;
+                            };
+                            if self.capabilities.get(&conn.id) == Some(&capabilities) {
+                                continue;
+                            }
+                            self.capabilities.insert(conn.id.clone(), capabilities.clone());
+                            return Ok(Some(ToGame::Capabilities { conn: conn.id, capabilities }));
+                        },
+                        Action::Delete => {
+                            self.capabilities.remove(&conn.id);
+                            return Ok(Some(ToGame::Disconnected { conn: conn.id }));
+                        },
+                        _ => {}
+                    }
+                },
+                GameRow::Input(n) => {
+                    if n.action != Action::Create {
+                        continue;
+                    }
+                    let row = n.data;
+                    consume(&self.db, row.id.clone());
+                    return decode(row).map(Some);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf}
+};
+
+use chrono::Local;
+
+use dbatrs_shared::logging::{LogRotation, RollingWriter};
+
+// A fresh directory under the system temp dir, removed again when dropped.
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("dbatrs-logging-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        TempDir(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+// Every file in dir, sorted by name, with its contents.
+fn files(dir: &Path) -> Vec<(String, String)> {
+    let mut out: Vec<(String, String)> = fs::read_dir(dir).unwrap()
+        .map(|e| e.unwrap().path())
+        .map(|p| (p.file_name().unwrap().to_string_lossy().into_owned(), fs::read_to_string(&p).unwrap()))
+        .collect();
+    out.sort();
+    out
+}
+
+#[test]
+fn size_rotation_moves_full_files_aside() {
+    let dir = TempDir::new("size");
+    let mut writer = RollingWriter::new(dir.0.clone(), "portal", LogRotation::Size, 10, 0).unwrap();
+    writer.write_all(b"0123456789").unwrap();
+    writer.write_all(b"abc").unwrap();
+    writer.flush().unwrap();
+
+    let files = files(&dir.0);
+    assert_eq!(files.len(), 2, "{:?}", files);
+    assert!(files[0].0.starts_with(&format!("portal.{}", Local::now().format("%Y%m%d"))), "{:?}", files);
+    assert_eq!(files[0].1, "0123456789");
+    assert_eq!(files[1], ("portal.log".to_string(), "abc".to_string()));
+}
+
+#[test]
+fn pruning_keeps_the_newest_files() {
+    let dir = TempDir::new("prune");
+    // Another binary's logs in the same directory are left alone.
+    fs::create_dir_all(&dir.0).unwrap();
+    fs::write(dir.0.join("game.log"), "game").unwrap();
+
+    let mut writer = RollingWriter::new(dir.0.clone(), "portal", LogRotation::Size, 1, 2).unwrap();
+    for line in ["1", "2", "3", "4", "5"] {
+        writer.write_all(line.as_bytes()).unwrap();
+    }
+    writer.flush().unwrap();
+
+    let contents: Vec<String> = files(&dir.0).into_iter().map(|(_, contents)| contents).collect();
+    // Several files are rotated within the same millisecond, so this also checks that
+    // their names still sort in the order they were written.
+    assert_eq!(contents, ["game", "3", "4", "5"]);
+}
+
+#[test]
+fn existing_files_count_towards_size() {
+    let dir = TempDir::new("reopen");
+    fs::create_dir_all(&dir.0).unwrap();
+    fs::write(dir.0.join("portal.log"), "0123456789").unwrap();
+
+    let mut writer = RollingWriter::new(dir.0.clone(), "portal", LogRotation::Size, 10, 0).unwrap();
+    writer.write_all(b"new").unwrap();
+    assert_eq!(files(&dir.0).len(), 2);
+}
+
+#[test]
+fn daily_and_never_rotation_names() {
+    let dir = TempDir::new("daily");
+    let mut writer = RollingWriter::new(dir.0.clone(), "game", LogRotation::Daily, 1, 1).unwrap();
+    writer.write_all(b"one").unwrap();
+    writer.write_all(b"two").unwrap();
+    let mut never = RollingWriter::new(dir.0.clone(), "portal", LogRotation::Never, 1, 1).unwrap();
+    never.write_all(b"one").unwrap();
+    never.write_all(b"two").unwrap();
+
+    assert_eq!(files(&dir.0), [
+        (format!("game.{}.log", Local::now().format("%Y-%m-%d")), "onetwo".to_string()),
+        ("portal.log".to_string(), "onetwo".to_string())
+    ]);
+}
@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+
+use surrealdb::engine::any;
+
+use dbatrs_shared::{
+    TotalConf, SurrealConf, SurrealEngine,
+    db::connect_root,
+    migrate::{self, MigrateError}
+};
+
+fn memory_conf() -> TotalConf {
+    TotalConf {
+        surreal: SurrealConf {
+            engine: SurrealEngine::Memory,
+            namespace: "test".to_string(),
+            database: "test".to_string(),
+            auto_migrate: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn migrations_apply_once() {
+    let db = connect_root(&memory_conf()).await.unwrap();
+
+    let applied = migrate::migrate(&db).await.unwrap();
+    assert_eq!(applied, migrate::MIGRATIONS.iter().map(|m| m.version).collect::<Vec<_>>());
+    assert_eq!(migrate::current_version(&db).await.unwrap(), migrate::latest_version());
+
+    assert!(migrate::migrate(&db).await.unwrap().is_empty());
+    assert_eq!(migrate::applied(&db).await.unwrap().len(), migrate::MIGRATIONS.len());
+}
+
+#[tokio::test]
+async fn refuses_newer_schema() {
+    let conf = memory_conf();
+    let db = connect_root(&conf).await.unwrap();
+    migrate::migrate(&db).await.unwrap();
+
+    let future = migrate::latest_version() + 1;
+    db.query("CREATE type::thing('schema_migration', $version) SET version = $version, name = 'future', time_applied = time::now()")
+        .bind(("version", future))
+        .await.unwrap()
+        .check().unwrap();
+
+    assert!(matches!(migrate::migrate(&db).await, Err(MigrateError::TooNew { database, .. }) if database == future));
+    assert!(matches!(migrate::startup(&db, &conf.surreal).await, Err(MigrateError::TooNew { .. })));
+}
+
+#[tokio::test]
+async fn startup_without_auto_migrate_reports_pending() {
+    let mut conf = memory_conf();
+    conf.surreal.auto_migrate = false;
+    let db = any::connect("mem://").await.unwrap();
+    db.use_ns("test").use_db("test").await.unwrap();
+
+    assert!(matches!(migrate::startup(&db, &conf.surreal).await, Err(MigrateError::Pending { database: 0, .. })));
+}
+
+#[tokio::test]
+async fn concurrent_migrations_both_succeed() {
+    let db = connect_root(&memory_conf()).await.unwrap();
+    let other = db.clone();
+
+    let (first, second) = tokio::join!(migrate::migrate(&db), migrate::migrate(&other));
+    let mut applied = first.unwrap();
+    applied.extend(second.unwrap());
+    applied.sort();
+    applied.dedup();
+    assert_eq!(applied, migrate::MIGRATIONS.iter().map(|m| m.version).collect::<Vec<_>>());
+    assert_eq!(migrate::applied(&db).await.unwrap().len(), migrate::MIGRATIONS.len());
+}
+
+#[tokio::test]
+async fn create_conn_is_defined() {
+    let db = connect_root(&memory_conf()).await.unwrap();
+    migrate::migrate(&db).await.unwrap();
+    let functions: Option<HashMap<String, String>> = db.query("INFO FOR DB").await.unwrap().take("functions").unwrap();
+    assert!(functions.unwrap().contains_key("create_conn"));
+}
@@ -0,0 +1,132 @@
+use std::time::Duration;
+
+use serde_json::json;
+use surrealdb::RecordId;
+use tokio::time::timeout;
+
+use dbatrs_shared::{
+    TotalConf, SurrealConf, SurrealEngine, ProtocolCapabilities,
+    db::connect_root,
+    migrate,
+    protocol::{PROTOCOL_VERSION, PagerControl, ToGame, ToPortal},
+    transport::{
+        Transport, TransportError,
+        framed::FramedTransport,
+        surreal::{SurrealGameTransport, SurrealPortalTransport}
+    }
+};
+
+async fn recv<Tx, Rx, T: Transport<Tx, Rx>>(transport: &mut T) -> Rx {
+    timeout(Duration::from_secs(5), transport.recv()).await
+        .expect("timed out waiting for a message")
+        .unwrap()
+        .expect("link closed")
+}
+
+#[tokio::test]
+async fn surreal_tables_round_trip() {
+    let conf = TotalConf {
+        surreal: SurrealConf {
+            engine: SurrealEngine::Memory,
+            namespace: "test".to_string(),
+            database: "test".to_string(),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let db = connect_root(&conf).await.unwrap();
+    migrate::migrate(&db).await.unwrap();
+
+    let mut game = SurrealGameTransport::new(db.clone()).await.unwrap();
+    let mut portal = SurrealPortalTransport::new(db.clone(), "portal-a").await.unwrap();
+
+    let user: Option<RecordId> = db.query("CREATE ONLY user SET email = 'a@example.com', password = 'pw' RETURN VALUE id")
+        .await.unwrap().take(0).unwrap();
+    let user = user.unwrap();
+    let conn: Option<RecordId> = db.query("CREATE ONLY conn SET user = $user, ip = '127.0.0.1', portal = 'portal-a' RETURN VALUE id")
+        .bind(("user", user.clone()))
+        .await.unwrap().take(0).unwrap();
+    let conn = conn.unwrap();
+
+    assert_eq!(recv(&mut game).await, ToGame::Connected { conn: conn.clone(), user });
+
+    let capabilities = ProtocolCapabilities::with_custom_defaults();
+    db.query("UPDATE $conn SET capabilities = $capabilities")
+        .bind(("conn", conn.clone()))
+        .bind(("capabilities", capabilities.clone()))
+        .await.unwrap();
+    assert_eq!(recv(&mut game).await, ToGame::Capabilities { conn: conn.clone(), capabilities });
+
+    portal.send(ToGame::Input { conn: conn.clone(), command: "look".to_string() }).await.unwrap();
+    assert_eq!(recv(&mut game).await, ToGame::Input { conn: conn.clone(), command: "look".to_string() });
+
+    game.send(ToPortal::Text { conn: conn.clone(), text: "You see nothing.".to_string() }).await.unwrap();
+    assert_eq!(recv(&mut portal).await, ToPortal::Text { conn: conn.clone(), text: "You see nothing.".to_string() });
+
+    game.send(ToPortal::Gmcp { conn: conn.clone(), package: "Char.Vitals".to_string(), data: json!([1, 2]) }).await.unwrap();
+    assert_eq!(recv(&mut portal).await, ToPortal::Gmcp { conn: conn.clone(), package: "Char.Vitals".to_string(), data: json!([1, 2]) });
+
+    let pager = ToPortal::Pager { conn: conn.clone(), control: PagerControl::Show { text: "one\ntwo".to_string() } };
+    game.send(pager.clone()).await.unwrap();
+    assert_eq!(recv(&mut portal).await, pager);
+
+    let disconnect = ToPortal::Disconnect { conn: conn.clone(), reason: Some("Bye.".to_string()) };
+    game.send(disconnect.clone()).await.unwrap();
+    assert_eq!(recv(&mut portal).await, disconnect);
+
+    // Rows written by a peer on another protocol version are refused, not misread.
+    db.query("CREATE conn_output SET user = $conn.user, conn = $conn, version = $version, message = '{}'")
+        .bind(("conn", conn.clone()))
+        .bind(("version", PROTOCOL_VERSION + 1))
+        .await.unwrap().check().unwrap();
+    let result = timeout(Duration::from_secs(5), portal.recv()).await.expect("timed out waiting for a message");
+    assert!(matches!(result, Err(TransportError::Incompatible { theirs, .. }) if theirs == PROTOCOL_VERSION + 1));
+
+    db.query("DELETE $conn").bind(("conn", conn.clone())).await.unwrap();
+    assert_eq!(recv(&mut game).await, ToGame::Disconnected { conn });
+}
+
+#[tokio::test]
+async fn framed_round_trip() {
+    let (a, b) = tokio::io::duplex(1024);
+    let mut portal: FramedTransport<_, ToGame, ToPortal> = FramedTransport::new(a);
+    let mut game: FramedTransport<_, ToPortal, ToGame> = FramedTransport::new(b);
+    let conn = RecordId::from(("conn", "abc"));
+
+    let input = ToGame::Gmcp { conn: conn.clone(), package: "Core.Hello".to_string(), data: json!({"client": "test"}) };
+    portal.send(input.clone()).await.unwrap();
+    assert_eq!(recv(&mut game).await, input);
+
+    let output = ToPortal::Text { conn, text: "hi".to_string() };
+    game.send(output.clone()).await.unwrap();
+    assert_eq!(recv(&mut portal).await, output);
+
+    drop(game);
+    assert!(portal.recv().await.unwrap().is_none());
+}
+
+#[cfg(unix)]
+#[test]
+fn only_stale_sockets_are_removed() {
+    use dbatrs_shared::transport::remove_stale_socket;
+
+    let path = std::env::temp_dir().join(format!("dbatrs-stale-socket-{}", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    remove_stale_socket(&path).unwrap();
+
+    // A socket something still listens on stays put.
+    let listener = std::os::unix::net::UnixListener::bind(&path).unwrap();
+    assert_eq!(remove_stale_socket(&path).unwrap_err().kind(), std::io::ErrorKind::AddrInUse);
+    assert!(path.exists());
+
+    // Once nothing listens, it's left over from an earlier run.
+    drop(listener);
+    remove_stale_socket(&path).unwrap();
+    assert!(!path.exists());
+
+    // Files that aren't sockets are never removed.
+    std::fs::write(&path, "not a socket").unwrap();
+    assert!(remove_stale_socket(&path).is_err());
+    assert!(path.exists());
+    std::fs::remove_file(&path).unwrap();
+}